@@ -0,0 +1,1039 @@
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+
+#[allow(unused_imports)]
+use alkanes_runtime::{
+    println,
+    stdio::{stdout, Write},
+};
+use alkanes_support::{
+    cellpack::Cellpack,
+    id::AlkaneId,
+    parcel::{AlkaneTransfer, AlkaneTransferParcel},
+    response::CallResponse,
+};
+use anyhow::{anyhow, Result};
+use metashrew_support::{index_pointer::KeyValuePointer, utils::consume_u128};
+use oylswap_library::{
+    concentrated::{
+        add_delta, compress_tick, compute_swap_step, encode_price_sqrt, get_amount_0_delta_signed,
+        get_amount_1_delta_signed, get_liquidity_for_amounts, get_sqrt_ratio_at_tick,
+        get_tick_at_sqrt_ratio, max_sqrt_ratio, min_sqrt_ratio, mul_div,
+        next_initialized_tick_within_one_word, q128, tick_bitmap_position,
+        tick_spacing_to_max_liquidity_per_tick, FEE_DENOMINATOR, MAX_TICK, MIN_TICK,
+    },
+    create_ownership_token, Lock, PoolInfo, StorableU256, U256,
+};
+use std::sync::Arc;
+
+// tick spacing is capped like in uniswap v3 so that a swap step can never skip a whole word
+pub const MAX_TICK_SPACING: u128 = 16384;
+
+// ticks are signed, so they travel through cellpacks as the two's complement of an i128
+pub fn tick_to_u128(tick: i32) -> u128 {
+    tick as i128 as u128
+}
+
+pub fn tick_from_u128(v: u128) -> Result<i32> {
+    i32::try_from(v as i128).map_err(|_| anyhow!("invalid tick"))
+}
+
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct TickInfo {
+    pub liquidity_gross: u128,
+    pub liquidity_net: i128,
+    pub fee_growth_outside_0_x128: U256,
+    pub fee_growth_outside_1_x128: U256,
+}
+
+impl TickInfo {
+    pub fn try_to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.liquidity_gross.to_le_bytes());
+        bytes.extend_from_slice(&self.liquidity_net.to_le_bytes());
+        bytes.extend_from_slice(&self.fee_growth_outside_0_x128.to_le_bytes::<32>());
+        bytes.extend_from_slice(&self.fee_growth_outside_1_x128.to_le_bytes::<32>());
+        bytes
+    }
+
+    pub fn from_vec(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 96 {
+            return Err(anyhow!("Invalid bytes length for TickInfo"));
+        }
+        Ok(TickInfo {
+            liquidity_gross: u128::from_le_bytes(bytes[0..16].try_into()?),
+            liquidity_net: i128::from_le_bytes(bytes[16..32].try_into()?),
+            fee_growth_outside_0_x128: U256::from_le_bytes::<32>(bytes[32..64].try_into()?),
+            fee_growth_outside_1_x128: U256::from_le_bytes::<32>(bytes[64..96].try_into()?),
+        })
+    }
+}
+
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct PositionInfo {
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: u128,
+    pub fee_growth_inside_0_last_x128: U256,
+    pub fee_growth_inside_1_last_x128: U256,
+    pub tokens_owed_0: u128,
+    pub tokens_owed_1: u128,
+}
+
+impl PositionInfo {
+    pub fn try_to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        // ticks are written as i128 so they decode the same way as cellpack inputs
+        bytes.extend_from_slice(&(self.tick_lower as i128).to_le_bytes());
+        bytes.extend_from_slice(&(self.tick_upper as i128).to_le_bytes());
+        bytes.extend_from_slice(&self.liquidity.to_le_bytes());
+        bytes.extend_from_slice(&self.fee_growth_inside_0_last_x128.to_le_bytes::<32>());
+        bytes.extend_from_slice(&self.fee_growth_inside_1_last_x128.to_le_bytes::<32>());
+        bytes.extend_from_slice(&self.tokens_owed_0.to_le_bytes());
+        bytes.extend_from_slice(&self.tokens_owed_1.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_vec(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 144 {
+            return Err(anyhow!("Invalid bytes length for PositionInfo"));
+        }
+        Ok(PositionInfo {
+            tick_lower: tick_from_u128(u128::from_le_bytes(bytes[0..16].try_into()?))?,
+            tick_upper: tick_from_u128(u128::from_le_bytes(bytes[16..32].try_into()?))?,
+            liquidity: u128::from_le_bytes(bytes[32..48].try_into()?),
+            fee_growth_inside_0_last_x128: U256::from_le_bytes::<32>(bytes[48..80].try_into()?),
+            fee_growth_inside_1_last_x128: U256::from_le_bytes::<32>(bytes[80..112].try_into()?),
+            tokens_owed_0: u128::from_le_bytes(bytes[112..128].try_into()?),
+            tokens_owed_1: u128::from_le_bytes(bytes[128..144].try_into()?),
+        })
+    }
+}
+
+// Incoming alkanes of a concentrated pool call, split into the pair tokens and an optional
+// position token proving ownership of an existing position.
+#[derive(Default)]
+pub struct IncomingAmounts {
+    pub amount_0: u128,
+    pub amount_1: u128,
+    pub position: Option<AlkaneId>,
+}
+
+pub trait ConcentratedPoolBase: AlkaneResponder {
+    fn factory(&self) -> Result<AlkaneId> {
+        let ptr = StoragePointer::from_keyword("/factory_id")
+            .get()
+            .as_ref()
+            .clone();
+        let mut cursor = std::io::Cursor::<Vec<u8>>::new(ptr);
+        Ok(AlkaneId::new(
+            consume_u128(&mut cursor)?,
+            consume_u128(&mut cursor)?,
+        ))
+    }
+    fn set_factory(&self, factory_id: AlkaneId) {
+        let mut factory_id_pointer = StoragePointer::from_keyword("/factory_id");
+        factory_id_pointer.set(Arc::new(factory_id.into()));
+    }
+    fn alkanes_for_self(&self) -> Result<(AlkaneId, AlkaneId)> {
+        Ok((
+            StoragePointer::from_keyword("/alkane/0")
+                .get()
+                .as_ref()
+                .clone()
+                .try_into()?,
+            StoragePointer::from_keyword("/alkane/1")
+                .get()
+                .as_ref()
+                .clone()
+                .try_into()?,
+        ))
+    }
+    fn name_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/name")
+    }
+    fn name(&self) -> String {
+        String::from_utf8(self.name_pointer().get().as_ref().clone()).unwrap_or_default()
+    }
+    fn fee_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/feeper1000000")
+    }
+    fn paused_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/paused")
+    }
+    fn paused_state(&self) -> bool {
+        self.paused_pointer().get_value::<u8>() == 1
+    }
+    fn set_paused_state(&self, v: bool) {
+        self.paused_pointer().set_value::<u8>(v as u8);
+    }
+    fn _only_factory_caller(&self) -> Result<()> {
+        if self.context()?.caller != self.factory()? {
+            return Err(anyhow!("Caller is not factory"));
+        }
+        Ok(())
+    }
+    // swaps and deposits stop while the pool is paused, burn and collect keep working so lps can
+    // exit
+    fn _only_not_paused(&self) -> Result<()> {
+        if self.paused_state() {
+            return Err(anyhow!("PAUSED"));
+        }
+        Ok(())
+    }
+    fn fee_per_1000000(&self) -> u128 {
+        self.fee_pointer().get_value::<u128>()
+    }
+    fn tick_spacing_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/tickspacing")
+    }
+    fn tick_spacing(&self) -> Result<i32> {
+        tick_from_u128(self.tick_spacing_pointer().get_value::<u128>())
+    }
+    fn max_liquidity_per_tick(&self) -> Result<u128> {
+        Ok(tick_spacing_to_max_liquidity_per_tick(self.tick_spacing()?))
+    }
+    fn sqrt_price_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/sqrtpricex96")
+    }
+    fn sqrt_price_x96(&self) -> U256 {
+        self.sqrt_price_pointer().get_value::<StorableU256>().into()
+    }
+    fn set_sqrt_price_x96(&self, v: U256) {
+        self.sqrt_price_pointer()
+            .set_value::<StorableU256>(v.into());
+    }
+    fn current_tick_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/tick")
+    }
+    fn current_tick(&self) -> Result<i32> {
+        tick_from_u128(self.current_tick_pointer().get_value::<u128>())
+    }
+    fn set_current_tick(&self, tick: i32) {
+        self.current_tick_pointer()
+            .set_value::<u128>(tick_to_u128(tick));
+    }
+    fn liquidity_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/liquidity")
+    }
+    fn liquidity(&self) -> u128 {
+        self.liquidity_pointer().get_value::<u128>()
+    }
+    fn set_liquidity(&self, v: u128) {
+        self.liquidity_pointer().set_value::<u128>(v);
+    }
+    fn fee_growth_global_pointers(&self) -> (StoragePointer, StoragePointer) {
+        (
+            StoragePointer::from_keyword("/feegrowthglobal0"),
+            StoragePointer::from_keyword("/feegrowthglobal1"),
+        )
+    }
+    fn fee_growth_global(&self) -> (U256, U256) {
+        let (p0, p1) = self.fee_growth_global_pointers();
+        (
+            p0.get_value::<StorableU256>().into(),
+            p1.get_value::<StorableU256>().into(),
+        )
+    }
+    fn set_fee_growth_global(&self, v0: U256, v1: U256) {
+        let (mut p0, mut p1) = self.fee_growth_global_pointers();
+        p0.set_value::<StorableU256>(v0.into());
+        p1.set_value::<StorableU256>(v1.into());
+    }
+    fn tick_info_pointer(&self, tick: i32) -> StoragePointer {
+        StoragePointer::from_keyword("/ticks/").select(&tick.to_le_bytes().to_vec())
+    }
+    fn tick_info(&self, tick: i32) -> Result<TickInfo> {
+        let bytes = self.tick_info_pointer(tick).get();
+        if bytes.len() == 0 {
+            Ok(TickInfo::default())
+        } else {
+            TickInfo::from_vec(&bytes)
+        }
+    }
+    fn set_tick_info(&self, tick: i32, info: &TickInfo) {
+        self.tick_info_pointer(tick)
+            .set(Arc::new(info.try_to_vec()));
+    }
+    fn clear_tick_info(&self, tick: i32) {
+        self.tick_info_pointer(tick).set(Arc::new(Vec::new()));
+    }
+    fn tick_bitmap_pointer(&self, word_pos: i16) -> StoragePointer {
+        StoragePointer::from_keyword("/tickbitmap/").select(&word_pos.to_le_bytes().to_vec())
+    }
+    fn tick_bitmap_word(&self, word_pos: i16) -> U256 {
+        self.tick_bitmap_pointer(word_pos)
+            .get_value::<StorableU256>()
+            .into()
+    }
+    fn flip_tick(&self, tick: i32, tick_spacing: i32) -> Result<()> {
+        if tick % tick_spacing != 0 {
+            return Err(anyhow!("tick must be a multiple of the tick spacing"));
+        }
+        let (word_pos, bit_pos) = tick_bitmap_position(tick / tick_spacing);
+        let word = self.tick_bitmap_word(word_pos) ^ (U256::from(1) << bit_pos as usize);
+        self.tick_bitmap_pointer(word_pos)
+            .set_value::<StorableU256>(word.into());
+        Ok(())
+    }
+    fn position_pointer(&self, position_id: &AlkaneId) -> StoragePointer {
+        StoragePointer::from_keyword("/positions/").select(&position_id.clone().into())
+    }
+    fn position(&self, position_id: &AlkaneId) -> Result<PositionInfo> {
+        let bytes = self.position_pointer(position_id).get();
+        if bytes.len() == 0 {
+            return Err(anyhow!("position {:?} does not exist", position_id));
+        }
+        PositionInfo::from_vec(&bytes)
+    }
+    fn set_position(&self, position_id: &AlkaneId, position: &PositionInfo) {
+        self.position_pointer(position_id)
+            .set(Arc::new(position.try_to_vec()));
+    }
+
+    fn init_pool(
+        &self,
+        alkane_a: AlkaneId,
+        alkane_b: AlkaneId,
+        factory: AlkaneId,
+        fee_per_1000000: u128,
+        tick_spacing: u128,
+        price_numerator: u128,
+        price_denominator: u128,
+    ) -> Result<CallResponse> {
+        self.observe_initialization()?;
+        if fee_per_1000000 >= FEE_DENOMINATOR {
+            return Err(anyhow!("fee must be below {}", FEE_DENOMINATOR));
+        }
+        if tick_spacing == 0 || tick_spacing >= MAX_TICK_SPACING {
+            return Err(anyhow!(
+                "tick spacing must be between 1 and {}",
+                MAX_TICK_SPACING
+            ));
+        }
+        StoragePointer::from_keyword("/alkane/0").set(Arc::new(alkane_a.into()));
+        StoragePointer::from_keyword("/alkane/1").set(Arc::new(alkane_b.into()));
+        self.set_factory(factory);
+        self.fee_pointer().set_value::<u128>(fee_per_1000000);
+        self.tick_spacing_pointer().set_value::<u128>(tick_spacing);
+        let sqrt_price_x96 = encode_price_sqrt(price_numerator, price_denominator)?;
+        self.set_sqrt_price_x96(sqrt_price_x96);
+        self.set_current_tick(get_tick_at_sqrt_ratio(sqrt_price_x96)?);
+        self.set_pool_name()?;
+        self.forward_incoming()
+    }
+
+    fn _alkane_name(&self, id: AlkaneId) -> String {
+        match self.call(
+            &Cellpack {
+                target: id,
+                inputs: vec![99],
+            },
+            &AlkaneTransferParcel(vec![]),
+            self.fuel(),
+        ) {
+            Ok(response) if !response.data.is_empty() => {
+                String::from_utf8_lossy(&response.data).to_string()
+            }
+            _ => format!("{},{}", id.block, id.tx),
+        }
+    }
+
+    fn set_pool_name(&self) -> Result<()> {
+        let (alkane_a, alkane_b) = self.alkanes_for_self()?;
+        let pool_name = format!(
+            "{} / {} CL {}",
+            self._alkane_name(alkane_a),
+            self._alkane_name(alkane_b),
+            self.fee_per_1000000()
+        );
+        self.name_pointer().set(Arc::new(pool_name.into_bytes()));
+        Ok(())
+    }
+
+    fn _check_ticks(&self, tick_lower: i32, tick_upper: i32) -> Result<()> {
+        let tick_spacing = self.tick_spacing()?;
+        if tick_lower >= tick_upper {
+            return Err(anyhow!("TLU: tick_lower must be below tick_upper"));
+        }
+        if tick_lower < MIN_TICK {
+            return Err(anyhow!("TLM: tick_lower too low"));
+        }
+        if tick_upper > MAX_TICK {
+            return Err(anyhow!("TUM: tick_upper too high"));
+        }
+        if tick_lower % tick_spacing != 0 || tick_upper % tick_spacing != 0 {
+            return Err(anyhow!("ticks must be multiples of the tick spacing"));
+        }
+        Ok(())
+    }
+
+    fn _parse_incoming(&self, parcel: &AlkaneTransferParcel) -> Result<IncomingAmounts> {
+        let (token_0, token_1) = self.alkanes_for_self()?;
+        let mut incoming = IncomingAmounts::default();
+        for transfer in parcel.0.iter() {
+            if transfer.id == token_0 {
+                incoming.amount_0 += transfer.value;
+            } else if transfer.id == token_1 {
+                incoming.amount_1 += transfer.value;
+            } else if self.position_pointer(&transfer.id).get().len() != 0
+                && incoming.position.is_none()
+            {
+                incoming.position = Some(transfer.id);
+            } else {
+                return Err(anyhow!("unsupported alkane sent to pool"));
+            }
+        }
+        Ok(incoming)
+    }
+
+    fn _pay(&self, response: &mut CallResponse, id: AlkaneId, value: u128) {
+        if value > 0 {
+            response.alkanes.pay(AlkaneTransfer { id, value });
+        }
+    }
+
+    fn _update_tick(
+        &self,
+        tick: i32,
+        tick_current: i32,
+        liquidity_delta: i128,
+        fee_growth_global_0_x128: U256,
+        fee_growth_global_1_x128: U256,
+        upper: bool,
+    ) -> Result<bool> {
+        let mut info = self.tick_info(tick)?;
+        let liquidity_gross_before = info.liquidity_gross;
+        let liquidity_gross_after = add_delta(liquidity_gross_before, liquidity_delta)?;
+        if liquidity_gross_after > self.max_liquidity_per_tick()? {
+            return Err(anyhow!("LO: liquidity per tick exceeded"));
+        }
+        let flipped = (liquidity_gross_after == 0) != (liquidity_gross_before == 0);
+        if liquidity_gross_before == 0 && tick <= tick_current {
+            // by convention, all growth before a tick was initialized happened below it
+            info.fee_growth_outside_0_x128 = fee_growth_global_0_x128;
+            info.fee_growth_outside_1_x128 = fee_growth_global_1_x128;
+        }
+        info.liquidity_gross = liquidity_gross_after;
+        // when the lower (upper) tick is crossed left to right (right to left), liquidity must be added (removed)
+        info.liquidity_net = if upper {
+            info.liquidity_net.checked_sub(liquidity_delta)
+        } else {
+            info.liquidity_net.checked_add(liquidity_delta)
+        }
+        .ok_or_else(|| anyhow!("liquidity net overflow"))?;
+        self.set_tick_info(tick, &info);
+        Ok(flipped)
+    }
+
+    fn _cross_tick(
+        &self,
+        tick: i32,
+        fee_growth_global_0_x128: U256,
+        fee_growth_global_1_x128: U256,
+    ) -> Result<i128> {
+        let mut info = self.tick_info(tick)?;
+        info.fee_growth_outside_0_x128 =
+            fee_growth_global_0_x128.wrapping_sub(info.fee_growth_outside_0_x128);
+        info.fee_growth_outside_1_x128 =
+            fee_growth_global_1_x128.wrapping_sub(info.fee_growth_outside_1_x128);
+        self.set_tick_info(tick, &info);
+        Ok(info.liquidity_net)
+    }
+
+    fn _fee_growth_inside(
+        &self,
+        tick_lower: i32,
+        tick_upper: i32,
+        tick_current: i32,
+        fee_growth_global_0_x128: U256,
+        fee_growth_global_1_x128: U256,
+    ) -> Result<(U256, U256)> {
+        let lower = self.tick_info(tick_lower)?;
+        let upper = self.tick_info(tick_upper)?;
+        let (below_0, below_1) = if tick_current >= tick_lower {
+            (
+                lower.fee_growth_outside_0_x128,
+                lower.fee_growth_outside_1_x128,
+            )
+        } else {
+            (
+                fee_growth_global_0_x128.wrapping_sub(lower.fee_growth_outside_0_x128),
+                fee_growth_global_1_x128.wrapping_sub(lower.fee_growth_outside_1_x128),
+            )
+        };
+        let (above_0, above_1) = if tick_current < tick_upper {
+            (
+                upper.fee_growth_outside_0_x128,
+                upper.fee_growth_outside_1_x128,
+            )
+        } else {
+            (
+                fee_growth_global_0_x128.wrapping_sub(upper.fee_growth_outside_0_x128),
+                fee_growth_global_1_x128.wrapping_sub(upper.fee_growth_outside_1_x128),
+            )
+        };
+        Ok((
+            fee_growth_global_0_x128
+                .wrapping_sub(below_0)
+                .wrapping_sub(above_0),
+            fee_growth_global_1_x128
+                .wrapping_sub(below_1)
+                .wrapping_sub(above_1),
+        ))
+    }
+
+    // Applies a liquidity change to a position and credits the fees it earned since its last
+    // update. Returns the token amounts owed by the caller when liquidity is added (rounded up),
+    // or owed to the position when liquidity is removed (rounded down).
+    fn _modify_position(
+        &self,
+        position_id: &AlkaneId,
+        liquidity_delta: i128,
+    ) -> Result<(u128, u128)> {
+        let mut position = self.position(position_id)?;
+        let (tick_lower, tick_upper) = (position.tick_lower, position.tick_upper);
+        let tick_spacing = self.tick_spacing()?;
+        let tick_current = self.current_tick()?;
+        let (fee_growth_global_0, fee_growth_global_1) = self.fee_growth_global();
+
+        let mut flipped_lower = false;
+        let mut flipped_upper = false;
+        if liquidity_delta != 0 {
+            flipped_lower = self._update_tick(
+                tick_lower,
+                tick_current,
+                liquidity_delta,
+                fee_growth_global_0,
+                fee_growth_global_1,
+                false,
+            )?;
+            flipped_upper = self._update_tick(
+                tick_upper,
+                tick_current,
+                liquidity_delta,
+                fee_growth_global_0,
+                fee_growth_global_1,
+                true,
+            )?;
+            if flipped_lower {
+                self.flip_tick(tick_lower, tick_spacing)?;
+            }
+            if flipped_upper {
+                self.flip_tick(tick_upper, tick_spacing)?;
+            }
+        } else if position.liquidity == 0 {
+            return Err(anyhow!("NP: position has no liquidity"));
+        }
+
+        let (fee_growth_inside_0, fee_growth_inside_1) = self._fee_growth_inside(
+            tick_lower,
+            tick_upper,
+            tick_current,
+            fee_growth_global_0,
+            fee_growth_global_1,
+        )?;
+        let owed_0: u128 = mul_div(
+            fee_growth_inside_0.wrapping_sub(position.fee_growth_inside_0_last_x128),
+            U256::from(position.liquidity),
+            q128(),
+        )?
+        .try_into()?;
+        let owed_1: u128 = mul_div(
+            fee_growth_inside_1.wrapping_sub(position.fee_growth_inside_1_last_x128),
+            U256::from(position.liquidity),
+            q128(),
+        )?
+        .try_into()?;
+        position.liquidity = add_delta(position.liquidity, liquidity_delta)?;
+        position.fee_growth_inside_0_last_x128 = fee_growth_inside_0;
+        position.fee_growth_inside_1_last_x128 = fee_growth_inside_1;
+        position.tokens_owed_0 = position
+            .tokens_owed_0
+            .checked_add(owed_0)
+            .ok_or_else(|| anyhow!("tokens owed overflow"))?;
+        position.tokens_owed_1 = position
+            .tokens_owed_1
+            .checked_add(owed_1)
+            .ok_or_else(|| anyhow!("tokens owed overflow"))?;
+        self.set_position(position_id, &position);
+
+        // clear any tick data that is no longer needed
+        if liquidity_delta < 0 {
+            if flipped_lower {
+                self.clear_tick_info(tick_lower);
+            }
+            if flipped_upper {
+                self.clear_tick_info(tick_upper);
+            }
+        }
+
+        let mut amount_0 = U256::ZERO;
+        let mut amount_1 = U256::ZERO;
+        if liquidity_delta != 0 {
+            let sqrt_ratio_lower = get_sqrt_ratio_at_tick(tick_lower)?;
+            let sqrt_ratio_upper = get_sqrt_ratio_at_tick(tick_upper)?;
+            if tick_current < tick_lower {
+                // the range is above the current price, only token0 is needed
+                amount_0 =
+                    get_amount_0_delta_signed(sqrt_ratio_lower, sqrt_ratio_upper, liquidity_delta)?;
+            } else if tick_current < tick_upper {
+                let sqrt_price_x96 = self.sqrt_price_x96();
+                amount_0 =
+                    get_amount_0_delta_signed(sqrt_price_x96, sqrt_ratio_upper, liquidity_delta)?;
+                amount_1 =
+                    get_amount_1_delta_signed(sqrt_ratio_lower, sqrt_price_x96, liquidity_delta)?;
+                self.set_liquidity(add_delta(self.liquidity(), liquidity_delta)?);
+            } else {
+                // the range is below the current price, only token1 is needed
+                amount_1 =
+                    get_amount_1_delta_signed(sqrt_ratio_lower, sqrt_ratio_upper, liquidity_delta)?;
+            }
+        }
+        Ok((amount_0.try_into()?, amount_1.try_into()?))
+    }
+
+    // Deposits the incoming pair tokens as liquidity into the position, refunding whatever
+    // could not be used at the current price.
+    fn _deposit(
+        &self,
+        position_id: &AlkaneId,
+        incoming: &IncomingAmounts,
+        response: &mut CallResponse,
+    ) -> Result<()> {
+        let position = self.position(position_id)?;
+        let liquidity = get_liquidity_for_amounts(
+            self.sqrt_price_x96(),
+            get_sqrt_ratio_at_tick(position.tick_lower)?,
+            get_sqrt_ratio_at_tick(position.tick_upper)?,
+            incoming.amount_0,
+            incoming.amount_1,
+        )?;
+        if liquidity == 0 {
+            return Err(anyhow!("INSUFFICIENT_LIQUIDITY_MINTED"));
+        }
+        let (amount_0, amount_1) =
+            self._modify_position(position_id, i128::try_from(liquidity)?)?;
+        let (token_0, token_1) = self.alkanes_for_self()?;
+        self._pay(
+            response,
+            token_0,
+            incoming
+                .amount_0
+                .checked_sub(amount_0)
+                .ok_or_else(|| anyhow!("INSUFFICIENT_AMOUNT_0"))?,
+        );
+        self._pay(
+            response,
+            token_1,
+            incoming
+                .amount_1
+                .checked_sub(amount_1)
+                .ok_or_else(|| anyhow!("INSUFFICIENT_AMOUNT_1"))?,
+        );
+        Ok(())
+    }
+
+    // pays out everything owed to a position and resets its owed balances
+    fn _collect(&self, position_id: &AlkaneId, response: &mut CallResponse) -> Result<()> {
+        let mut position = self.position(position_id)?;
+        let (token_0, token_1) = self.alkanes_for_self()?;
+        self._pay(response, token_0, position.tokens_owed_0);
+        self._pay(response, token_1, position.tokens_owed_1);
+        position.tokens_owed_0 = 0;
+        position.tokens_owed_1 = 0;
+        self.set_position(position_id, &position);
+        Ok(())
+    }
+
+    fn mint_position(&self, tick_lower: u128, tick_upper: u128) -> Result<CallResponse> {
+        Lock::lock(|| {
+            self._only_not_paused()?;
+            let context = self.context()?;
+            let (tick_lower, tick_upper) =
+                (tick_from_u128(tick_lower)?, tick_from_u128(tick_upper)?);
+            self._check_ticks(tick_lower, tick_upper)?;
+            let incoming = self._parse_incoming(&context.incoming_alkanes)?;
+            if incoming.position.is_some() {
+                return Err(anyhow!(
+                    "use IncreaseLiquidity to add to an existing position"
+                ));
+            }
+            let position_token = create_ownership_token(self)?;
+            self.set_position(
+                &position_token.id,
+                &PositionInfo {
+                    tick_lower,
+                    tick_upper,
+                    ..Default::default()
+                },
+            );
+            let mut response = CallResponse::default();
+            self._deposit(&position_token.id, &incoming, &mut response)?;
+            response.alkanes.pay(position_token);
+            Ok(response)
+        })
+    }
+
+    fn increase_liquidity(&self) -> Result<CallResponse> {
+        Lock::lock(|| {
+            self._only_not_paused()?;
+            let context = self.context()?;
+            let incoming = self._parse_incoming(&context.incoming_alkanes)?;
+            let position_id = incoming
+                .position
+                .ok_or_else(|| anyhow!("position token must be sent to the pool"))?;
+            let mut response = CallResponse::default();
+            self._deposit(&position_id, &incoming, &mut response)?;
+            self._pay(
+                &mut response,
+                position_id,
+                self.balance(&context.myself, &position_id),
+            );
+            Ok(response)
+        })
+    }
+
+    fn burn(&self, liquidity: u128) -> Result<CallResponse> {
+        Lock::lock(|| {
+            let context = self.context()?;
+            let incoming = self._parse_incoming(&context.incoming_alkanes)?;
+            let position_id = incoming
+                .position
+                .ok_or_else(|| anyhow!("position token must be sent to the pool"))?;
+            if incoming.amount_0 != 0 || incoming.amount_1 != 0 {
+                return Err(anyhow!("only the position token can be sent to burn"));
+            }
+            let position = self.position(&position_id)?;
+            if liquidity > position.liquidity {
+                return Err(anyhow!("INSUFFICIENT_LIQUIDITY_BURNED"));
+            }
+            if liquidity > 0 {
+                let (amount_0, amount_1) =
+                    self._modify_position(&position_id, -i128::try_from(liquidity)?)?;
+                let mut position = self.position(&position_id)?;
+                position.tokens_owed_0 = position
+                    .tokens_owed_0
+                    .checked_add(amount_0)
+                    .ok_or_else(|| anyhow!("tokens owed overflow"))?;
+                position.tokens_owed_1 = position
+                    .tokens_owed_1
+                    .checked_add(amount_1)
+                    .ok_or_else(|| anyhow!("tokens owed overflow"))?;
+                self.set_position(&position_id, &position);
+            } else if position.liquidity > 0 {
+                // burning zero liquidity just updates the fees owed to the position
+                self._modify_position(&position_id, 0)?;
+            }
+            let mut response = CallResponse::default();
+            self._collect(&position_id, &mut response)?;
+            self._pay(
+                &mut response,
+                position_id,
+                self.balance(&context.myself, &position_id),
+            );
+            Ok(response)
+        })
+    }
+
+    fn collect(&self) -> Result<CallResponse> {
+        self.burn(0)
+    }
+
+    fn _check_deadline(&self, deadline: u128) -> Result<()> {
+        let height = self.height();
+        if deadline != 0 && height as u128 > deadline {
+            Err(anyhow!(format!(
+                "EXPIRED deadline: block height ({}) > deadline({})",
+                height, deadline
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    // the price the swap may not move past, given as a tick since a sqrt price does not fit in
+    // the u128 inputs. A limit at or beyond the bound in the direction of the swap means no limit.
+    fn _sqrt_price_limit_x96(&self, zero_for_one: bool, tick_limit: i32) -> Result<U256> {
+        let sqrt_price_x96 = self.sqrt_price_x96();
+        let sqrt_price_limit_x96 = if zero_for_one {
+            if tick_limit <= MIN_TICK {
+                return Ok(min_sqrt_ratio() + U256::from(1));
+            }
+            get_sqrt_ratio_at_tick(tick_limit)?
+        } else {
+            if tick_limit >= MAX_TICK {
+                return Ok(max_sqrt_ratio() - U256::from(1));
+            }
+            get_sqrt_ratio_at_tick(tick_limit)?
+        };
+        if (zero_for_one && sqrt_price_limit_x96 >= sqrt_price_x96)
+            || (!zero_for_one && sqrt_price_limit_x96 <= sqrt_price_x96)
+        {
+            return Err(anyhow!(
+                "SPL: price limit is on the wrong side of the price"
+            ));
+        }
+        Ok(sqrt_price_limit_x96)
+    }
+
+    // Swaps against the active liquidity, stepping across initialized ticks until the amount is
+    // used up or the price reaches the limit. Returns the total input (including fees) and output
+    // amounts.
+    fn _swap(
+        &self,
+        zero_for_one: bool,
+        exact_in: bool,
+        amount_specified: u128,
+        sqrt_price_limit_x96: U256,
+    ) -> Result<(u128, u128)> {
+        if amount_specified == 0 {
+            return Err(anyhow!("AS: amount specified cannot be zero"));
+        }
+        let fee = self.fee_per_1000000();
+        let tick_spacing = self.tick_spacing()?;
+        let mut sqrt_price_x96 = self.sqrt_price_x96();
+        let mut tick = self.current_tick()?;
+        let mut liquidity = self.liquidity();
+        let (mut fee_growth_global_0, mut fee_growth_global_1) = self.fee_growth_global();
+        let mut amount_remaining = U256::from(amount_specified);
+        let mut amount_in = U256::ZERO;
+        let mut amount_out = U256::ZERO;
+
+        while !amount_remaining.is_zero() && sqrt_price_x96 != sqrt_price_limit_x96 {
+            let sqrt_price_start_x96 = sqrt_price_x96;
+            let compressed = compress_tick(tick, tick_spacing);
+            let (word_pos, _) = tick_bitmap_position(if zero_for_one {
+                compressed
+            } else {
+                compressed + 1
+            });
+            let (tick_next, initialized) = next_initialized_tick_within_one_word(
+                self.tick_bitmap_word(word_pos),
+                tick,
+                tick_spacing,
+                zero_for_one,
+            );
+            // the bitmap is not aware of the tick bounds
+            let tick_next = tick_next.clamp(MIN_TICK, MAX_TICK);
+            let sqrt_price_next_x96 = get_sqrt_ratio_at_tick(tick_next)?;
+            let sqrt_price_target_x96 = if (zero_for_one
+                && sqrt_price_next_x96 < sqrt_price_limit_x96)
+                || (!zero_for_one && sqrt_price_next_x96 > sqrt_price_limit_x96)
+            {
+                sqrt_price_limit_x96
+            } else {
+                sqrt_price_next_x96
+            };
+
+            let step = compute_swap_step(
+                sqrt_price_x96,
+                sqrt_price_target_x96,
+                liquidity,
+                amount_remaining,
+                exact_in,
+                fee,
+            )?;
+            sqrt_price_x96 = step.sqrt_price_next_x96;
+            if exact_in {
+                amount_remaining -= step.amount_in + step.fee_amount;
+            } else {
+                amount_remaining -= step.amount_out;
+            }
+            amount_in += step.amount_in + step.fee_amount;
+            amount_out += step.amount_out;
+
+            if liquidity > 0 {
+                let growth = mul_div(step.fee_amount, q128(), U256::from(liquidity))?;
+                if zero_for_one {
+                    fee_growth_global_0 = fee_growth_global_0.wrapping_add(growth);
+                } else {
+                    fee_growth_global_1 = fee_growth_global_1.wrapping_add(growth);
+                }
+            }
+
+            if sqrt_price_x96 == sqrt_price_next_x96 {
+                // we reached the next tick, so shift liquidity if it is initialized
+                if initialized {
+                    let liquidity_net =
+                        self._cross_tick(tick_next, fee_growth_global_0, fee_growth_global_1)?;
+                    let liquidity_net = if zero_for_one {
+                        liquidity_net
+                            .checked_neg()
+                            .ok_or_else(|| anyhow!("liquidity net overflow"))?
+                    } else {
+                        liquidity_net
+                    };
+                    liquidity = add_delta(liquidity, liquidity_net)?;
+                }
+                tick = if zero_for_one {
+                    tick_next - 1
+                } else {
+                    tick_next
+                };
+            } else if sqrt_price_x96 != sqrt_price_start_x96 {
+                tick = get_tick_at_sqrt_ratio(sqrt_price_x96)?;
+            }
+        }
+
+        if !exact_in && !amount_remaining.is_zero() {
+            if sqrt_price_x96 == sqrt_price_limit_x96 {
+                return Err(anyhow!("SPL: price limit reached before the output"));
+            }
+            return Err(anyhow!("INSUFFICIENT_LIQUIDITY"));
+        }
+        self.set_sqrt_price_x96(sqrt_price_x96);
+        self.set_current_tick(tick);
+        self.set_liquidity(liquidity);
+        self.set_fee_growth_global(fee_growth_global_0, fee_growth_global_1);
+        Ok((amount_in.try_into()?, amount_out.try_into()?))
+    }
+
+    fn _single_incoming(&self, parcel: &AlkaneTransferParcel) -> Result<(AlkaneId, u128)> {
+        let (token_0, token_1) = self.alkanes_for_self()?;
+        if parcel.0.len() != 1 || (parcel.0[0].id != token_0 && parcel.0[0].id != token_1) {
+            return Err(anyhow!("must send exactly one of the pool alkanes to swap"));
+        }
+        Ok((parcel.0[0].id, parcel.0[0].value))
+    }
+
+    // a swap stopped by the price limit refunds the input it did not use
+    fn swap_exact_input(
+        &self,
+        amount_out_min: u128,
+        tick_limit: u128,
+        deadline: u128,
+    ) -> Result<CallResponse> {
+        Lock::lock(|| {
+            self._only_not_paused()?;
+            self._check_deadline(deadline)?;
+            let context = self.context()?;
+            let (token_in, amount_in_max) = self._single_incoming(&context.incoming_alkanes)?;
+            let (token_0, token_1) = self.alkanes_for_self()?;
+            let zero_for_one = token_in == token_0;
+            let sqrt_price_limit_x96 =
+                self._sqrt_price_limit_x96(zero_for_one, tick_from_u128(tick_limit)?)?;
+            let (amount_in, amount_out) =
+                self._swap(zero_for_one, true, amount_in_max, sqrt_price_limit_x96)?;
+            if amount_out < amount_out_min {
+                return Err(anyhow!("predicate failed: insufficient output"));
+            }
+            let mut response = CallResponse::default();
+            self._pay(
+                &mut response,
+                if zero_for_one { token_1 } else { token_0 },
+                amount_out,
+            );
+            self._pay(&mut response, token_in, amount_in_max - amount_in);
+            Ok(response)
+        })
+    }
+
+    fn swap_exact_output(
+        &self,
+        amount_out: u128,
+        tick_limit: u128,
+        deadline: u128,
+    ) -> Result<CallResponse> {
+        Lock::lock(|| {
+            self._only_not_paused()?;
+            self._check_deadline(deadline)?;
+            let context = self.context()?;
+            let (token_in, amount_in_max) = self._single_incoming(&context.incoming_alkanes)?;
+            let (token_0, token_1) = self.alkanes_for_self()?;
+            let zero_for_one = token_in == token_0;
+            let sqrt_price_limit_x96 =
+                self._sqrt_price_limit_x96(zero_for_one, tick_from_u128(tick_limit)?)?;
+            let (amount_in, amount_out) =
+                self._swap(zero_for_one, false, amount_out, sqrt_price_limit_x96)?;
+            if amount_in > amount_in_max {
+                return Err(anyhow!(format!(
+                    "EXCESSIVE_INPUT_AMOUNT: required({}) > amount_in_max({})",
+                    amount_in, amount_in_max
+                )));
+            }
+            let mut response = CallResponse::default();
+            self._pay(
+                &mut response,
+                if zero_for_one { token_1 } else { token_0 },
+                amount_out,
+            );
+            self._pay(&mut response, token_in, amount_in_max - amount_in);
+            Ok(response)
+        })
+    }
+
+    fn forward_incoming(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    fn get_paused(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = (self.paused_state() as u128).to_le_bytes().to_vec();
+        Ok(response)
+    }
+
+    fn set_paused(&self, paused: u128) -> Result<CallResponse> {
+        self._only_factory_caller()?;
+        let context = self.context()?;
+        if paused > 1 {
+            return Err(anyhow!("paused must be 0 or 1"));
+        }
+        self.set_paused_state(paused == 1);
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    fn get_slot0(&self) -> Result<CallResponse> {
+        Lock::view(|| {
+            let context = self.context()?;
+            let mut response = CallResponse::forward(&context.incoming_alkanes);
+            let mut bytes = Vec::new();
+            bytes.extend_from_slice(&self.sqrt_price_x96().to_le_bytes::<32>());
+            bytes.extend_from_slice(&tick_to_u128(self.current_tick()?).to_le_bytes());
+            bytes.extend_from_slice(&self.liquidity().to_le_bytes());
+            bytes.extend_from_slice(&self.fee_per_1000000().to_le_bytes());
+            bytes.extend_from_slice(&tick_to_u128(self.tick_spacing()?).to_le_bytes());
+            response.data = bytes;
+            Ok(response)
+        })
+    }
+
+    fn get_position(&self, position_id: AlkaneId) -> Result<CallResponse> {
+        Lock::view(|| {
+            let context = self.context()?;
+            let mut response = CallResponse::forward(&context.incoming_alkanes);
+            response.data = self.position(&position_id)?.try_to_vec();
+            Ok(response)
+        })
+    }
+
+    fn get_name(&self) -> Result<CallResponse> {
+        Lock::view(|| {
+            let context = self.context()?;
+            let mut response = CallResponse::forward(&context.incoming_alkanes);
+            response.data = self.name().into_bytes().to_vec();
+            Ok(response)
+        })
+    }
+
+    fn pool_details(&self) -> Result<CallResponse> {
+        Lock::view(|| {
+            let context = self.context()?;
+            let (token_a, token_b) = self.alkanes_for_self()?;
+            // concentrated pools have no fungible LP token, so the active liquidity stands in for
+            // the total supply
+            let pool_info = PoolInfo {
+                token_a,
+                token_b,
+                reserve_a: self.balance(&context.myself, &token_a),
+                reserve_b: self.balance(&context.myself, &token_b),
+                total_supply: self.liquidity(),
+                pool_name: self.name(),
+            };
+            let mut response = CallResponse::forward(&context.incoming_alkanes.clone());
+            response.data = pool_info.try_to_vec();
+            Ok(response)
+        })
+    }
+}
@@ -19,9 +19,12 @@ use metashrew_support::{
     index_pointer::KeyValuePointer,
     utils::{consume_sized_int, consume_u128},
 };
-use oylswap_library::{PoolInfo, U256};
+use oylswap_library::{
+    concentrated::U512, PoolInfo, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
+    PROTOCOL_FEE_AMOUNT_PER_1000000, RECEIVE_EXTCALL_OPCODE, U256,
+};
 use protorune_support::utils::consensus_decode;
-use std::{collections::BTreeSet, sync::Arc};
+use std::{cmp::min, collections::BTreeSet, sync::Arc};
 
 pub fn join_ids(a: AlkaneId, b: AlkaneId) -> Vec<u8> {
     let mut result: Vec<u8> = a.into();
@@ -34,6 +37,23 @@ pub fn join_ids_from_tuple(v: (AlkaneId, AlkaneId)) -> Vec<u8> {
     join_ids(v.0, v.1)
 }
 
+// pool templates a factory can deploy, each backed by its own upgradeable beacon
+pub const POOL_TEMPLATE_CONSTANT_PRODUCT: u128 = 0;
+pub const POOL_TEMPLATE_CONCENTRATED: u128 = 1;
+pub const POOL_TEMPLATE_STABLE: u128 = 2;
+pub const POOL_TEMPLATE_WEIGHTED: u128 = 3;
+
+// bounds of the on-chain path search: the longest route in pools, and how many intermediate
+// tokens the owner can allow routes to go through
+pub const MAX_ROUTE_HOPS: usize = 3;
+pub const MAX_ROUTE_TOKENS: usize = 8;
+
+// split swaps give each path a share of the input, in parts of this denominator
+pub const SPLIT_SHARE_DENOMINATOR: u128 = 10000;
+
+// quotes report the price impact of each hop in parts of this denominator
+pub const PRICE_IMPACT_DENOMINATOR: u128 = 1_000_000;
+
 pub trait AMMFactoryBase: AuthenticatedResponder {
     fn pool_id(&self) -> Result<u128> {
         let ptr = StoragePointer::from_keyword("/pool_factory_id")
@@ -63,11 +83,98 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
         let mut ptr = StoragePointer::from_keyword("/beacon_id");
         ptr.set(Arc::new(v.into()));
     }
-    fn pool_pointer(&self, a: &AlkaneId, b: &AlkaneId) -> StoragePointer {
-        StoragePointer::from_keyword("/pools/")
+    // constant product pools of the default fee tier keep the key they had before fee tiers and
+    // templates existed. Stable and weighted pools only exist in the default tier and are keyed by
+    // template so that they can live next to the constant product pool of the pair.
+    fn pool_pointer(
+        &self,
+        a: &AlkaneId,
+        b: &AlkaneId,
+        template: u128,
+        fee_tier: u128,
+    ) -> StoragePointer {
+        let pointer = StoragePointer::from_keyword("/pools/")
             .select(&a.clone().into())
             .keyword("/")
-            .select(&b.clone().into())
+            .select(&b.clone().into());
+        if template != POOL_TEMPLATE_CONSTANT_PRODUCT {
+            pointer
+                .keyword("/template/")
+                .select(&template.to_le_bytes().to_vec())
+        } else if fee_tier == DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000 {
+            pointer
+        } else {
+            pointer
+                .keyword("/")
+                .select(&fee_tier.to_le_bytes().to_vec())
+        }
+    }
+    // the fee tier of constant product pools created outside the default tier
+    fn fee_tier_of_pointer(&self, pool_id: &AlkaneId) -> StoragePointer {
+        StoragePointer::from_keyword("/fee_tier_of/").select(&pool_id.clone().into())
+    }
+    fn fee_tier_of(&self, pool_id: &AlkaneId) -> u128 {
+        let ptr = self.fee_tier_of_pointer(pool_id);
+        if ptr.get().len() == 0 {
+            DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000
+        } else {
+            ptr.get_value::<u128>()
+        }
+    }
+    // the protocol keeps its fee in tiers at or above the default one and the share of the fee it
+    // takes in the default tier below it, so tiers under the protocol fee still pay lps
+    fn protocol_fee_for_tier(&self, fee_tier: u128) -> u128 {
+        min(
+            PROTOCOL_FEE_AMOUNT_PER_1000000,
+            fee_tier * PROTOCOL_FEE_AMOUNT_PER_1000000 / DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
+        )
+    }
+    fn fee_tier_enabled_pointer(&self, fee_tier: u128) -> StoragePointer {
+        StoragePointer::from_keyword("/fee_tier_enabled/").select(&fee_tier.to_le_bytes().to_vec())
+    }
+    fn fee_tier_enabled(&self, fee_tier: u128) -> bool {
+        fee_tier == DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000
+            || self.fee_tier_enabled_pointer(fee_tier).get_value::<u8>() == 1
+    }
+    // the default tier followed by every tier the owner has enabled, even if disabled since, so
+    // the router keeps finding the pools created in it
+    fn fee_tiers(&self) -> Vec<u128> {
+        let mut fee_tiers = vec![DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000];
+        let bytes = StoragePointer::from_keyword("/fee_tiers").get();
+        for chunk in bytes.chunks_exact(16) {
+            fee_tiers.push(u128::from_le_bytes(chunk.try_into().unwrap()));
+        }
+        fee_tiers
+    }
+    fn set_fee_tier(&self, fee_tier: u128, enabled: u128) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        if fee_tier == DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000 {
+            return Err(anyhow!("the default fee tier is always enabled"));
+        }
+        if fee_tier == 0 || fee_tier >= 1000000 {
+            return Err(anyhow!("fee tier must be between 1 and 999999 per 1000000"));
+        }
+        if !self.fee_tiers().contains(&fee_tier) {
+            let mut fee_tiers_pointer = StoragePointer::from_keyword("/fee_tiers");
+            let mut bytes = fee_tiers_pointer.get().as_ref().clone();
+            bytes.extend_from_slice(&fee_tier.to_le_bytes());
+            fee_tiers_pointer.set(Arc::new(bytes));
+        }
+        self.fee_tier_enabled_pointer(fee_tier)
+            .set_value::<u8>(if enabled == 0 { 0 } else { 1 });
+        Ok(CallResponse::forward(&context.incoming_alkanes.clone()))
+    }
+    // fee tiers new pools can be created in, 16 bytes each
+    fn get_fee_tiers(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes.clone());
+        for fee_tier in self.fee_tiers() {
+            if self.fee_tier_enabled(fee_tier) {
+                response.data.extend_from_slice(&fee_tier.to_le_bytes());
+            }
+        }
+        Ok(response)
     }
     fn _pull_incoming(&self, context: &mut Context) -> Option<AlkaneTransfer> {
         let i = context
@@ -99,35 +206,132 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
         token_b: AlkaneId,
         amount_a: u128,
         amount_b: u128,
+    ) -> Result<CallResponse> {
+        self._create_pool(
+            token_a,
+            token_b,
+            amount_a,
+            amount_b,
+            POOL_TEMPLATE_CONSTANT_PRODUCT,
+            vec![],
+            DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
+        )
+    }
+
+    // a constant product pool charging fee_tier per 1000000 on swaps. A pair can have one pool in
+    // each enabled fee tier
+    fn create_new_pool_with_fee_tier(
+        &self,
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+        amount_a: u128,
+        amount_b: u128,
+        fee_tier: u128,
+    ) -> Result<CallResponse> {
+        if !self.fee_tier_enabled(fee_tier) {
+            return Err(anyhow!(format!("fee tier {} is not enabled", fee_tier)));
+        }
+        self._create_pool(
+            token_a,
+            token_b,
+            amount_a,
+            amount_b,
+            POOL_TEMPLATE_CONSTANT_PRODUCT,
+            vec![],
+            fee_tier,
+        )
+    }
+
+    // a pair has at most one stable pool, registered next to its constant product pools, and the
+    // router quotes it along with them
+    fn create_new_stable_pool(
+        &self,
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+        amount_a: u128,
+        amount_b: u128,
+        amp: u128,
+    ) -> Result<CallResponse> {
+        oylswap_library::stableswap::check_amp(amp)?;
+        self._create_pool(
+            token_a,
+            token_b,
+            amount_a,
+            amount_b,
+            POOL_TEMPLATE_STABLE,
+            vec![amp],
+            DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
+        )
+    }
+
+    // weight_a is the weight of token_a per 1000, token_b gets the rest. Like stable pools, a pair
+    // has at most one weighted pool.
+    fn create_new_weighted_pool(
+        &self,
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+        amount_a: u128,
+        amount_b: u128,
+        weight_a: u128,
+    ) -> Result<CallResponse> {
+        let weight_b = oylswap_library::weighted::WEIGHT_DENOMINATOR.saturating_sub(weight_a);
+        oylswap_library::weighted::check_weights(weight_a, weight_b)?;
+        // the pool stores the weights in the order of the sorted alkanes
+        let (a, _) = oylswap_library::sort_alkanes((token_a.clone(), token_b.clone()));
+        let weights = if a == token_a {
+            vec![weight_a, weight_b]
+        } else {
+            vec![weight_b, weight_a]
+        };
+        self._create_pool(
+            token_a,
+            token_b,
+            amount_a,
+            amount_b,
+            POOL_TEMPLATE_WEIGHTED,
+            weights,
+            DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
+        )
+    }
+
+    // creates a pool in the pair registry and seeds it with the initial liquidity. `params` are
+    // appended to the init inputs for templates that take extra parameters.
+    fn _create_pool(
+        &self,
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+        amount_a: u128,
+        amount_b: u128,
+        template: u128,
+        params: Vec<u128>,
+        fee_tier: u128,
     ) -> Result<CallResponse> {
         let context = self.context()?;
         if token_a == token_b {
             return Err(anyhow!("tokens to create the pool cannot be the same"));
         }
+        // stable and weighted pools swap at their fee per 1000 and do not answer
+        // SetFeesPerMillion, so they stay in the default fee tier
+        if fee_tier != DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000
+            && matches!(template, POOL_TEMPLATE_STABLE | POOL_TEMPLATE_WEIGHTED)
+        {
+            return Err(anyhow!(
+                "only constant product pools can be created in a fee tier"
+            ));
+        }
         if amount_a == 0 || amount_b == 0 {
             return Err(anyhow!("input amount cannot be zero"));
         }
         let (a, b) = oylswap_library::sort_alkanes((token_a.clone(), token_b.clone()));
         let pool_id = AlkaneId::new(2, self.sequence());
         // check if this pool already exists
-        if self.pool_pointer(&a, &b).get().len() == 0 {
-            self.pool_pointer(&a, &b).set(Arc::new(pool_id.into()));
+        if self.pool_pointer(&a, &b, template, fee_tier).get().len() == 0 {
+            self.pool_pointer(&a, &b, template, fee_tier)
+                .set(Arc::new(pool_id.into()));
         } else {
             return Err(anyhow!("pool already exists"));
         }
 
-        // Add the new pool to the registry
-        let length = self.all_pools_length()?;
-
-        // Store the pool ID at the current index
-        StoragePointer::from_keyword("/all_pools/")
-            .select(&length.to_le_bytes().to_vec())
-            .set(Arc::new(pool_id.into()));
-
-        // Update the length
-        StoragePointer::from_keyword("/all_pools_length")
-            .set(Arc::new((length + 1).to_le_bytes().to_vec()));
-
         let input_transfer = AlkaneTransferParcel(vec![
             AlkaneTransfer {
                 id: token_a,
@@ -139,7 +343,62 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
             },
         ]);
 
-        let beacon_id = self.beacon_id()?;
+        let mut init_inputs = vec![
+            0,
+            a.block,
+            a.tx,
+            b.block,
+            b.tx,
+            context.myself.block,
+            context.myself.tx,
+        ];
+        init_inputs.extend(params);
+        let result = self._deploy_pool(pool_id, template, init_inputs, &input_transfer)?;
+        if fee_tier != DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000 {
+            self.fee_tier_of_pointer(&pool_id)
+                .set_value::<u128>(fee_tier);
+            self.call(
+                &Cellpack {
+                    target: pool_id,
+                    inputs: vec![38, fee_tier, self.protocol_fee_for_tier(fee_tier)],
+                },
+                &AlkaneTransferParcel::default(),
+                self.fuel(),
+            )?;
+        }
+        self._return_leftovers(context.myself, result, context.incoming_alkanes)
+    }
+
+    // Deploys a beacon proxy for the given pool template at `pool_id`, which must be the next
+    // sequence, adds it to the pool registry and initializes it with `init_inputs`.
+    fn _deploy_pool(
+        &self,
+        pool_id: AlkaneId,
+        template: u128,
+        init_inputs: Vec<u128>,
+        input_transfer: &AlkaneTransferParcel,
+    ) -> Result<CallResponse> {
+        let beacon_id = self.pool_template(template)?;
+
+        // Add the new pool to the registry. Concentrated pools answer the pool opcodes differently,
+        // so they get their own list and GetAllPools only returns pools with reserves.
+        let (list, length_key) = if template == POOL_TEMPLATE_CONCENTRATED {
+            ("/all_concentrated_pools/", "/all_concentrated_pools_length")
+        } else {
+            ("/all_pools/", "/all_pools_length")
+        };
+        let length = StoragePointer::from_keyword(length_key).get_value::<u128>();
+
+        // Store the pool ID at the current index
+        StoragePointer::from_keyword(list)
+            .select(&length.to_le_bytes().to_vec())
+            .set(Arc::new(pool_id.into()));
+
+        // Update the length
+        StoragePointer::from_keyword(length_key).set(Arc::new((length + 1).to_le_bytes().to_vec()));
+
+        self.pool_template_of_pointer(&pool_id)
+            .set_value::<u128>(template);
 
         // deploys proxy
         self.call(
@@ -155,44 +414,225 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
         )?;
 
         // inits proxy
-        let result = self.call(
+        self.call(
             &Cellpack {
                 target: AlkaneId {
                     block: 2,
                     tx: pool_id.tx,
                 },
-                inputs: vec![
-                    0,
-                    a.block,
-                    a.tx,
-                    b.block,
-                    b.tx,
-                    context.myself.block,
-                    context.myself.tx,
-                ],
+                inputs: init_inputs,
             },
-            &input_transfer,
+            input_transfer,
             self.fuel(),
+        )
+    }
+
+    fn pool_template_pointer(&self, template: u128) -> StoragePointer {
+        StoragePointer::from_keyword("/pool_templates/").select(&template.to_le_bytes().to_vec())
+    }
+
+    // beacon used for new pools of the given template. The constant product template falls back
+    // to the beacon set at init so factories deployed before templates existed keep working.
+    fn pool_template(&self, template: u128) -> Result<AlkaneId> {
+        let ptr = self.pool_template_pointer(template).get().as_ref().clone();
+        if ptr.len() == 0 {
+            if template == POOL_TEMPLATE_CONSTANT_PRODUCT {
+                return self.beacon_id();
+            }
+            return Err(anyhow!("pool template {} is not registered", template));
+        }
+        let mut cursor = std::io::Cursor::<Vec<u8>>::new(ptr);
+        Ok(AlkaneId::new(
+            consume_u128(&mut cursor)?,
+            consume_u128(&mut cursor)?,
+        ))
+    }
+
+    fn set_pool_template(&self, template: u128, beacon_id: AlkaneId) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        self.pool_template_pointer(template)
+            .set(Arc::new(beacon_id.into()));
+        Ok(CallResponse::forward(&context.incoming_alkanes.clone()))
+    }
+
+    fn pool_template_of_pointer(&self, pool_id: &AlkaneId) -> StoragePointer {
+        StoragePointer::from_keyword("/pool_template_of/").select(&pool_id.clone().into())
+    }
+
+    // pools created before templates existed have nothing stored and are constant product
+    fn pool_template_of(&self, pool_id: &AlkaneId) -> u128 {
+        self.pool_template_of_pointer(pool_id).get_value::<u128>()
+    }
+
+    fn concentrated_pool_pointer(
+        &self,
+        a: &AlkaneId,
+        b: &AlkaneId,
+        fee_per_1000000: u128,
+    ) -> StoragePointer {
+        StoragePointer::from_keyword("/concentrated_pools/")
+            .select(&a.clone().into())
+            .keyword("/")
+            .select(&b.clone().into())
+            .keyword("/")
+            .select(&fee_per_1000000.to_le_bytes().to_vec())
+    }
+
+    // the initial price is given as units of token_b per unit of token_a
+    fn create_concentrated_pool(
+        &self,
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+        fee_per_1000000: u128,
+        tick_spacing: u128,
+        price_numerator: u128,
+        price_denominator: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        if token_a == token_b {
+            return Err(anyhow!("tokens to create the pool cannot be the same"));
+        }
+        let (a, b) = oylswap_library::sort_alkanes((token_a.clone(), token_b.clone()));
+        // the pool stores the price of token1 in terms of token0
+        let (price_numerator, price_denominator) = if a == token_a {
+            (price_numerator, price_denominator)
+        } else {
+            (price_denominator, price_numerator)
+        };
+        let pool_id = AlkaneId::new(2, self.sequence());
+        if self
+            .concentrated_pool_pointer(&a, &b, fee_per_1000000)
+            .get()
+            .len()
+            == 0
+        {
+            self.concentrated_pool_pointer(&a, &b, fee_per_1000000)
+                .set(Arc::new(pool_id.into()));
+        } else {
+            return Err(anyhow!("pool already exists"));
+        }
+        let result = self._deploy_pool(
+            pool_id,
+            POOL_TEMPLATE_CONCENTRATED,
+            vec![
+                0,
+                a.block,
+                a.tx,
+                b.block,
+                b.tx,
+                context.myself.block,
+                context.myself.tx,
+                fee_per_1000000,
+                tick_spacing,
+                price_numerator,
+                price_denominator,
+            ],
+            &AlkaneTransferParcel::default(),
         )?;
         self._return_leftovers(context.myself, result, context.incoming_alkanes)
     }
 
+    fn find_existing_concentrated_pool_id(
+        &self,
+        alkane_a: AlkaneId,
+        alkane_b: AlkaneId,
+        fee_per_1000000: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        let (a, b) = oylswap_library::sort_alkanes((alkane_a, alkane_b));
+        let ptr = self
+            .concentrated_pool_pointer(&a, &b, fee_per_1000000)
+            .get();
+        if ptr.len() == 0 {
+            return Err(anyhow!(format!(
+                "the concentrated pool {:?} {:?} with fee {} doesn't exist in the factory",
+                alkane_a, alkane_b, fee_per_1000000
+            )));
+        }
+        let mut response = CallResponse::forward(&context.incoming_alkanes.clone());
+        response.data = ptr.as_ref().clone();
+        Ok(response)
+    }
+
+    // the pool of the pair in the default fee tier
     fn _find_existing_pool_id(&self, alkane_a: AlkaneId, alkane_b: AlkaneId) -> Result<AlkaneId> {
+        self._find_existing_pool_id_for_fee_tier(
+            alkane_a,
+            alkane_b,
+            DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
+        )
+    }
+
+    fn _find_existing_pool_id_for_fee_tier(
+        &self,
+        alkane_a: AlkaneId,
+        alkane_b: AlkaneId,
+        fee_tier: u128,
+    ) -> Result<AlkaneId> {
         let (a, b) = oylswap_library::sort_alkanes((alkane_a, alkane_b));
-        if self.pool_pointer(&a, &b).get().len() == 0 {
+        let ptr = self
+            .pool_pointer(&a, &b, POOL_TEMPLATE_CONSTANT_PRODUCT, fee_tier)
+            .get();
+        if ptr.len() == 0 {
+            if fee_tier == DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000 {
+                return Err(anyhow!(format!(
+                    "the pool {:?} {:?} doesn't exist in the factory",
+                    alkane_a, alkane_b
+                )));
+            }
             return Err(anyhow!(format!(
-                "the pool {:?} {:?} doesn't exist in the factory",
-                alkane_a, alkane_b
+                "the pool {:?} {:?} with fee tier {} doesn't exist in the factory",
+                alkane_a, alkane_b, fee_tier
             )));
         }
-        let mut cursor =
-            std::io::Cursor::<Vec<u8>>::new(self.pool_pointer(&a, &b).get().as_ref().clone());
+        let mut cursor = std::io::Cursor::<Vec<u8>>::new(ptr.as_ref().clone());
         Ok(AlkaneId::new(
             consume_sized_int::<u128>(&mut cursor)?,
             consume_sized_int::<u128>(&mut cursor)?,
         ))
     }
 
+    // the stable or weighted pool of the pair, if it has one
+    fn _find_template_pool_id(
+        &self,
+        alkane_a: AlkaneId,
+        alkane_b: AlkaneId,
+        template: u128,
+    ) -> Option<AlkaneId> {
+        let (a, b) = oylswap_library::sort_alkanes((alkane_a, alkane_b));
+        let ptr = self
+            .pool_pointer(&a, &b, template, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000)
+            .get();
+        if ptr.len() == 0 {
+            return None;
+        }
+        let mut cursor = std::io::Cursor::<Vec<u8>>::new(ptr.as_ref().clone());
+        Some(AlkaneId::new(
+            consume_sized_int::<u128>(&mut cursor).ok()?,
+            consume_sized_int::<u128>(&mut cursor).ok()?,
+        ))
+    }
+
+    // every pool of the pair: one constant product pool per fee tier it exists in, then the
+    // stable and weighted pools
+    fn _find_pools_for_pair(&self, alkane_a: AlkaneId, alkane_b: AlkaneId) -> Vec<AlkaneId> {
+        let mut pools: Vec<AlkaneId> = self
+            .fee_tiers()
+            .into_iter()
+            .filter_map(|fee_tier| {
+                self._find_existing_pool_id_for_fee_tier(alkane_a, alkane_b, fee_tier)
+                    .ok()
+            })
+            .collect();
+        pools.extend(
+            [POOL_TEMPLATE_STABLE, POOL_TEMPLATE_WEIGHTED]
+                .into_iter()
+                .filter_map(|template| self._find_template_pool_id(alkane_a, alkane_b, template)),
+        );
+        pools
+    }
+
     fn find_existing_pool_id(
         &self,
         alkane_a: AlkaneId,
@@ -204,6 +644,20 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
         response.data = self._find_existing_pool_id(alkane_a, alkane_b)?.into();
         Ok(response)
     }
+
+    fn find_existing_pool_id_for_fee_tier(
+        &self,
+        alkane_a: AlkaneId,
+        alkane_b: AlkaneId,
+        fee_tier: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes.clone());
+        response.data = self
+            ._find_existing_pool_id_for_fee_tier(alkane_a, alkane_b, fee_tier)?
+            .into();
+        Ok(response)
+    }
     // Get the total number of pools
     fn all_pools_length(&self) -> Result<u128> {
         let ptr = StoragePointer::from_keyword("/all_pools_length")
@@ -263,6 +717,31 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
         response.data = all_pools_data;
         Ok(response)
     }
+    // same layout as GetAllPools
+    fn get_all_concentrated_pools(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let length =
+            StoragePointer::from_keyword("/all_concentrated_pools_length").get_value::<u128>();
+        let mut response = CallResponse::forward(&context.incoming_alkanes.clone());
+        let mut all_pools_data = Vec::new();
+        all_pools_data.extend_from_slice(&length.to_le_bytes());
+        for i in 0..length {
+            let ptr = StoragePointer::from_keyword("/all_concentrated_pools/")
+                .select(&i.to_le_bytes().to_vec())
+                .get();
+            all_pools_data.extend_from_slice(ptr.as_ref());
+        }
+        response.data = all_pools_data;
+        Ok(response)
+    }
+
+    fn get_pool_template(&self, pool_id: AlkaneId) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes.clone());
+        response.data = self.pool_template_of(&pool_id).to_le_bytes().to_vec();
+        Ok(response)
+    }
+
     fn get_num_pools(&self) -> Result<CallResponse> {
         let context = self.context()?;
         let mut response = CallResponse::forward(&context.incoming_alkanes.clone());
@@ -311,9 +790,13 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
         Ok((reserve_a, reserve_b))
     }
 
-    fn _get_reserves_ordered(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<(u128, u128)> {
+    fn _get_reserves_ordered(
+        &self,
+        pool: AlkaneId,
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+    ) -> Result<(u128, u128)> {
         let (token_0, _) = oylswap_library::sort_alkanes((token_a, token_b));
-        let pool = self._find_existing_pool_id(token_a, token_b)?;
         let (reserve_0, reserve_1) = self._get_reserves(pool)?;
         if token_a == token_0 {
             Ok((reserve_0, reserve_1))
@@ -345,42 +828,101 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
         Ok(response)
     }
 
+    // like _return_leftovers, but what the call produced is paid to `to` through
+    // RECEIVE_EXTCALL_OPCODE while unused inputs still go back to the caller. A zero `to` is the
+    // caller
+    fn _return_leftovers_to(
+        &self,
+        to: AlkaneId,
+        myself: AlkaneId,
+        result: CallResponse,
+        input_alkanes: AlkaneTransferParcel,
+    ) -> Result<CallResponse> {
+        if to == AlkaneId::new(0, 0) {
+            return self._return_leftovers(myself, result, input_alkanes);
+        }
+        let input_ids: BTreeSet<AlkaneId> = input_alkanes.0.iter().map(|t| t.id).collect();
+        let output_ids: BTreeSet<AlkaneId> = result
+            .alkanes
+            .0
+            .iter()
+            .map(|t| t.id)
+            .filter(|id| !input_ids.contains(id))
+            .collect();
+        let mut outputs = AlkaneTransferParcel::default();
+        for id in output_ids {
+            let value = self.balance(&myself, &id);
+            if value != 0 {
+                outputs.pay(AlkaneTransfer { id, value });
+            }
+        }
+        if !outputs.0.is_empty() {
+            let mut inputs = vec![RECEIVE_EXTCALL_OPCODE];
+            inputs.append(&mut self.context()?.caller.into());
+            self.call(&Cellpack { target: to, inputs }, &outputs, self.fuel())?;
+        }
+        self._return_leftovers(myself, CallResponse::default(), input_alkanes)
+    }
+
+    // amounts to deposit in the current ratio of the pool, using as much of the desired amounts as possible
+    fn _quote_liquidity_amounts(
+        &self,
+        pool: AlkaneId,
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+        amount_a_desired: u128,
+        amount_b_desired: u128,
+        amount_a_min: u128,
+        amount_b_min: u128,
+    ) -> Result<(u128, u128)> {
+        let (previous_a, previous_b) = self._get_reserves_ordered(pool, token_a, token_b)?;
+        if previous_a == 0 && previous_b == 0 {
+            return Ok((amount_a_desired, amount_b_desired));
+        }
+        let amount_b_optimal: u128 = (U256::from(amount_a_desired) * U256::from(previous_b)
+            / U256::from(previous_a))
+        .try_into()?;
+        if amount_b_optimal <= amount_b_desired {
+            if amount_b_optimal < amount_b_min {
+                return Err(anyhow!("INSUFFICIENT_B_AMOUNT"));
+            }
+            Ok((amount_a_desired, amount_b_optimal))
+        } else {
+            let amount_a_optimal = (U256::from(amount_b_desired) * U256::from(previous_a)
+                / U256::from(previous_b))
+            .try_into()?;
+            if amount_a_optimal > amount_a_desired || amount_a_optimal < amount_a_min {
+                return Err(anyhow!("INSUFFICIENT_A_AMOUNT"));
+            }
+            Ok((amount_a_optimal, amount_b_desired))
+        }
+    }
+
     // note: for now, token_a and token_b must be in alphabetical order
     fn add_liquidity(
         &self,
         token_a: AlkaneId,
         token_b: AlkaneId,
+        fee_tier: u128,
         amount_a_desired: u128,
         amount_b_desired: u128,
         amount_a_min: u128,
         amount_b_min: u128,
+        to: AlkaneId,
         deadline: u128,
     ) -> Result<CallResponse> {
         let context = self.context()?;
         self._check_deadline(self.height(), deadline)?;
-        let pool = self._find_existing_pool_id(token_a, token_b)?;
-        let (previous_a, previous_b) = self._get_reserves_ordered(token_a, token_b)?;
-        let (amount_a, amount_b) = if previous_a == 0 && previous_b == 0 {
-            (amount_a_desired, amount_b_desired)
-        } else {
-            let amount_b_optimal: u128 = (U256::from(amount_a_desired) * U256::from(previous_b)
-                / U256::from(previous_a))
-            .try_into()?;
-            if amount_b_optimal <= amount_b_desired {
-                if amount_b_optimal < amount_b_min {
-                    return Err(anyhow!("INSUFFICIENT_B_AMOUNT"));
-                }
-                (amount_a_desired, amount_b_optimal)
-            } else {
-                let amount_a_optimal = (U256::from(amount_b_desired) * U256::from(previous_a)
-                    / U256::from(previous_b))
-                .try_into()?;
-                if amount_a_optimal > amount_a_desired || amount_a_optimal < amount_a_min {
-                    return Err(anyhow!("INSUFFICIENT_A_AMOUNT"));
-                }
-                (amount_a_optimal, amount_b_desired)
-            }
-        };
+        let pool = self._find_existing_pool_id_for_fee_tier(token_a, token_b, fee_tier)?;
+        let (amount_a, amount_b) = self._quote_liquidity_amounts(
+            pool,
+            token_a,
+            token_b,
+            amount_a_desired,
+            amount_b_desired,
+            amount_a_min,
+            amount_b_min,
+        )?;
 
         let input_transfer = AlkaneTransferParcel(vec![
             AlkaneTransfer {
@@ -397,22 +939,109 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
             inputs: vec![1],
         };
         let result = self.call(&cellpack, &input_transfer, self.fuel())?;
-        self._return_leftovers(context.myself, result, context.incoming_alkanes)
+        self._return_leftovers_to(to, context.myself, result, context.incoming_alkanes)
     }
 
-    fn burn(
+    // adds liquidity from `amount_in` of token_a alone: swaps the right part of it for token_b
+    // in the pool, then deposits both sides. Returns the lp tokens and any dust left over
+    fn zap_in(
         &self,
         token_a: AlkaneId,
         token_b: AlkaneId,
-        liquidity: u128,
-        amount_a_min: u128,
+        fee_tier: u128,
+        amount_in: u128,
+        min_lp_out: u128,
+        to: AlkaneId,
+        deadline: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        self._check_deadline(self.height(), deadline)?;
+        let pool = self._find_existing_pool_id_for_fee_tier(token_a, token_b, fee_tier)?;
+        if self.pool_template_of(&pool) != POOL_TEMPLATE_CONSTANT_PRODUCT {
+            return Err(anyhow!(
+                "zaps are only supported for constant product pools"
+            ));
+        }
+        let (reserve_in, reserve_out) = self._get_reserves_ordered(pool, token_a, token_b)?;
+        let total_fee = self._get_total_fee_for_pool(pool)?;
+        let swap_amount =
+            oylswap_library::get_zap_swap_amount_per_1000000(amount_in, reserve_in, total_fee)?;
+        let amount_out = oylswap_library::get_amount_out_per_1000000(
+            swap_amount,
+            reserve_in,
+            reserve_out,
+            total_fee,
+        )?;
+        self._swap(
+            &vec![swap_amount, amount_out],
+            &vec![token_a, token_b],
+            &vec![pool],
+        )?;
+
+        let (amount_a, amount_b) = self._quote_liquidity_amounts(
+            pool,
+            token_a,
+            token_b,
+            amount_in - swap_amount,
+            amount_out,
+            0,
+            0,
+        )?;
+        let input_transfer = AlkaneTransferParcel(vec![
+            AlkaneTransfer {
+                id: token_a,
+                value: amount_a,
+            },
+            AlkaneTransfer {
+                id: token_b,
+                value: amount_b,
+            },
+        ]);
+        let result = self.call(
+            &Cellpack {
+                target: pool,
+                inputs: vec![1],
+            },
+            &input_transfer,
+            self.fuel(),
+        )?;
+        let minted: u128 = result
+            .alkanes
+            .0
+            .iter()
+            .filter(|transfer| transfer.id == pool)
+            .map(|transfer| transfer.value)
+            .sum();
+        if minted < min_lp_out {
+            return Err(anyhow!(format!(
+                "INSUFFICIENT_LP_AMOUNT: minted({}) < min_lp_out({})",
+                minted, min_lp_out
+            )));
+        }
+        // the token_b dust comes from the swap, so it is not among the incoming alkanes
+        let mut returned = context.incoming_alkanes.clone();
+        returned.0.push(AlkaneTransfer {
+            id: token_b,
+            value: 0,
+        });
+        self._return_leftovers_to(to, context.myself, result, returned)
+    }
+
+    fn burn(
+        &self,
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+        fee_tier: u128,
+        liquidity: u128,
+        amount_a_min: u128,
         amount_b_min: u128,
+        to: AlkaneId,
         deadline: u128,
     ) -> Result<CallResponse> {
         let context = self.context()?;
         self._check_deadline(self.height(), deadline)?;
         let parcel = context.incoming_alkanes;
-        let pool = self._find_existing_pool_id(token_a, token_b)?;
+        let pool = self._find_existing_pool_id_for_fee_tier(token_a, token_b, fee_tier)?;
         let input_transfer = AlkaneTransferParcel(vec![AlkaneTransfer {
             id: pool,
             value: liquidity,
@@ -437,14 +1066,79 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
                 return Err(anyhow!("INSUFFICIENT_A_AMOUNT"));
             }
         }
-        self._return_leftovers(context.myself, result, parcel)
+        self._return_leftovers_to(to, context.myself, result, parcel)
     }
 
-    fn _swap(&self, amounts: &Vec<u128>, path: &Vec<AlkaneId>) -> Result<CallResponse> {
+    // burns `liquidity` and swaps the token_b share for token_a, so everything is paid out in token_a
+    fn zap_out(
+        &self,
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+        fee_tier: u128,
+        liquidity: u128,
+        amount_out_min: u128,
+        to: AlkaneId,
+        deadline: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        self._check_deadline(self.height(), deadline)?;
+        let parcel = context.incoming_alkanes;
+        let pool = self._find_existing_pool_id_for_fee_tier(token_a, token_b, fee_tier)?;
+        let result = self.call(
+            &Cellpack {
+                target: pool,
+                inputs: vec![2],
+            },
+            &AlkaneTransferParcel(vec![AlkaneTransfer {
+                id: pool,
+                value: liquidity,
+            }]),
+            self.fuel(),
+        )?;
+        let burned = |id: AlkaneId| -> u128 {
+            result
+                .alkanes
+                .0
+                .iter()
+                .filter(|transfer| transfer.id == id)
+                .map(|transfer| transfer.value)
+                .sum()
+        };
+        let (amount_a, amount_b) = (burned(token_a), burned(token_b));
+        let amount_swapped = if amount_b == 0 {
+            0
+        } else {
+            self._get_amount_out_for_pool(pool, amount_b, token_b, token_a)?
+        };
+        // too little token_b to buy any token_a is returned as is
+        if amount_swapped != 0 {
+            self._swap(
+                &vec![amount_b, amount_swapped],
+                &vec![token_b, token_a],
+                &vec![pool],
+            )?;
+        }
+        let amount_out = amount_a + amount_swapped;
+        if amount_out < amount_out_min {
+            return Err(anyhow!(format!(
+                "INSUFFICIENT_OUTPUT_AMOUNT: received({}) < amount_out_min({})",
+                amount_out, amount_out_min
+            )));
+        }
+        self._return_leftovers_to(to, context.myself, result, parcel)
+    }
+
+    // swaps along `path`, hop i going through pools[i - 1]
+    fn _swap(
+        &self,
+        amounts: &Vec<u128>,
+        path: &Vec<AlkaneId>,
+        pools: &Vec<AlkaneId>,
+    ) -> Result<CallResponse> {
         let context = self.context()?;
         let mut response: CallResponse = CallResponse::default();
         for i in 1..path.len() {
-            let pool = self._find_existing_pool_id(path[i - 1], path[i])?;
+            let pool = pools[i - 1];
             let (token_a, _) = oylswap_library::sort_alkanes((path[i - 1], path[i]));
             let amount_out = amounts[i];
             let (amount_0_out, amount_1_out) = if path[i - 1] == token_a {
@@ -472,12 +1166,25 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
         Ok(response)
     }
 
-    fn _get_total_fee_for_pool(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<u128> {
-        let pool_id = self._find_existing_pool_id(token_a, token_b)?;
+    // per 1000000. Stable and weighted pools only exist in the default tier, their curves take
+    // the fee per 1000
+    fn _get_total_fee_for_pool(&self, pool_id: AlkaneId) -> Result<u128> {
+        let response = self.call(
+            &Cellpack {
+                target: pool_id,
+                inputs: vec![37],
+            },
+            &AlkaneTransferParcel::default(),
+            self.fuel(),
+        )?;
+        Ok(u128::from_le_bytes(response.data[..16].try_into()?))
+    }
+
+    fn _get_amplification_for_pool(&self, pool_id: AlkaneId) -> Result<u128> {
         let response = self.call(
             &Cellpack {
                 target: pool_id,
-                inputs: vec![20],
+                inputs: vec![22],
             },
             &AlkaneTransferParcel::default(),
             self.fuel(),
@@ -485,30 +1192,290 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
         Ok(u128::from_le_bytes(response.data[..16].try_into()?))
     }
 
-    fn get_amounts_out(&self, amount_in: u128, path: &Vec<AlkaneId>) -> Result<Vec<u128>> {
+    // weights of the pool in the order of the given alkanes
+    fn _get_weights_ordered(
+        &self,
+        pool_id: AlkaneId,
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+    ) -> Result<(u128, u128)> {
+        let response = self.call(
+            &Cellpack {
+                target: pool_id,
+                inputs: vec![23],
+            },
+            &AlkaneTransferParcel::default(),
+            self.fuel(),
+        )?;
+        let weight_0 = u128::from_le_bytes(response.data[..16].try_into()?);
+        let weight_1 = u128::from_le_bytes(response.data[16..32].try_into()?);
+        let (token_0, _) = oylswap_library::sort_alkanes((token_a, token_b));
+        if token_a == token_0 {
+            Ok((weight_0, weight_1))
+        } else {
+            Ok((weight_1, weight_0))
+        }
+    }
+
+    // quotes a single hop with the curve of the given pool
+    fn _get_amount_out_for_pool(
+        &self,
+        pool: AlkaneId,
+        amount_in: u128,
+        token_in: AlkaneId,
+        token_out: AlkaneId,
+    ) -> Result<u128> {
+        let (reserve_in, reserve_out) = self._get_reserves_ordered(pool, token_in, token_out)?;
+        let total_fee = self._get_total_fee_for_pool(pool)?;
+        match self.pool_template_of(&pool) {
+            POOL_TEMPLATE_STABLE => oylswap_library::stableswap::get_amount_out(
+                amount_in,
+                reserve_in,
+                reserve_out,
+                total_fee / 1000,
+                self._get_amplification_for_pool(pool)?,
+            ),
+            POOL_TEMPLATE_WEIGHTED => {
+                let (weight_in, weight_out) =
+                    self._get_weights_ordered(pool, token_in, token_out)?;
+                oylswap_library::weighted::get_amount_out(
+                    amount_in,
+                    reserve_in,
+                    reserve_out,
+                    total_fee / 1000,
+                    weight_in,
+                    weight_out,
+                )
+            }
+            _ => oylswap_library::get_amount_out_per_1000000(
+                amount_in,
+                reserve_in,
+                reserve_out,
+                total_fee,
+            ),
+        }
+    }
+
+    fn _get_amount_in_for_pool(
+        &self,
+        pool: AlkaneId,
+        amount_out: u128,
+        token_in: AlkaneId,
+        token_out: AlkaneId,
+    ) -> Result<u128> {
+        let (reserve_in, reserve_out) = self._get_reserves_ordered(pool, token_in, token_out)?;
+        let total_fee = self._get_total_fee_for_pool(pool)?;
+        match self.pool_template_of(&pool) {
+            POOL_TEMPLATE_STABLE => oylswap_library::stableswap::get_amount_in(
+                amount_out,
+                reserve_in,
+                reserve_out,
+                total_fee / 1000,
+                self._get_amplification_for_pool(pool)?,
+            ),
+            POOL_TEMPLATE_WEIGHTED => {
+                let (weight_in, weight_out) =
+                    self._get_weights_ordered(pool, token_in, token_out)?;
+                oylswap_library::weighted::get_amount_in(
+                    amount_out,
+                    reserve_in,
+                    reserve_out,
+                    total_fee / 1000,
+                    weight_in,
+                    weight_out,
+                )
+            }
+            _ => oylswap_library::get_amount_in_per_1000000(
+                amount_out,
+                reserve_in,
+                reserve_out,
+                total_fee,
+            ),
+        }
+    }
+
+    // quotes a single hop through whichever fee tier of the pair pays out the most
+    fn _get_amount_out(
+        &self,
+        amount_in: u128,
+        token_in: AlkaneId,
+        token_out: AlkaneId,
+    ) -> Result<(u128, AlkaneId)> {
+        let mut best: Option<(u128, AlkaneId)> = None;
+        let mut last_error = None;
+        for pool in self._find_pools_for_pair(token_in, token_out) {
+            match self._get_amount_out_for_pool(pool, amount_in, token_in, token_out) {
+                Ok(amount_out) => {
+                    if best.map_or(true, |(best_out, _)| amount_out > best_out) {
+                        best = Some((amount_out, pool));
+                    }
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match (best, last_error) {
+            (Some(best), _) => Ok(best),
+            (None, Some(e)) => Err(e),
+            (None, None) => Err(anyhow!(format!(
+                "the pool {:?} {:?} doesn't exist in the factory",
+                token_in, token_out
+            ))),
+        }
+    }
+
+    // quotes a single hop through whichever fee tier of the pair takes the least input
+    fn _get_amount_in(
+        &self,
+        amount_out: u128,
+        token_in: AlkaneId,
+        token_out: AlkaneId,
+    ) -> Result<(u128, AlkaneId)> {
+        let mut best: Option<(u128, AlkaneId)> = None;
+        let mut last_error = None;
+        for pool in self._find_pools_for_pair(token_in, token_out) {
+            match self._get_amount_in_for_pool(pool, amount_out, token_in, token_out) {
+                Ok(amount_in) => {
+                    if best.map_or(true, |(best_in, _)| amount_in < best_in) {
+                        best = Some((amount_in, pool));
+                    }
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match (best, last_error) {
+            (Some(best), _) => Ok(best),
+            (None, Some(e)) => Err(e),
+            (None, None) => Err(anyhow!(format!(
+                "the pool {:?} {:?} doesn't exist in the factory",
+                token_in, token_out
+            ))),
+        }
+    }
+
+    // amounts along the path and the pool each hop goes through
+    fn _get_amounts_out(
+        &self,
+        amount_in: u128,
+        path: &Vec<AlkaneId>,
+    ) -> Result<(Vec<u128>, Vec<AlkaneId>)> {
         let n = path.len();
         if n < 2 {
             return Err(anyhow!("Routing path must be at least two alkanes long"));
         }
         let mut amounts: Vec<u128> = vec![0; n];
+        let mut pools: Vec<AlkaneId> = Vec::with_capacity(n - 1);
         amounts[0] = amount_in;
         for i in 1..n {
-            let (reserve_in, reserve_out) = self._get_reserves_ordered(path[i - 1], path[i])?;
-            let total_fee = self._get_total_fee_for_pool(path[i - 1], path[i])?;
-            amounts[i] = oylswap_library::get_amount_out(
+            let (amount_out, pool) = self._get_amount_out(amounts[i - 1], path[i - 1], path[i])?;
+            amounts[i] = amount_out;
+            pools.push(pool);
+        }
+        Ok((amounts, pools))
+    }
+
+    // output per unit of input at the pool's current marginal price, before fees, as a fraction
+    fn _get_spot_price_for_pool(
+        &self,
+        pool: AlkaneId,
+        token_in: AlkaneId,
+        token_out: AlkaneId,
+    ) -> Result<(U512, U512)> {
+        let (reserve_in, reserve_out) = self._get_reserves_ordered(pool, token_in, token_out)?;
+        match self.pool_template_of(&pool) {
+            POOL_TEMPLATE_STABLE => {
+                let amp = self._get_amplification_for_pool(pool)?;
+                let (numerator, denominator) =
+                    oylswap_library::stableswap::get_marginal_price(reserve_in, reserve_out, amp)?;
+                Ok((U512::from(numerator), U512::from(denominator)))
+            }
+            POOL_TEMPLATE_WEIGHTED => {
+                let (weight_in, weight_out) =
+                    self._get_weights_ordered(pool, token_in, token_out)?;
+                Ok((
+                    U512::from(reserve_out) * U512::from(weight_in),
+                    U512::from(reserve_in) * U512::from(weight_out),
+                ))
+            }
+            _ => Ok((U512::from(reserve_out), U512::from(reserve_in))),
+        }
+    }
+
+    // how much worse than the marginal price a hop pays out once its fee is taken, in parts of
+    // PRICE_IMPACT_DENOMINATOR
+    fn _get_price_impact_for_pool(
+        &self,
+        pool: AlkaneId,
+        amount_in: u128,
+        amount_out: u128,
+        token_in: AlkaneId,
+        token_out: AlkaneId,
+    ) -> Result<u128> {
+        let (price_numerator, price_denominator) =
+            self._get_spot_price_for_pool(pool, token_in, token_out)?;
+        let total_fee = self._get_total_fee_for_pool(pool)?;
+        // both sides are scaled by 1000000 to keep the fee exact
+        let spot_amount_out = (U512::from(amount_in) * U512::from(1000000 - total_fee))
+            .checked_mul(price_numerator)
+            .ok_or_else(|| anyhow!("math overflow"))?
+            / price_denominator;
+        let amount_out = U512::from(amount_out) * U512::from(1000000);
+        if spot_amount_out.is_zero() || amount_out >= spot_amount_out {
+            return Ok(0);
+        }
+        Ok(
+            ((spot_amount_out - amount_out) * U512::from(PRICE_IMPACT_DENOMINATOR)
+                / spot_amount_out)
+                .try_into()?,
+        )
+    }
+
+    // the amounts along the path, prefixed by their count, then for each hop the pool, the fee
+    // it charges in its input alkane and its price impact
+    fn _quote_response(
+        &self,
+        amounts: &Vec<u128>,
+        path: &Vec<AlkaneId>,
+        pools: &Vec<AlkaneId>,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes.clone());
+        response
+            .data
+            .extend_from_slice(&(amounts.len() as u128).to_le_bytes());
+        for amount in amounts {
+            response.data.extend_from_slice(&amount.to_le_bytes());
+        }
+        for i in 1..path.len() {
+            let pool = pools[i - 1];
+            let fee: u128 = (U256::from(amounts[i - 1])
+                * U256::from(self._get_total_fee_for_pool(pool)?)
+                / U256::from(1000000))
+            .try_into()?;
+            let price_impact = self._get_price_impact_for_pool(
+                pool,
                 amounts[i - 1],
-                reserve_in,
-                reserve_out,
-                total_fee,
+                amounts[i],
+                path[i - 1],
+                path[i],
             )?;
+            response.data.extend_from_slice(&pool.block.to_le_bytes());
+            response.data.extend_from_slice(&pool.tx.to_le_bytes());
+            response.data.extend_from_slice(&fee.to_le_bytes());
+            response.data.extend_from_slice(&price_impact.to_le_bytes());
         }
-        Ok(amounts)
+        Ok(response)
+    }
+
+    fn get_amounts_out(&self, amount_in: u128, path: Vec<AlkaneId>) -> Result<CallResponse> {
+        let (amounts, pools) = self._get_amounts_out(amount_in, &path)?;
+        self._quote_response(&amounts, &path, &pools)
     }
 
     fn swap_exact_tokens_for_tokens_implicit(
         &self,
         path: Vec<AlkaneId>,
         amount_out_min: u128,
+        to: AlkaneId,
         deadline: u128,
     ) -> Result<CallResponse> {
         let context = self.context()?;
@@ -519,13 +1486,13 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
         }
         let mut full_path = vec![parcel.0[0].id.clone()];
         full_path.extend(&path);
-        let amounts = self.get_amounts_out(parcel.0[0].value, &full_path)?;
+        let (amounts, pools) = self._get_amounts_out(parcel.0[0].value, &full_path)?;
         if amounts[amounts.len() - 1] < amount_out_min {
             return Err(anyhow!("predicate failed: insufficient output"));
         }
 
-        let result = self._swap(&amounts, &full_path)?;
-        self._return_leftovers(context.myself, result, parcel)
+        let result = self._swap(&amounts, &full_path, &pools)?;
+        self._return_leftovers_to(to, context.myself, result, parcel)
     }
 
     fn swap_exact_tokens_for_tokens(
@@ -533,36 +1500,46 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
         path: Vec<AlkaneId>,
         amount_in: u128,
         amount_out_min: u128,
+        to: AlkaneId,
         deadline: u128,
     ) -> Result<CallResponse> {
         let context = self.context()?;
         self._check_deadline(self.height(), deadline)?;
         let parcel = context.incoming_alkanes;
 
-        let amounts = self.get_amounts_out(amount_in, &path)?;
+        let (amounts, pools) = self._get_amounts_out(amount_in, &path)?;
         if amounts[amounts.len() - 1] < amount_out_min {
             return Err(anyhow!("predicate failed: insufficient output"));
         }
 
-        let result = self._swap(&amounts, &path)?;
-        self._return_leftovers(context.myself, result, parcel)
+        let result = self._swap(&amounts, &path, &pools)?;
+        self._return_leftovers_to(to, context.myself, result, parcel)
     }
 
-    fn get_amounts_in(&self, amount_out: u128, path: &Vec<AlkaneId>) -> Result<Vec<u128>> {
+    fn _get_amounts_in(
+        &self,
+        amount_out: u128,
+        path: &Vec<AlkaneId>,
+    ) -> Result<(Vec<u128>, Vec<AlkaneId>)> {
         let n = path.len();
         if n < 2 {
             return Err(anyhow!("Routing path must be at least two alkanes long"));
         }
         let mut amounts: Vec<u128> = vec![0; n];
+        let mut pools: Vec<AlkaneId> = vec![AlkaneId::default(); n - 1];
         amounts[n - 1] = amount_out;
         for i in 1..n {
-            let (reserve_in, reserve_out) =
-                self._get_reserves_ordered(path[n - i - 1], path[n - i])?;
-            let total_fee = self._get_total_fee_for_pool(path[n - i - 1], path[n - i])?;
-            amounts[n - i - 1] =
-                oylswap_library::get_amount_in(amounts[n - i], reserve_in, reserve_out, total_fee)?;
+            let (amount_in, pool) =
+                self._get_amount_in(amounts[n - i], path[n - i - 1], path[n - i])?;
+            amounts[n - i - 1] = amount_in;
+            pools[n - i - 1] = pool;
         }
-        Ok(amounts)
+        Ok((amounts, pools))
+    }
+
+    fn get_amounts_in(&self, amount_out: u128, path: Vec<AlkaneId>) -> Result<CallResponse> {
+        let (amounts, pools) = self._get_amounts_in(amount_out, &path)?;
+        self._quote_response(&amounts, &path, &pools)
     }
 
     fn swap_tokens_for_exact_tokens(
@@ -570,13 +1547,188 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
         path: Vec<AlkaneId>,
         desired_amount_out: u128,
         amount_in_max: u128,
+        to: AlkaneId,
         deadline: u128,
     ) -> Result<CallResponse> {
         let context = self.context()?;
         self._check_deadline(self.height(), deadline)?;
         let parcel: AlkaneTransferParcel = context.clone().incoming_alkanes;
 
-        let amounts = self.get_amounts_in(desired_amount_out, &path)?;
+        let (amounts, pools) = self._get_amounts_in(desired_amount_out, &path)?;
+        if amounts[0] > amount_in_max {
+            return Err(anyhow!(format!(
+                "EXCESSIVE_INPUT_AMOUNT: required({}) > amount_in_max({})",
+                amounts[0], amount_in_max
+            )));
+        }
+
+        let result = self._swap(&amounts, &path, &pools)?;
+        self._return_leftovers_to(to, context.myself, result, parcel)
+    }
+
+    // intermediate tokens the best path search may route through
+    fn route_tokens(&self) -> Result<Vec<AlkaneId>> {
+        let bytes = StoragePointer::from_keyword("/route_tokens").get();
+        let mut route_tokens = Vec::new();
+        for chunk in bytes.chunks_exact(32) {
+            route_tokens.push(chunk.to_vec().try_into()?);
+        }
+        Ok(route_tokens)
+    }
+
+    fn set_route_token(&self, token: AlkaneId, enabled: u128) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        let mut route_tokens = self.route_tokens()?;
+        route_tokens.retain(|route_token| *route_token != token);
+        if enabled != 0 {
+            if route_tokens.len() >= MAX_ROUTE_TOKENS {
+                return Err(anyhow!(format!(
+                    "at most {} route tokens can be set",
+                    MAX_ROUTE_TOKENS
+                )));
+            }
+            route_tokens.push(token);
+        }
+        let mut bytes = Vec::new();
+        for route_token in route_tokens {
+            bytes.extend_from_slice(&Into::<Vec<u8>>::into(route_token));
+        }
+        StoragePointer::from_keyword("/route_tokens").set(Arc::new(bytes));
+        Ok(CallResponse::forward(&context.incoming_alkanes.clone()))
+    }
+
+    fn get_route_tokens(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes.clone());
+        for route_token in self.route_tokens()? {
+            response
+                .data
+                .extend_from_slice(&Into::<Vec<u8>>::into(route_token));
+        }
+        Ok(response)
+    }
+
+    // every path from token_in to token_out of at most MAX_ROUTE_HOPS pools that only goes
+    // through route tokens, skipping hops without a pool
+    fn _candidate_paths(
+        &self,
+        token_in: AlkaneId,
+        token_out: AlkaneId,
+    ) -> Result<Vec<Vec<AlkaneId>>> {
+        if token_in == token_out {
+            return Err(anyhow!("tokens to swap cannot be the same"));
+        }
+        let route_tokens: Vec<AlkaneId> = self
+            .route_tokens()?
+            .into_iter()
+            .filter(|token| *token != token_in && *token != token_out)
+            .collect();
+        let mut paths = Vec::new();
+        let mut stack = vec![vec![token_in]];
+        while let Some(path) = stack.pop() {
+            let last = path[path.len() - 1];
+            if !self._find_pools_for_pair(last, token_out).is_empty() {
+                let mut complete = path.clone();
+                complete.push(token_out);
+                paths.push(complete);
+            }
+            if path.len() >= MAX_ROUTE_HOPS {
+                continue;
+            }
+            for token in route_tokens.iter() {
+                if !path.contains(token) && !self._find_pools_for_pair(last, *token).is_empty() {
+                    let mut extended = path.clone();
+                    extended.push(*token);
+                    stack.push(extended);
+                }
+            }
+        }
+        Ok(paths)
+    }
+
+    // the candidate path paying out the most for amount_in, with its amounts and pools
+    fn _best_path_out(
+        &self,
+        amount_in: u128,
+        token_in: AlkaneId,
+        token_out: AlkaneId,
+    ) -> Result<(Vec<AlkaneId>, Vec<u128>, Vec<AlkaneId>)> {
+        let mut best: Option<(Vec<AlkaneId>, Vec<u128>, Vec<AlkaneId>)> = None;
+        for path in self._candidate_paths(token_in, token_out)? {
+            if let Ok((amounts, pools)) = self._get_amounts_out(amount_in, &path) {
+                let better = match &best {
+                    Some((_, best_amounts, _)) => {
+                        amounts[amounts.len() - 1] > best_amounts[best_amounts.len() - 1]
+                    }
+                    None => true,
+                };
+                if better {
+                    best = Some((path, amounts, pools));
+                }
+            }
+        }
+        best.ok_or_else(|| anyhow!(format!("no route from {:?} to {:?}", token_in, token_out)))
+    }
+
+    // the candidate path taking the least input for amount_out, with its amounts and pools
+    fn _best_path_in(
+        &self,
+        amount_out: u128,
+        token_in: AlkaneId,
+        token_out: AlkaneId,
+    ) -> Result<(Vec<AlkaneId>, Vec<u128>, Vec<AlkaneId>)> {
+        let mut best: Option<(Vec<AlkaneId>, Vec<u128>, Vec<AlkaneId>)> = None;
+        for path in self._candidate_paths(token_in, token_out)? {
+            if let Ok((amounts, pools)) = self._get_amounts_in(amount_out, &path) {
+                let better = match &best {
+                    Some((_, best_amounts, _)) => amounts[0] < best_amounts[0],
+                    None => true,
+                };
+                if better {
+                    best = Some((path, amounts, pools));
+                }
+            }
+        }
+        best.ok_or_else(|| anyhow!(format!("no route from {:?} to {:?}", token_in, token_out)))
+    }
+
+    fn swap_exact_tokens_for_tokens_best_path(
+        &self,
+        token_in: AlkaneId,
+        token_out: AlkaneId,
+        amount_in: u128,
+        amount_out_min: u128,
+        to: AlkaneId,
+        deadline: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        self._check_deadline(self.height(), deadline)?;
+        let parcel = context.incoming_alkanes;
+
+        let (path, amounts, pools) = self._best_path_out(amount_in, token_in, token_out)?;
+        if amounts[amounts.len() - 1] < amount_out_min {
+            return Err(anyhow!("predicate failed: insufficient output"));
+        }
+
+        let result = self._swap(&amounts, &path, &pools)?;
+        self._return_leftovers_to(to, context.myself, result, parcel)
+    }
+
+    fn swap_tokens_for_exact_tokens_best_path(
+        &self,
+        token_in: AlkaneId,
+        token_out: AlkaneId,
+        desired_amount_out: u128,
+        amount_in_max: u128,
+        to: AlkaneId,
+        deadline: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        self._check_deadline(self.height(), deadline)?;
+        let parcel = context.incoming_alkanes;
+
+        let (path, amounts, pools) = self._best_path_in(desired_amount_out, token_in, token_out)?;
         if amounts[0] > amount_in_max {
             return Err(anyhow!(format!(
                 "EXCESSIVE_INPUT_AMOUNT: required({}) > amount_in_max({})",
@@ -584,8 +1736,96 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
             )));
         }
 
-        let result = self._swap(&amounts, &path)?;
-        self._return_leftovers(context.myself, result, parcel)
+        let result = self._swap(&amounts, &path, &pools)?;
+        self._return_leftovers_to(to, context.myself, result, parcel)
+    }
+
+    // splits concatenated paths that all end in the same alkane, e.g. [a, b, d, a, c, d] into
+    // [a, b, d] and [a, c, d]
+    fn _split_paths(&self, paths: &Vec<AlkaneId>) -> Result<Vec<Vec<AlkaneId>>> {
+        if paths.len() < 2 {
+            return Err(anyhow!("Routing path must be at least two alkanes long"));
+        }
+        let token_out = paths[paths.len() - 1];
+        let mut routes: Vec<Vec<AlkaneId>> = vec![];
+        let mut route: Vec<AlkaneId> = vec![];
+        for token in paths {
+            route.push(*token);
+            if *token != token_out {
+                continue;
+            }
+            if route.len() < 2 {
+                return Err(anyhow!("Routing path must be at least two alkanes long"));
+            }
+            if route[0] != paths[0] {
+                return Err(anyhow!("split paths must all start with the same alkane"));
+            }
+            routes.push(std::mem::take(&mut route));
+        }
+        Ok(routes)
+    }
+
+    fn swap_exact_tokens_for_tokens_split(
+        &self,
+        paths: Vec<AlkaneId>,
+        shares: Vec<u128>,
+        amount_in: u128,
+        amount_out_min: u128,
+        to: AlkaneId,
+        deadline: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        self._check_deadline(self.height(), deadline)?;
+        let parcel = context.incoming_alkanes;
+
+        let routes = self._split_paths(&paths)?;
+        if routes.len() != shares.len() {
+            return Err(anyhow!(format!(
+                "got {} paths but {} shares",
+                routes.len(),
+                shares.len()
+            )));
+        }
+        let total_shares = shares
+            .iter()
+            .try_fold(0u128, |total, share| total.checked_add(*share))
+            .ok_or_else(|| anyhow!("shares overflow"))?;
+        if total_shares != SPLIT_SHARE_DENOMINATOR {
+            return Err(anyhow!(format!(
+                "shares must add up to {}, got {}",
+                SPLIT_SHARE_DENOMINATOR, total_shares
+            )));
+        }
+
+        // each path is quoted right before it is swapped, so paths sharing a pool see the
+        // reserves left by the previous ones
+        let mut result = CallResponse::default();
+        let mut amount_out: u128 = 0;
+        let mut remaining = amount_in;
+        for (i, route) in routes.iter().enumerate() {
+            // the last path also takes the rounding remainder
+            let route_amount_in: u128 = if i == routes.len() - 1 {
+                remaining
+            } else {
+                (U256::from(amount_in) * U256::from(shares[i])
+                    / U256::from(SPLIT_SHARE_DENOMINATOR))
+                .try_into()?
+            };
+            if route_amount_in == 0 {
+                continue;
+            }
+            remaining -= route_amount_in;
+            let (amounts, pools) = self._get_amounts_out(route_amount_in, route)?;
+            amount_out = amount_out
+                .checked_add(amounts[amounts.len() - 1])
+                .ok_or_else(|| anyhow!("output overflow"))?;
+            let response = self._swap(&amounts, route, &pools)?;
+            result.alkanes.0.extend(response.alkanes.0);
+        }
+        if amount_out < amount_out_min {
+            return Err(anyhow!("predicate failed: insufficient output"));
+        }
+        self._return_leftovers_to(to, context.myself, result, parcel)
     }
 
     fn set_total_fee_for_pool(
@@ -595,6 +1835,12 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
     ) -> Result<CallResponse> {
         self.only_owner()?;
         let context = self.context()?;
+        // the pool would no longer charge the fee of the tier it is registered under
+        if self.fee_tier_of(&pool_id) != DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000 {
+            return Err(anyhow!(
+                "the total fee of a pool in a fee tier cannot be changed"
+            ));
+        }
         self.call(
             &Cellpack {
                 target: pool_id,
@@ -605,6 +1851,53 @@ pub trait AMMFactoryBase: AuthenticatedResponder {
         )
     }
 
+    fn set_protocol_fee_for_pool(
+        &self,
+        pool_id: AlkaneId,
+        protocol_fee_per_1000: u128,
+    ) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        self.call(
+            &Cellpack {
+                target: pool_id,
+                inputs: vec![25, protocol_fee_per_1000],
+            },
+            &context.incoming_alkanes.clone(),
+            self.fuel(),
+        )
+    }
+
+    fn set_flash_fee_for_pool(
+        &self,
+        pool_id: AlkaneId,
+        flash_fee_per_1000: u128,
+    ) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        self.call(
+            &Cellpack {
+                target: pool_id,
+                inputs: vec![29, flash_fee_per_1000],
+            },
+            &context.incoming_alkanes.clone(),
+            self.fuel(),
+        )
+    }
+
+    fn set_paused_for_pool(&self, pool_id: AlkaneId, paused: u128) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        self.call(
+            &Cellpack {
+                target: pool_id,
+                inputs: vec![34, paused],
+            },
+            &context.incoming_alkanes.clone(),
+            self.fuel(),
+        )
+    }
+
     fn forward(&self) -> Result<CallResponse> {
         let context = self.context()?;
         let response = CallResponse::forward(&context.incoming_alkanes);
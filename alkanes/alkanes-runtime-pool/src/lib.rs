@@ -17,8 +17,11 @@ use alkanes_support::{
 use anyhow::{anyhow, Result};
 use metashrew_support::{index_pointer::KeyValuePointer, utils::consume_u128};
 use oylswap_library::{
-    Lock, PoolInfo, Sqrt, StorableU256, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
-    PROTOCOL_FEE_AMOUNT_PER_1000, U256,
+    concentrated::U512,
+    fixed_point::{self, UQ128x128},
+    oracle::{self, Observation, MAX_OBSERVATION_CARDINALITY, ORACLE_CLOCK_CLAMPED_BLOCK_TIME},
+    Lock, PoolInfo, PoolStats, Sqrt, StorableU256, DEFAULT_FLASH_FEE_AMOUNT_PER_1000,
+    DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000, PROTOCOL_FEE_AMOUNT_PER_1000000, U256,
 };
 use protorune_support::balance_sheet::{BalanceSheetOperations, CachedBalanceSheet};
 use std::{cmp::min, sync::Arc};
@@ -26,7 +29,10 @@ use std::{cmp::min, sync::Arc};
 // per uniswap docs, the first 1e3 wei of lp token minted are burned to mitigate attacks where the value of a lp token is raised too high easily
 pub const MINIMUM_LIQUIDITY: u128 = 1000;
 pub const SWAP_EXTCALL_OPCODE: u128 = 73776170;
-pub const PRECISION: u32 = 128;
+pub use oylswap_library::RECEIVE_EXTCALL_OPCODE;
+// called on the receiver of a flash loan, which must pay back the loan plus the fee before returning
+pub const FLASH_LOAN_EXTCALL_OPCODE: u128 = 7265706179;
+pub const PRECISION: u32 = fixed_point::RESOLUTION as u32;
 
 pub trait AMMPoolBase: MintableToken + AlkaneResponder {
     fn factory(&self) -> Result<AlkaneId> {
@@ -71,19 +77,121 @@ pub trait AMMPoolBase: MintableToken + AlkaneResponder {
     fn set_block_timestamp_last(&self, v: u32) {
         self.block_timestamp_last_pointer().set_value::<u32>(v);
     }
+    // fees are kept per 1000000 so that pools in fee tiers below 0.1% can charge them. Pools
+    // that were last given a fee per 1000 only have it under the old key
     fn total_fee_pointer(&self) -> StoragePointer {
         StoragePointer::from_keyword("/totalfeeper1000")
     }
-    fn total_fee_per_1000(&self) -> u128 {
+    fn total_fee_per_1000000_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/totalfeeper1000000")
+    }
+    fn total_fee_per_1000000(&self) -> u128 {
+        let ptr = self.total_fee_per_1000000_pointer();
+        if ptr.get().len() != 0 {
+            return ptr.get_value::<u128>();
+        }
         let ptr = self.total_fee_pointer();
         if ptr.get().len() == 0 {
-            DEFAULT_TOTAL_FEE_AMOUNT_PER_1000
+            DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000
         } else {
-            ptr.get_value::<u128>()
+            ptr.get_value::<u128>() * 1000
         }
     }
+    fn set_total_fee_per_1000000(&self, v: u128) {
+        self.total_fee_per_1000000_pointer().set_value::<u128>(v);
+    }
+    // rounded down for pools charging a fraction of a unit per 1000
+    fn total_fee_per_1000(&self) -> u128 {
+        self.total_fee_per_1000000() / 1000
+    }
     fn set_total_fee_per_1000(&self, v: u128) {
-        self.total_fee_pointer().set_value::<u128>(v);
+        self.set_total_fee_per_1000000(v * 1000);
+    }
+    fn protocol_fee_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/protocolfeeper1000")
+    }
+    fn protocol_fee_per_1000000_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/protocolfeeper1000000")
+    }
+    // share of the total fee that is minted to the factory as lp tokens
+    fn protocol_fee_per_1000000(&self) -> u128 {
+        let ptr = self.protocol_fee_per_1000000_pointer();
+        if ptr.get().len() != 0 {
+            return ptr.get_value::<u128>();
+        }
+        let ptr = self.protocol_fee_pointer();
+        if ptr.get().len() == 0 {
+            PROTOCOL_FEE_AMOUNT_PER_1000000
+        } else {
+            ptr.get_value::<u128>() * 1000
+        }
+    }
+    fn set_protocol_fee_per_1000000(&self, v: u128) {
+        self.protocol_fee_per_1000000_pointer().set_value::<u128>(v);
+    }
+    fn protocol_fee_per_1000(&self) -> u128 {
+        self.protocol_fee_per_1000000() / 1000
+    }
+    fn set_protocol_fee_per_1000(&self, v: u128) {
+        self.set_protocol_fee_per_1000000(v * 1000);
+    }
+    fn flash_fee_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/flashfeeper1000")
+    }
+    // charged on top of flash loans, independently of the swap fee
+    fn flash_fee_per_1000(&self) -> u128 {
+        let ptr = self.flash_fee_pointer();
+        if ptr.get().len() == 0 {
+            DEFAULT_FLASH_FEE_AMOUNT_PER_1000
+        } else {
+            ptr.get_value::<u128>()
+        }
+    }
+    fn set_flash_fee_per_1000(&self, v: u128) {
+        self.flash_fee_pointer().set_value::<u128>(v);
+    }
+    fn stats_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/stats")
+    }
+    fn stats(&self) -> Result<PoolStats> {
+        let bytes = self.stats_pointer().get();
+        if bytes.len() == 0 {
+            Ok(PoolStats::default())
+        } else {
+            PoolStats::from_vec(&bytes)
+        }
+    }
+    fn set_stats(&self, stats: &PoolStats) {
+        self.stats_pointer().set(Arc::new(stats.try_to_vec()));
+    }
+    fn fee_growth_per_lp_pointers(&self) -> (StoragePointer, StoragePointer) {
+        (
+            StoragePointer::from_keyword("/feegrowth0perlp"),
+            StoragePointer::from_keyword("/feegrowth1perlp"),
+        )
+    }
+    // fees of each token earned per lp token since the pool was created, as UQ128x128 values
+    // that wrap on overflow like the price cumulatives
+    fn fee_growth_per_lp(&self) -> (U256, U256) {
+        let (g0, g1) = self.fee_growth_per_lp_pointers();
+        (
+            g0.get_value::<StorableU256>().into(),
+            g1.get_value::<StorableU256>().into(),
+        )
+    }
+    fn set_fee_growth_per_lp(&self, v0: U256, v1: U256) {
+        let (mut g0, mut g1) = self.fee_growth_per_lp_pointers();
+        g0.set_value::<StorableU256>(v0.into());
+        g1.set_value::<StorableU256>(v1.into());
+    }
+    fn paused_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/paused")
+    }
+    fn paused_state(&self) -> bool {
+        self.paused_pointer().get_value::<u8>() == 1
+    }
+    fn set_paused_state(&self, v: bool) {
+        self.paused_pointer().set_value::<u8>(v as u8);
     }
     fn price_cumulative_pointers(&self) -> (StoragePointer, StoragePointer) {
         (
@@ -101,8 +209,65 @@ pub trait AMMPoolBase: MintableToken + AlkaneResponder {
     fn increase_price_cumulative(&self, v0: U256, v1: U256) {
         let (mut p0, mut p1) = self.price_cumulative_pointers();
         let (p0_val, p1_val) = self.price_cumulative();
-        p0.set_value::<StorableU256>((p0_val + v0).into());
-        p1.set_value::<StorableU256>((p1_val + v1).into());
+        // cumulatives are meant to overflow, see oylswap_library::fixed_point
+        p0.set_value::<StorableU256>(p0_val.wrapping_add(v0).into());
+        p1.set_value::<StorableU256>(p1_val.wrapping_add(v1).into());
+    }
+    fn stored_reserve_pointers(&self) -> (StoragePointer, StoragePointer) {
+        (
+            StoragePointer::from_keyword("/reserve0"),
+            StoragePointer::from_keyword("/reserve1"),
+        )
+    }
+    fn set_stored_reserves(&self, reserve0: u128, reserve1: u128) {
+        let (mut p0, mut p1) = self.stored_reserve_pointers();
+        p0.set_value::<u128>(reserve0);
+        p1.set_value::<u128>(reserve1);
+    }
+    fn observation_pointer(&self, index: u128) -> StoragePointer {
+        StoragePointer::from_keyword("/observations/").select(&index.to_le_bytes().to_vec())
+    }
+    // slots past the newest observation stay empty until the buffer wraps around for the first time
+    fn observation(&self, index: u128) -> Result<Option<Observation>> {
+        let bytes = self.observation_pointer(index).get();
+        if bytes.len() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(Observation::from_vec(&bytes)?))
+        }
+    }
+    fn set_observation(&self, index: u128, observation: &Observation) {
+        self.observation_pointer(index)
+            .set(Arc::new(observation.try_to_vec()));
+    }
+    fn observation_index_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/observationindex")
+    }
+    fn observation_index(&self) -> u128 {
+        self.observation_index_pointer().get_value::<u128>()
+    }
+    fn set_observation_index(&self, v: u128) {
+        self.observation_index_pointer().set_value::<u128>(v);
+    }
+    fn observation_cardinality_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/observationcardinality")
+    }
+    fn observation_cardinality(&self) -> u128 {
+        self.observation_cardinality_pointer().get_value::<u128>()
+    }
+    fn set_observation_cardinality(&self, v: u128) {
+        self.observation_cardinality_pointer().set_value::<u128>(v);
+    }
+    fn observation_cardinality_next_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/observationcardinalitynext")
+    }
+    fn observation_cardinality_next(&self) -> u128 {
+        self.observation_cardinality_next_pointer()
+            .get_value::<u128>()
+    }
+    fn set_observation_cardinality_next(&self, v: u128) {
+        self.observation_cardinality_next_pointer()
+            .set_value::<u128>(v);
     }
     fn _only_factory_caller(&self) -> Result<()> {
         if self.context()?.caller != self.factory()? {
@@ -110,6 +275,13 @@ pub trait AMMPoolBase: MintableToken + AlkaneResponder {
         }
         Ok(())
     }
+    // trading and deposits stop while the pool is paused, withdrawals keep working so lps can exit
+    fn _only_not_paused(&self) -> Result<()> {
+        if self.paused_state() {
+            return Err(anyhow!("PAUSED"));
+        }
+        Ok(())
+    }
     fn init_pool(
         &self,
         alkane_a: AlkaneId,
@@ -228,7 +400,8 @@ pub trait AMMPoolBase: MintableToken + AlkaneResponder {
             },
         ))
     }
-    fn previous_reserves(
+    // balances the pool held before the alkanes sent along with this call
+    fn balances_before_incoming(
         &self,
         parcel: &AlkaneTransferParcel,
     ) -> Result<(AlkaneTransfer, AlkaneTransfer)> {
@@ -245,25 +418,89 @@ pub trait AMMPoolBase: MintableToken + AlkaneResponder {
             },
         ))
     }
+    // reserves as of the last add, burn, swap or sync, so stray transfers and half finished
+    // swaps never move the price. Pools that have not been updated since reserves started being
+    // stored fall back to their balances
+    fn previous_reserves(
+        &self,
+        parcel: &AlkaneTransferParcel,
+    ) -> Result<(AlkaneTransfer, AlkaneTransfer)> {
+        let (p0, p1) = self.stored_reserve_pointers();
+        if p0.get().len() == 0 {
+            return self.balances_before_incoming(parcel);
+        }
+        let (a, b) = self.alkanes_for_self()?;
+        Ok((
+            AlkaneTransfer {
+                id: a,
+                value: p0.get_value::<u128>(),
+            },
+            AlkaneTransfer {
+                id: b,
+                value: p1.get_value::<u128>(),
+            },
+        ))
+    }
 
-    fn _mint_fee(&self, previous_a: u128, previous_b: u128) -> Result<()> {
-        let total_supply = self.total_supply();
+    // the pool invariant k, scaled so that its square root grows linearly with liquidity. Used
+    // for the first lp mint and for the protocol fee. Pool variants with a different curve
+    // override this together with `_check_invariant`.
+    fn _k(&self, reserve_a: u128, reserve_b: u128) -> Result<U256> {
+        Ok(U256::from(reserve_a) * U256::from(reserve_b))
+    }
+
+    // checks that a swap did not decrease the invariant once the fee is taken from the inputs
+    fn _check_invariant(
+        &self,
+        balance_0: u128,
+        balance_1: u128,
+        amount_0_in: u128,
+        amount_1_in: u128,
+        reserve_0: u128,
+        reserve_1: u128,
+    ) -> Result<()> {
+        // Check K value (constant product formula)
+        // In Uniswap: balance0Adjusted.mul(balance1Adjusted) >= uint(_reserve0).mul(_reserve1).mul(1000**2),
+        // here with the fee per 1000000
+        let total_fee = self.total_fee_per_1000000();
+        let balance_0_adjusted = U512::from(balance_0) * U512::from(1000000)
+            - U512::from(amount_0_in) * U512::from(total_fee);
+        let balance_1_adjusted = U512::from(balance_1) * U512::from(1000000)
+            - U512::from(amount_1_in) * U512::from(total_fee);
+
+        if balance_0_adjusted * balance_1_adjusted
+            < U512::from(reserve_0) * U512::from(reserve_1) * U512::from(1000000u128 * 1000000)
+        {
+            return Err(anyhow!("K is not increasing"));
+        }
+        Ok(())
+    }
+
+    // lp tokens _mint_fee would mint to the protocol for the growth of k since k_last
+    fn _pending_protocol_liquidity(&self, previous_a: u128, previous_b: u128) -> Result<u128> {
         let k_last = self.k_last();
-        if !k_last.is_zero() {
-            let root_k_last = k_last.sqrt();
-            let root_k = (U256::from(previous_a) * U256::from(previous_b)).sqrt();
-            if root_k > root_k_last {
-                let numerator = U256::from(total_supply) * (root_k - root_k_last);
-                let root_k_fee_adj = root_k
-                    * U256::from(self.total_fee_per_1000() - PROTOCOL_FEE_AMOUNT_PER_1000)
-                    / U256::from(PROTOCOL_FEE_AMOUNT_PER_1000);
-                let denominator = root_k_fee_adj + root_k_last;
-                let liquidity: u128 = (numerator / denominator).try_into()?; // guaranteed to be storable in u128
-                self.increase_total_supply(liquidity)?;
-                self.set_claimable_fees(checked_expr!(self
-                    .claimable_fees()
-                    .checked_add(liquidity))?);
-            }
+        let protocol_fee = self.protocol_fee_per_1000000();
+        // a protocol fee of zero turns fee minting off
+        if k_last.is_zero() || protocol_fee == 0 {
+            return Ok(0);
+        }
+        let root_k_last = k_last.sqrt();
+        let root_k = self._k(previous_a, previous_b)?.sqrt();
+        if root_k <= root_k_last {
+            return Ok(0);
+        }
+        let numerator = U256::from(self.total_supply()) * (root_k - root_k_last);
+        let root_k_fee_adj = root_k * U256::from(self.total_fee_per_1000000() - protocol_fee)
+            / U256::from(protocol_fee);
+        let denominator = root_k_fee_adj + root_k_last;
+        Ok((numerator / denominator).try_into()?) // guaranteed to be storable in u128
+    }
+
+    fn _mint_fee(&self, previous_a: u128, previous_b: u128) -> Result<()> {
+        let liquidity = self._pending_protocol_liquidity(previous_a, previous_b)?;
+        if liquidity > 0 {
+            self.increase_total_supply(liquidity)?;
+            self.set_claimable_fees(checked_expr!(self.claimable_fees().checked_add(liquidity))?);
         }
         Ok(())
     }
@@ -280,7 +517,7 @@ pub trait AMMPoolBase: MintableToken + AlkaneResponder {
             value: self.claimable_fees(),
         });
         self.set_claimable_fees(0);
-        let new_k = U256::from(previous_a.value) * U256::from(previous_b.value);
+        let new_k = self._k(previous_a.value, previous_b.value)?;
         self.set_k_last(new_k);
         Ok(response)
     }
@@ -289,27 +526,175 @@ pub trait AMMPoolBase: MintableToken + AlkaneResponder {
         Ok(CallResponse::forward(&context.incoming_alkanes))
     }
 
+    // spot prices of token 0 in token 1 and of token 1 in token 0, zero while the pool is empty.
+    // These feed the price cumulatives and the observations, so pool variants whose marginal
+    // price is not the reserve ratio override this together with `_k` and `_check_invariant`.
+    fn _prices(&self, reserve0: u128, reserve1: u128) -> Result<(UQ128x128, UQ128x128)> {
+        if reserve0 == 0 || reserve1 == 0 {
+            return Ok((UQ128x128::default(), UQ128x128::default()));
+        }
+        Ok((
+            UQ128x128::fraction(reserve1, reserve0)?,
+            UQ128x128::fraction(reserve0, reserve1)?,
+        ))
+    }
+
+    // the time the oracle runs on, see ORACLE_CLOCK_CLAMPED_BLOCK_TIME
+    fn _oracle_time(&self) -> Result<u32> {
+        Ok(self.block_header()?.time.max(self.block_timestamp_last()))
+    }
+
     fn _update_cum_prices(&self, reserve0: u128, reserve1: u128) -> Result<()> {
-        let block_header = self.block_header()?;
-        let current_timestamp = block_header.time;
+        let current_timestamp = self._oracle_time()?;
         let last_timestamp = self.block_timestamp_last();
         let time_elapsed = current_timestamp - last_timestamp;
         if time_elapsed > 0 && reserve0 != 0 && reserve1 != 0 {
+            let (price_0, price_1) = self._prices(reserve0, reserve1)?;
             self.increase_price_cumulative(
-                ((U256::from(reserve1) << U256::from(PRECISION)) / U256::from(reserve0)
-                    * U256::from(time_elapsed))
-                .try_into()?,
-                ((U256::from(reserve0) << U256::from(PRECISION)) / U256::from(reserve1)
-                    * U256::from(time_elapsed))
-                .try_into()?,
+                fixed_point::cumulative_increase(price_0, time_elapsed),
+                fixed_point::cumulative_increase(price_1, time_elapsed),
             );
         }
         self.set_block_timestamp_last(current_timestamp);
+        self._write_observation(current_timestamp)
+    }
+
+    // records the current price cumulatives in the observation buffer, at most once per timestamp
+    fn _write_observation(&self, block_timestamp: u32) -> Result<()> {
+        let (price_0_cumulative, price_1_cumulative) = self.price_cumulative();
+        let observation = Observation {
+            block_timestamp,
+            price_0_cumulative,
+            price_1_cumulative,
+        };
+        let cardinality = self.observation_cardinality();
+        // pools created before the oracle existed start their buffer on the first update
+        if cardinality == 0 {
+            self.set_observation(0, &observation);
+            self.set_observation_index(0);
+            self.set_observation_cardinality(1);
+            if self.observation_cardinality_next() == 0 {
+                self.set_observation_cardinality_next(1);
+            }
+            return Ok(());
+        }
+        let index = self.observation_index();
+        if let Some(last) = self.observation(index)? {
+            if last.block_timestamp == block_timestamp {
+                return Ok(());
+            }
+        }
+        // the buffer only grows once the newest observation sits in the last slot, so the
+        // observations stay in chronological order
+        let cardinality_next = self.observation_cardinality_next();
+        let cardinality = if cardinality_next > cardinality && index == cardinality - 1 {
+            cardinality_next
+        } else {
+            cardinality
+        };
+        let index = (index + 1) % cardinality;
+        self.set_observation(index, &observation);
+        self.set_observation_index(index);
+        self.set_observation_cardinality(cardinality);
         Ok(())
     }
 
+    // cumulative prices `seconds_ago` seconds before `time`, where reserve0 and reserve1 are the
+    // reserves the pool has held since its newest observation
+    fn _observe_single(
+        &self,
+        time: u32,
+        seconds_ago: u32,
+        reserve0: u128,
+        reserve1: u128,
+    ) -> Result<Observation> {
+        let target = time
+            .checked_sub(seconds_ago)
+            .ok_or_else(|| anyhow!("OLD"))?;
+        let cardinality = self.observation_cardinality();
+        if cardinality == 0 {
+            return Err(anyhow!("pool has no observations"));
+        }
+        let index = self.observation_index();
+        let last = self
+            .observation(index)?
+            .ok_or_else(|| anyhow!("pool has no observations"))?;
+        if target >= last.block_timestamp {
+            let (price_0, price_1) = self._prices(reserve0, reserve1)?;
+            return Ok(last.transform(target, price_0, price_1));
+        }
+
+        // until the buffer wraps around the oldest observation is the one in slot 0
+        let (oldest_index, length) = match self.observation((index + 1) % cardinality)? {
+            Some(_) => ((index + 1) % cardinality, cardinality),
+            None => (0, index + 1),
+        };
+        let slot = |position: u128| -> Result<Observation> {
+            self.observation((oldest_index + position) % cardinality)?
+                .ok_or_else(|| anyhow!("observation is not initialized"))
+        };
+        if target < slot(0)?.block_timestamp {
+            return Err(anyhow!("OLD"));
+        }
+
+        // binary search for the last observation at or before the target, the newest observation
+        // is known to be after it
+        let (mut low, mut high) = (0, length - 1);
+        while high - low > 1 {
+            let middle = (low + high) / 2;
+            if slot(middle)?.block_timestamp <= target {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        let before = slot(low)?;
+        if before.block_timestamp == target {
+            return Ok(before);
+        }
+        oracle::interpolate(&before, &slot(high)?, target)
+    }
+
+    fn increase_observation_cardinality_next(
+        &self,
+        observation_cardinality_next: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        if observation_cardinality_next > MAX_OBSERVATION_CARDINALITY {
+            return Err(anyhow!(
+                "observation cardinality cannot exceed {}",
+                MAX_OBSERVATION_CARDINALITY
+            ));
+        }
+        if observation_cardinality_next > self.observation_cardinality_next() {
+            self.set_observation_cardinality_next(observation_cardinality_next);
+        }
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    // returns the price 0 and price 1 cumulatives, 32 bytes each, for every entry of seconds_ago
+    fn consult(&self, seconds_ago: Vec<u128>) -> Result<CallResponse> {
+        Lock::view(|| {
+            let context = self.context()?;
+            let time = self._oracle_time()?;
+            let (reserve_a, reserve_b) = self.previous_reserves(&context.incoming_alkanes)?;
+            let mut response = CallResponse::forward(&context.incoming_alkanes);
+            let mut bytes = Vec::new();
+            for s in seconds_ago {
+                let s: u32 = s.try_into().map_err(|_| anyhow!("OLD"))?;
+                let observation =
+                    self._observe_single(time, s, reserve_a.value, reserve_b.value)?;
+                bytes.extend_from_slice(&observation.price_0_cumulative.to_le_bytes::<32>());
+                bytes.extend_from_slice(&observation.price_1_cumulative.to_le_bytes::<32>());
+            }
+            response.data = bytes;
+            Ok(response)
+        })
+    }
+
     fn add_liquidity(&self) -> Result<CallResponse> {
         Lock::lock(|| {
+            self._only_not_paused()?;
             let context = self.context()?;
             let myself = context.myself;
             let parcel = context.incoming_alkanes.clone();
@@ -324,7 +709,7 @@ pub trait AMMPoolBase: MintableToken + AlkaneResponder {
             let total_supply = self.total_supply(); // must be defined here since totalSupply can update in _mintFee
             let liquidity: u128;
             if total_supply == 0 {
-                let root_k = (U256::from(amount_a_in) * U256::from(amount_b_in)).sqrt();
+                let root_k = self._k(amount_a_in, amount_b_in)?.sqrt();
                 liquidity = checked_expr!(
                     <U256 as TryInto<u128>>::try_into(root_k)?.checked_sub(MINIMUM_LIQUIDITY)
                 )?;
@@ -344,7 +729,8 @@ pub trait AMMPoolBase: MintableToken + AlkaneResponder {
             let mut response = CallResponse::default();
             response.alkanes.pay(self.mint(&context, liquidity)?);
             self._update_cum_prices(previous_a.value, previous_b.value)?;
-            let new_k = U256::from(reserve_a.value) * U256::from(reserve_b.value);
+            self.set_stored_reserves(reserve_a.value, reserve_b.value);
+            let new_k = self._k(reserve_a.value, reserve_b.value)?;
             self.set_k_last(new_k);
             Ok(response)
         })
@@ -387,8 +773,8 @@ pub trait AMMPoolBase: MintableToken + AlkaneResponder {
             ]);
 
             self._update_cum_prices(previous_a.value, previous_b.value)?;
-            let new_k =
-                U256::from(reserve_a.value - amount_a) * U256::from(reserve_b.value - amount_b);
+            self.set_stored_reserves(reserve_a.value - amount_a, reserve_b.value - amount_b);
+            let new_k = self._k(reserve_a.value - amount_a, reserve_b.value - amount_b)?;
             self.set_k_last(new_k);
             Ok(response)
         })
@@ -402,6 +788,7 @@ pub trait AMMPoolBase: MintableToken + AlkaneResponder {
         data: Vec<u128>,
     ) -> Result<CallResponse> {
         Lock::lock(|| {
+            self._only_not_paused()?;
             if amount_0_out == 0 && amount_1_out == 0 {
                 return Err(anyhow!("INSUFFICIENT_OUTPUT_AMOUNT"));
             }
@@ -483,29 +870,245 @@ pub trait AMMPoolBase: MintableToken + AlkaneResponder {
                 return Err(anyhow!("INSUFFICIENT_INPUT_AMOUNT"));
             }
 
-            // Check K value (constant product formula)
-            // In Uniswap: balance0Adjusted.mul(balance1Adjusted) >= uint(_reserve0).mul(_reserve1).mul(1000**2)
-            let total_fee = self.total_fee_per_1000();
-            let balance_0_adjusted = U256::from(balance_0.value) * U256::from(1000)
-                - U256::from(amount_0_in) * U256::from(total_fee);
-            let balance_1_adjusted = U256::from(balance_1.value) * U256::from(1000)
-                - U256::from(amount_1_in) * U256::from(total_fee);
-
-            if balance_0_adjusted * balance_1_adjusted
-                < U256::from(reserve_0.value)
-                    * U256::from(reserve_1.value)
-                    * U256::from(1000 * 1000)
+            self._check_invariant(
+                balance_0.value,
+                balance_1.value,
+                amount_0_in,
+                amount_1_in,
+                reserve_0.value,
+                reserve_1.value,
+            )?;
+
+            self._update_cum_prices(reserve_0.value, reserve_1.value)?;
+            self.set_stored_reserves(balance_0.value, balance_1.value);
+            self._record_swap(
+                [amount_0_in, amount_1_in],
+                [amount_0_out, amount_1_out],
+                (reserve_0.value, reserve_1.value),
+            )?;
+
+            // Return response with transfers
+            Ok(response)
+        })
+    }
+
+    // flash fee owed on a loan of `amount`, rounded up
+    fn _flash_fee(&self, amount: u128) -> Result<u128> {
+        Ok(
+            ((U256::from(amount) * U256::from(self.flash_fee_per_1000()) + U256::from(999))
+                / U256::from(1000))
+            .try_into()?,
+        )
+    }
+
+    // part of a flash fee kept by lps, the protocol takes the same share of it as of swap fees
+    fn _flash_lp_fee(&self, fee: u128) -> Result<u128> {
+        let total_fee = self.total_fee_per_1000000();
+        if total_fee == 0 {
+            return Ok(fee);
+        }
+        let protocol_fee: u128 = (U256::from(fee) * U256::from(self.protocol_fee_per_1000000())
+            / U256::from(total_fee))
+        .try_into()?;
+        Ok(fee - protocol_fee)
+    }
+
+    // lends amount_0 and amount_1 to `receiver` and calls it back. Any alkanes sent along with
+    // the call count towards the repayment, which must cover the loan plus the flash fee by the
+    // time the callback returns. The fee stays in the reserves, so it accrues to lps and to the
+    // protocol like swap fees do.
+    fn flash_loan(
+        &self,
+        amount_0: u128,
+        amount_1: u128,
+        receiver: AlkaneId,
+        data: Vec<u128>,
+    ) -> Result<CallResponse> {
+        Lock::lock(|| {
+            self._only_not_paused()?;
+            if amount_0 == 0 && amount_1 == 0 {
+                return Err(anyhow!("INSUFFICIENT_LOAN_AMOUNT"));
+            }
+            let context = self.context()?;
+            let parcel = context.incoming_alkanes.clone();
+            let (balance_0, balance_1) = self.balances_before_incoming(&parcel)?;
+            if amount_0 >= balance_0.value || amount_1 >= balance_1.value {
+                return Err(anyhow!("INSUFFICIENT_LIQUIDITY"));
+            }
+            if receiver == AlkaneId::new(0, 0)
+                || receiver == balance_0.id
+                || receiver == balance_1.id
             {
-                return Err(anyhow!("K is not increasing"));
+                return Err(anyhow!("INVALID_TO"));
+            }
+            let (reserve_0, reserve_1) = self.previous_reserves(&parcel)?;
+            let fee_0 = self._flash_fee(amount_0)?;
+            let fee_1 = self._flash_fee(amount_1)?;
+
+            let mut loan = AlkaneTransferParcel::default();
+            if amount_0 > 0 {
+                loan.0.push(AlkaneTransfer {
+                    id: balance_0.id.clone(),
+                    value: amount_0,
+                });
+            }
+            if amount_1 > 0 {
+                loan.0.push(AlkaneTransfer {
+                    id: balance_1.id.clone(),
+                    value: amount_1,
+                });
+            }
+            let mut extcall_input: Vec<u128> = vec![FLASH_LOAN_EXTCALL_OPCODE];
+            extcall_input.append(&mut context.caller.clone().into());
+            extcall_input.push(amount_0);
+            extcall_input.push(amount_1);
+            extcall_input.push(fee_0);
+            extcall_input.push(fee_1);
+            extcall_input.push(data.len() as u128);
+            extcall_input.append(&mut data.clone());
+            self.call(
+                &Cellpack {
+                    target: receiver,
+                    inputs: extcall_input,
+                },
+                &loan,
+                self.fuel(),
+            )?;
+
+            let (repaid_0, repaid_1) = self.reserves()?;
+            if repaid_0.value < checked_expr!(balance_0.value.checked_add(fee_0))?
+                || repaid_1.value < checked_expr!(balance_1.value.checked_add(fee_1))?
+            {
+                return Err(anyhow!("FLASH_LOAN_NOT_REPAID"));
             }
 
             self._update_cum_prices(reserve_0.value, reserve_1.value)?;
+            self.set_stored_reserves(repaid_0.value, repaid_1.value);
+            self._accrue_fee_growth(
+                [self._flash_lp_fee(fee_0)?, self._flash_lp_fee(fee_1)?],
+                (reserve_0.value, reserve_1.value),
+            )?;
+            Ok(CallResponse::default())
+        })
+    }
 
-            // Return response with transfers
+    // adds a swap to the trading stats and the lp fee growth. Fees are taken on the input
+    // amounts, the protocol's share being protocol_fee_per_1000000 of the total_fee_per_1000000 charged
+    fn _record_swap(
+        &self,
+        amounts_in: [u128; 2],
+        amounts_out: [u128; 2],
+        previous_reserves: (u128, u128),
+    ) -> Result<()> {
+        let mut stats = self.stats()?;
+        let mut lp_fees = [0u128; 2];
+        for i in 0..2 {
+            let (lp_fee, protocol_fee) = self._swap_fees(amounts_in[i])?;
+            stats.amount_in[i] = stats.amount_in[i].saturating_add(amounts_in[i]);
+            stats.amount_out[i] = stats.amount_out[i].saturating_add(amounts_out[i]);
+            stats.lp_fees[i] = stats.lp_fees[i].saturating_add(lp_fee);
+            stats.protocol_fees[i] = stats.protocol_fees[i].saturating_add(protocol_fee);
+            lp_fees[i] = lp_fee;
+        }
+        stats.swap_count = stats.swap_count.saturating_add(1);
+        self.set_stats(&stats);
+        self._accrue_fee_growth(lp_fees, previous_reserves)
+    }
+
+    // (lp fee, protocol fee) charged on a swap input of `amount_in`
+    fn _swap_fees(&self, amount_in: u128) -> Result<(u128, u128)> {
+        let fee: u128 = (U256::from(amount_in) * U256::from(self.total_fee_per_1000000())
+            / U256::from(1000000))
+        .try_into()?;
+        let protocol_fee: u128 = (U256::from(amount_in)
+            * U256::from(self.protocol_fee_per_1000000())
+            / U256::from(1000000))
+        .try_into()?;
+        Ok((fee - protocol_fee, protocol_fee))
+    }
+
+    // credits fees earned by lps to every lp token outstanding. The protocol's cut is left out
+    // here because _mint_fee pays it later by minting new lp tokens. Those tokens are owed for
+    // the fees since k_last and share in the lp fees from then on, so the tokens _mint_fee would
+    // mint at the reserves before the fees count as outstanding
+    fn _accrue_fee_growth(
+        &self,
+        lp_fees: [u128; 2],
+        previous_reserves: (u128, u128),
+    ) -> Result<()> {
+        let total_supply = checked_expr!(self.total_supply().checked_add(
+            self._pending_protocol_liquidity(previous_reserves.0, previous_reserves.1)?
+        ))?;
+        if total_supply == 0 {
+            return Ok(());
+        }
+        let (growth_0, growth_1) = self.fee_growth_per_lp();
+        self.set_fee_growth_per_lp(
+            growth_0.wrapping_add(UQ128x128::fraction(lp_fees[0], total_supply)?.0),
+            growth_1.wrapping_add(UQ128x128::fraction(lp_fees[1], total_supply)?.0),
+        );
+        Ok(())
+    }
+
+    // pays `alkanes` to `to`, or to the caller when `to` is zero
+    fn _pay_to(
+        &self,
+        to: AlkaneId,
+        alkanes: AlkaneTransferParcel,
+        response: &mut CallResponse,
+    ) -> Result<()> {
+        if to == AlkaneId::new(0, 0) {
+            for transfer in alkanes.0 {
+                response.alkanes.pay(transfer);
+            }
+            return Ok(());
+        }
+        if !alkanes.0.is_empty() {
+            let mut inputs: Vec<u128> = vec![RECEIVE_EXTCALL_OPCODE];
+            inputs.append(&mut self.context()?.caller.into());
+            self.call(&Cellpack { target: to, inputs }, &alkanes, self.fuel())?;
+        }
+        Ok(())
+    }
+
+    // sends whatever the pool holds on top of its stored reserves to `to`
+    fn skim(&self, to: AlkaneId) -> Result<CallResponse> {
+        Lock::lock(|| {
+            let context = self.context()?;
+            let (balance_0, balance_1) =
+                self.balances_before_incoming(&context.incoming_alkanes)?;
+            if to == balance_0.id || to == balance_1.id {
+                return Err(anyhow!("INVALID_TO"));
+            }
+            let (reserve_0, reserve_1) = self.previous_reserves(&context.incoming_alkanes)?;
+            let mut excess = AlkaneTransferParcel::default();
+            for (balance, reserve) in [(balance_0, reserve_0), (balance_1, reserve_1)] {
+                if balance.value > reserve.value {
+                    excess.0.push(AlkaneTransfer {
+                        id: balance.id,
+                        value: balance.value - reserve.value,
+                    });
+                }
+            }
+            let mut response = CallResponse::forward(&context.incoming_alkanes);
+            self._pay_to(to, excess, &mut response)?;
             Ok(response)
         })
     }
 
+    // makes the stored reserves match the pool balances
+    fn sync(&self) -> Result<CallResponse> {
+        Lock::lock(|| {
+            let context = self.context()?;
+            let (balance_0, balance_1) =
+                self.balances_before_incoming(&context.incoming_alkanes)?;
+            let (reserve_0, reserve_1) = self.previous_reserves(&context.incoming_alkanes)?;
+            self._update_cum_prices(reserve_0.value, reserve_1.value)?;
+            self.set_stored_reserves(balance_0.value, balance_1.value);
+            Ok(CallResponse::forward(&context.incoming_alkanes))
+        })
+    }
+
     fn get_total_fee(&self) -> Result<CallResponse> {
         let context = self.context()?;
         let mut response = CallResponse::forward(&context.incoming_alkanes);
@@ -518,34 +1121,171 @@ pub trait AMMPoolBase: MintableToken + AlkaneResponder {
     fn set_total_fee(&self, total_fee_per_1000: u128) -> Result<CallResponse> {
         self._only_factory_caller()?;
         let context = self.context()?;
-        self.set_total_fee_per_1000(total_fee_per_1000);
+        let total_fee_per_1000000 = total_fee_per_1000
+            .checked_mul(1000)
+            .filter(|fee| *fee < 1000000)
+            .ok_or_else(|| anyhow!("total fee must be below 1000 per 1000"))?;
+        if total_fee_per_1000000 < self.protocol_fee_per_1000000() {
+            return Err(anyhow!("protocol fee cannot exceed the total fee"));
+        }
+        // accrue the protocol fee owed so far at the old rate before switching
+        let (previous_a, previous_b) = self.previous_reserves(&context.incoming_alkanes)?;
+        self._mint_fee(previous_a.value, previous_b.value)?;
+        self.set_k_last(self._k(previous_a.value, previous_b.value)?);
+        self.set_total_fee_per_1000000(total_fee_per_1000000);
         let response = CallResponse::forward(&context.incoming_alkanes);
         Ok(response)
     }
 
-    fn get_price_cumulative_last(&self) -> Result<CallResponse> {
+    fn get_total_fee_per_million(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = self.total_fee_per_1000000().to_le_bytes().to_vec();
+        Ok(response)
+    }
+
+    // sets both fees at once, as a fee tier below the protocol fee needs its protocol fee
+    // lowered along with the total fee
+    fn set_fees_per_million(
+        &self,
+        total_fee_per_1000000: u128,
+        protocol_fee_per_1000000: u128,
+    ) -> Result<CallResponse> {
+        self._only_factory_caller()?;
+        let context = self.context()?;
+        if total_fee_per_1000000 >= 1000000 {
+            return Err(anyhow!("total fee must be below 1000000 per 1000000"));
+        }
+        if protocol_fee_per_1000000 > total_fee_per_1000000 {
+            return Err(anyhow!("protocol fee cannot exceed the total fee"));
+        }
+        // accrue the protocol fee owed so far at the old rate before switching
+        let (previous_a, previous_b) = self.previous_reserves(&context.incoming_alkanes)?;
+        self._mint_fee(previous_a.value, previous_b.value)?;
+        self.set_k_last(self._k(previous_a.value, previous_b.value)?);
+        self.set_total_fee_per_1000000(total_fee_per_1000000);
+        self.set_protocol_fee_per_1000000(protocol_fee_per_1000000);
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    // amounts in, amounts out, lp fees and protocol fees for token 0 then token 1, followed by
+    // the swap count, 16 bytes each
+    fn get_stats(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = self.stats()?.try_to_vec();
+        Ok(response)
+    }
+
+    fn get_fee_growth_per_lp(&self) -> Result<CallResponse> {
         let context = self.context()?;
         let mut response = CallResponse::forward(&context.incoming_alkanes);
         let mut bytes = Vec::new();
-        let (p0, p1) = self.price_cumulative();
-        bytes.extend_from_slice(&p0.to_le_bytes::<32>());
-        bytes.extend_from_slice(&p1.to_le_bytes::<32>());
+        let (g0, g1) = self.fee_growth_per_lp();
+        bytes.extend_from_slice(&g0.to_le_bytes::<32>());
+        bytes.extend_from_slice(&g1.to_le_bytes::<32>());
         response.data = bytes;
         Ok(response)
     }
 
-    fn get_reserves(&self) -> Result<CallResponse> {
+    fn get_paused(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = (self.paused_state() as u128).to_le_bytes().to_vec();
+        Ok(response)
+    }
+
+    fn set_paused(&self, paused: u128) -> Result<CallResponse> {
+        self._only_factory_caller()?;
+        let context = self.context()?;
+        if paused > 1 {
+            return Err(anyhow!("paused must be 0 or 1"));
+        }
+        self.set_paused_state(paused == 1);
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    fn get_flash_fee(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.flash_fee_per_1000().to_le_bytes());
+        response.data = bytes;
+        Ok(response)
+    }
+
+    fn set_flash_fee(&self, flash_fee_per_1000: u128) -> Result<CallResponse> {
+        self._only_factory_caller()?;
+        let context = self.context()?;
+        if flash_fee_per_1000 > 1000 {
+            return Err(anyhow!("flash fee cannot exceed 1000 per 1000"));
+        }
+        self.set_flash_fee_per_1000(flash_fee_per_1000);
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    fn get_protocol_fee(&self) -> Result<CallResponse> {
         let context = self.context()?;
-        let parcel = context.incoming_alkanes.clone();
-        let (reserve_a, reserve_b) = self.previous_reserves(&parcel)?;
         let mut response = CallResponse::forward(&context.incoming_alkanes);
         let mut bytes = Vec::new();
-        bytes.extend_from_slice(&reserve_a.value.to_le_bytes());
-        bytes.extend_from_slice(&reserve_b.value.to_le_bytes());
+        bytes.extend_from_slice(&self.protocol_fee_per_1000().to_le_bytes());
         response.data = bytes;
         Ok(response)
     }
 
+    fn set_protocol_fee(&self, protocol_fee_per_1000: u128) -> Result<CallResponse> {
+        self._only_factory_caller()?;
+        let context = self.context()?;
+        if protocol_fee_per_1000 * 1000 > self.total_fee_per_1000000() {
+            return Err(anyhow!("protocol fee cannot exceed the total fee"));
+        }
+        // accrue the protocol fee owed so far at the old rate before switching
+        let (previous_a, previous_b) = self.previous_reserves(&context.incoming_alkanes)?;
+        self._mint_fee(previous_a.value, previous_b.value)?;
+        self.set_k_last(self._k(previous_a.value, previous_b.value)?);
+        self.set_protocol_fee_per_1000(protocol_fee_per_1000);
+        let response = CallResponse::forward(&context.incoming_alkanes);
+        Ok(response)
+    }
+
+    fn get_price_cumulative_last(&self) -> Result<CallResponse> {
+        Lock::view(|| {
+            let context = self.context()?;
+            let mut response = CallResponse::forward(&context.incoming_alkanes);
+            let mut bytes = Vec::new();
+            let (p0, p1) = self.price_cumulative();
+            bytes.extend_from_slice(&p0.to_le_bytes::<32>());
+            bytes.extend_from_slice(&p1.to_le_bytes::<32>());
+            response.data = bytes;
+            Ok(response)
+        })
+    }
+
+    // returns the kind of clock the oracle uses and its current time
+    fn get_oracle_clock(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&ORACLE_CLOCK_CLAMPED_BLOCK_TIME.to_le_bytes());
+        bytes.extend_from_slice(&(self._oracle_time()? as u128).to_le_bytes());
+        response.data = bytes;
+        Ok(response)
+    }
+
+    fn get_reserves(&self) -> Result<CallResponse> {
+        Lock::view(|| {
+            let context = self.context()?;
+            let parcel = context.incoming_alkanes.clone();
+            let (reserve_a, reserve_b) = self.previous_reserves(&parcel)?;
+            let mut response = CallResponse::forward(&context.incoming_alkanes);
+            let mut bytes = Vec::new();
+            bytes.extend_from_slice(&reserve_a.value.to_le_bytes());
+            bytes.extend_from_slice(&reserve_b.value.to_le_bytes());
+            response.data = bytes;
+            Ok(response)
+        })
+    }
+
     fn get_name(&self) -> Result<CallResponse> {
         let context = self.context()?;
         let mut response = CallResponse::forward(&context.incoming_alkanes);
@@ -554,25 +1294,25 @@ pub trait AMMPoolBase: MintableToken + AlkaneResponder {
     }
 
     fn pool_details(&self) -> Result<CallResponse> {
-        let context = self.context()?;
-        println!("in pool details");
-        let (reserve_a, reserve_b) = self.previous_reserves(&context.incoming_alkanes)?;
-        println!("after previous reserves");
-        let (token_a, token_b) = self.alkanes_for_self()?;
-
-        let pool_info = PoolInfo {
-            token_a,
-            token_b,
-            reserve_a: reserve_a.value,
-            reserve_b: reserve_b.value,
-            total_supply: self.total_supply(),
-            pool_name: self.name(),
-        };
+        Lock::view(|| {
+            let context = self.context()?;
+            let (reserve_a, reserve_b) = self.previous_reserves(&context.incoming_alkanes)?;
+            let (token_a, token_b) = self.alkanes_for_self()?;
 
-        let mut response = CallResponse::forward(&context.incoming_alkanes.clone());
-        response.data = pool_info.try_to_vec();
+            let pool_info = PoolInfo {
+                token_a,
+                token_b,
+                reserve_a: reserve_a.value,
+                reserve_b: reserve_b.value,
+                total_supply: self.total_supply(),
+                pool_name: self.name(),
+            };
 
-        Ok(response)
+            let mut response = CallResponse::forward(&context.incoming_alkanes.clone());
+            response.data = pool_info.try_to_vec();
+
+            Ok(response)
+        })
     }
     fn pull_ids(&self, v: &mut Vec<u128>) -> Option<(AlkaneId, AlkaneId)> {
         let a_block = shift(v)?;
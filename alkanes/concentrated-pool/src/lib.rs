@@ -0,0 +1,91 @@
+use alkanes_runtime::{declare_alkane, message::MessageDispatch, runtime::AlkaneResponder};
+#[allow(unused_imports)]
+use alkanes_runtime::{
+    println,
+    stdio::{stdout, Write},
+};
+use alkanes_runtime_concentrated_pool::ConcentratedPoolBase;
+use alkanes_support::id::AlkaneId;
+use anyhow::Result;
+use metashrew_support::compat::{to_arraybuffer_layout, to_passback_ptr};
+
+// ticks are i32 values passed as the two's complement of an i128. Swaps stop once the price
+// reaches the tick_limit, pass MIN_TICK or MAX_TICK in the direction of the swap for no limit.
+#[derive(MessageDispatch)]
+pub enum ConcentratedPoolMessage {
+    #[opcode(0)]
+    InitPool {
+        alkane_a: AlkaneId,
+        alkane_b: AlkaneId,
+        factory: AlkaneId,
+        fee_per_1000000: u128,
+        tick_spacing: u128,
+        price_numerator: u128,
+        price_denominator: u128,
+    },
+
+    #[opcode(1)]
+    MintPosition { tick_lower: u128, tick_upper: u128 },
+
+    #[opcode(2)]
+    IncreaseLiquidity,
+
+    #[opcode(3)]
+    Burn { liquidity: u128 },
+
+    #[opcode(4)]
+    Collect,
+
+    #[opcode(5)]
+    SwapExactInput {
+        amount_out_min: u128,
+        tick_limit: u128,
+        deadline: u128,
+    },
+
+    #[opcode(6)]
+    SwapExactOutput {
+        amount_out: u128,
+        tick_limit: u128,
+        deadline: u128,
+    },
+
+    // same opcodes as the constant product pool, so the factory pauses either the same way
+    #[opcode(33)]
+    #[returns(u128)]
+    GetPaused,
+
+    #[opcode(34)]
+    SetPaused { paused: u128 },
+
+    #[opcode(50)]
+    ForwardIncoming,
+
+    #[opcode(97)]
+    #[returns(Vec<u8>)]
+    GetSlot0,
+
+    #[opcode(98)]
+    #[returns(Vec<u8>)]
+    GetPosition { position_id: AlkaneId },
+
+    #[opcode(99)]
+    #[returns(String)]
+    GetName,
+
+    #[opcode(999)]
+    #[returns(Vec<u8>)]
+    PoolDetails,
+}
+
+#[derive(Default)]
+pub struct ConcentratedPool();
+
+impl ConcentratedPoolBase for ConcentratedPool {}
+
+impl AlkaneResponder for ConcentratedPool {}
+declare_alkane! {
+    impl AlkaneResponder for ConcentratedPool {
+        type Message = ConcentratedPoolMessage;
+    }
+}
@@ -36,6 +36,20 @@ enum FlashSwapMessage {
         amount_1_out: u128,
         data: Vec<u128>,
     },
+
+    // keeps whatever it is paid, standing in for a vault receiving router outputs
+    #[opcode(72656376)]
+    ReceiveCallback { caller: AlkaneId },
+
+    #[opcode(7265706179)]
+    FlashLoanCallback {
+        caller: AlkaneId,
+        amount_0: u128,
+        amount_1: u128,
+        fee_0: u128,
+        fee_1: u128,
+        data: Vec<u128>,
+    },
 }
 
 impl FlashSwap {
@@ -139,6 +153,22 @@ impl FlashSwap {
             self.arb_call(data)
         }
     }
+
+    fn receive_callback(&self, caller: AlkaneId) -> Result<CallResponse> {
+        self.no_refund()
+    }
+
+    fn flash_loan_callback(
+        &self,
+        caller: AlkaneId,
+        amount_0: u128,
+        amount_1: u128,
+        fee_0: u128,
+        fee_1: u128,
+        data: Vec<u128>,
+    ) -> Result<CallResponse> {
+        self.callback(caller, amount_0, amount_1, data)
+    }
 }
 
 impl AlkaneResponder for FlashSwap {}
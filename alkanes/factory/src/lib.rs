@@ -42,20 +42,56 @@ pub enum AMMFactoryMessage {
     #[returns(Vec<u8>)]
     GetNumPools,
 
+    #[opcode(5)]
+    CreateConcentratedPool {
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+        fee_per_1000000: u128,
+        tick_spacing: u128,
+        price_numerator: u128,
+        price_denominator: u128,
+    },
+
+    #[opcode(6)]
+    FindExistingConcentratedPoolId {
+        alkane_a: AlkaneId,
+        alkane_b: AlkaneId,
+        fee_per_1000000: u128,
+    },
+
     #[opcode(7)]
     SetPoolFactoryId { pool_factory_id: u128 },
 
+    #[opcode(8)]
+    SetPoolTemplate { template: u128, beacon_id: AlkaneId },
+
+    #[opcode(9)]
+    CreateNewStablePool {
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+        amount_a: u128,
+        amount_b: u128,
+        amp: u128,
+    },
+
     #[opcode(10)]
     CollectFees { pool_id: AlkaneId },
 
+    // router operations pay what they produce to `to`, or to the caller when it is zero, and
+    // return unused inputs to the caller. A non-zero `to` must be a contract implementing the
+    // RECEIVE_EXTCALL_OPCODE (72656376) receive hook, otherwise the operation reverts; contracts
+    // that need the outputs back in the call response must pass (0, 0).
+    // Liquidity operations go through the constant product pool of the pair in fee_tier
     #[opcode(11)]
     AddLiquidity {
         token_a: AlkaneId,
         token_b: AlkaneId,
+        fee_tier: u128,
         amount_a_desired: u128,
         amount_b_desired: u128,
         amount_a_min: u128,
         amount_b_min: u128,
+        to: AlkaneId,
         deadline: u128,
     },
 
@@ -63,9 +99,11 @@ pub enum AMMFactoryMessage {
     Burn {
         token_a: AlkaneId,
         token_b: AlkaneId,
+        fee_tier: u128,
         liquidity: u128,
         amount_a_min: u128,
         amount_b_min: u128,
+        to: AlkaneId,
         deadline: u128,
     },
     #[opcode(13)]
@@ -73,6 +111,7 @@ pub enum AMMFactoryMessage {
         path: Vec<AlkaneId>,
         amount_in: u128,
         amount_out_min: u128,
+        to: AlkaneId,
         deadline: u128,
     },
 
@@ -81,22 +120,168 @@ pub enum AMMFactoryMessage {
         path: Vec<AlkaneId>,
         amount_out: u128,
         amount_in_max: u128,
+        to: AlkaneId,
         deadline: u128,
     },
 
+    #[opcode(15)]
+    CreateNewWeightedPool {
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+        amount_a: u128,
+        amount_b: u128,
+        weight_a: u128,
+    },
+
+    // amount_in is in token_a, which is partly swapped for token_b before adding liquidity
+    #[opcode(16)]
+    ZapIn {
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+        fee_tier: u128,
+        amount_in: u128,
+        min_lp_out: u128,
+        to: AlkaneId,
+        deadline: u128,
+    },
+
+    // burns liquidity and pays out only token_a, swapping the token_b share of the pool
+    #[opcode(17)]
+    ZapOut {
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+        fee_tier: u128,
+        liquidity: u128,
+        amount_out_min: u128,
+        to: AlkaneId,
+        deadline: u128,
+    },
+
+    // enabled is 1 to allow new pools in the fee tier (total fee per 1000000), 0 to stop them.
+    // Pools already created in the tier stay routable
+    #[opcode(18)]
+    SetFeeTier { fee_tier: u128, enabled: u128 },
+
+    // FindExistingPoolId looks up the default fee tier
+    #[opcode(19)]
+    FindExistingPoolIdForFeeTier {
+        alkane_a: AlkaneId,
+        alkane_b: AlkaneId,
+        fee_tier: u128,
+    },
+
+    #[opcode(20)]
+    CreateNewPoolWithFeeTier {
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+        amount_a: u128,
+        amount_b: u128,
+        fee_tier: u128,
+    },
+
+    // rejected for pools in a fee tier other than the default one
     #[opcode(21)]
     SetTotalFeeForPool {
         pool_id: AlkaneId,
         total_fee_per_1000: u128,
     },
 
+    #[opcode(22)]
+    SetProtocolFeeForPool {
+        pool_id: AlkaneId,
+        protocol_fee_per_1000: u128,
+    },
+
+    #[opcode(23)]
+    SetFlashFeeForPool {
+        pool_id: AlkaneId,
+        flash_fee_per_1000: u128,
+    },
+
+    // 1 pauses swaps and deposits on the pool, 0 resumes them
+    #[opcode(24)]
+    SetPausedForPool { pool_id: AlkaneId, paused: u128 },
+
+    #[opcode(25)]
+    #[returns(Vec<u8>)]
+    GetFeeTiers,
+
+    // routes through the best path of at most MAX_ROUTE_HOPS pools over the route tokens
+    #[opcode(26)]
+    SwapExactTokensForTokensBestPath {
+        token_in: AlkaneId,
+        token_out: AlkaneId,
+        amount_in: u128,
+        amount_out_min: u128,
+        to: AlkaneId,
+        deadline: u128,
+    },
+
+    #[opcode(27)]
+    SwapTokensForExactTokensBestPath {
+        token_in: AlkaneId,
+        token_out: AlkaneId,
+        amount_out: u128,
+        amount_in_max: u128,
+        to: AlkaneId,
+        deadline: u128,
+    },
+
+    // 1 allows the best path search to route through the token, 0 removes it
+    #[opcode(28)]
+    SetRouteToken { token: AlkaneId, enabled: u128 },
+
     #[opcode(29)]
     SwapExactTokensForTokensImplicit {
         path: Vec<AlkaneId>,
         amount_out_min: u128,
+        to: AlkaneId,
         deadline: u128,
     },
 
+    #[opcode(30)]
+    #[returns(Vec<u8>)]
+    GetRouteTokens,
+
+    // paths are concatenated, each ending in the output alkane, and shares are the parts of
+    // amount_in each path swaps, out of SPLIT_SHARE_DENOMINATOR
+    #[opcode(31)]
+    SwapExactTokensForTokensSplit {
+        paths: Vec<AlkaneId>,
+        shares: Vec<u128>,
+        amount_in: u128,
+        amount_out_min: u128,
+        to: AlkaneId,
+        deadline: u128,
+    },
+
+    // quotes return the amounts along the path and, per hop, the pool, its fee and its price
+    // impact in parts of PRICE_IMPACT_DENOMINATOR
+    #[opcode(32)]
+    #[returns(Vec<u8>)]
+    GetAmountsOut {
+        amount_in: u128,
+        path: Vec<AlkaneId>,
+    },
+
+    #[opcode(33)]
+    #[returns(Vec<u8>)]
+    GetAmountsIn {
+        amount_out: u128,
+        path: Vec<AlkaneId>,
+    },
+
+    // concentrated pools are not part of GetAllPools
+    #[opcode(34)]
+    #[returns(Vec<u8>)]
+    GetAllConcentratedPools,
+
+    // the template the pool was deployed from, pools created before templates existed are
+    // constant product
+    #[opcode(35)]
+    #[returns(u128)]
+    GetPoolTemplate { pool_id: AlkaneId },
+
     #[opcode(50)]
     Forward {},
 }
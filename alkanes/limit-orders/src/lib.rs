@@ -0,0 +1,387 @@
+use alkanes_runtime::runtime::AlkaneResponder;
+use alkanes_runtime::storage::StoragePointer;
+use alkanes_runtime::{declare_alkane, message::MessageDispatch};
+#[allow(unused_imports)]
+use alkanes_runtime::{
+    println,
+    stdio::{stdout, Write},
+};
+use alkanes_support::cellpack::Cellpack;
+use alkanes_support::id::AlkaneId;
+use alkanes_support::parcel::{AlkaneTransfer, AlkaneTransferParcel};
+use alkanes_support::response::CallResponse;
+use anyhow::{anyhow, Result};
+use metashrew_support::compat::{to_arraybuffer_layout, to_passback_ptr};
+use metashrew_support::index_pointer::KeyValuePointer;
+use metashrew_support::utils::consume_u128;
+use oylswap_library::{create_ownership_token, get_amount_out_per_1000000, PoolInfo};
+use std::sync::Arc;
+
+// "sell amount_in of token_in for at least amount_out_min of token_out". The keeper filling the
+// order is paid keeper_tip out of the proceeds, so the pool has to return at least
+// amount_out_min + keeper_tip. amount_received stays 0 until the order is filled.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct LimitOrder {
+    pub token_in: AlkaneId,
+    pub token_out: AlkaneId,
+    pub amount_in: u128,
+    pub amount_out_min: u128,
+    pub keeper_tip: u128,
+    pub expiry: u128,
+    pub amount_received: u128,
+}
+
+impl LimitOrder {
+    pub fn try_to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.token_in.block.to_le_bytes());
+        bytes.extend_from_slice(&self.token_in.tx.to_le_bytes());
+        bytes.extend_from_slice(&self.token_out.block.to_le_bytes());
+        bytes.extend_from_slice(&self.token_out.tx.to_le_bytes());
+        bytes.extend_from_slice(&self.amount_in.to_le_bytes());
+        bytes.extend_from_slice(&self.amount_out_min.to_le_bytes());
+        bytes.extend_from_slice(&self.keeper_tip.to_le_bytes());
+        bytes.extend_from_slice(&self.expiry.to_le_bytes());
+        bytes.extend_from_slice(&self.amount_received.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_vec(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 144 {
+            return Err(anyhow!("Invalid bytes length for LimitOrder"));
+        }
+        Ok(LimitOrder {
+            token_in: AlkaneId::new(
+                u128::from_le_bytes(bytes[0..16].try_into()?),
+                u128::from_le_bytes(bytes[16..32].try_into()?),
+            ),
+            token_out: AlkaneId::new(
+                u128::from_le_bytes(bytes[32..48].try_into()?),
+                u128::from_le_bytes(bytes[48..64].try_into()?),
+            ),
+            amount_in: u128::from_le_bytes(bytes[64..80].try_into()?),
+            amount_out_min: u128::from_le_bytes(bytes[80..96].try_into()?),
+            keeper_tip: u128::from_le_bytes(bytes[96..112].try_into()?),
+            expiry: u128::from_le_bytes(bytes[112..128].try_into()?),
+            amount_received: u128::from_le_bytes(bytes[128..144].try_into()?),
+        })
+    }
+
+    pub fn is_filled(&self) -> bool {
+        self.amount_received != 0
+    }
+}
+
+#[derive(Default)]
+pub struct LimitOrders(());
+
+#[derive(MessageDispatch)]
+enum LimitOrdersMessage {
+    #[opcode(0)]
+    Initialize { factory: AlkaneId },
+
+    // the tokens to sell are sent along with the call. Returns the order token
+    #[opcode(1)]
+    PlaceOrder {
+        token_out: AlkaneId,
+        amount_out_min: u128,
+        keeper_tip: u128,
+        expiry: u128,
+    },
+
+    // fills through the constant product pool of the pair in the default fee tier
+    #[opcode(2)]
+    FillOrder { order_id: AlkaneId },
+
+    // the order token is sent along with the call. Pays the proceeds of a filled order,
+    // or cancels an open one and refunds it
+    #[opcode(3)]
+    WithdrawOrder {},
+
+    #[opcode(4)]
+    #[returns(Vec<u8>)]
+    GetOrder { order_id: AlkaneId },
+
+    // the pool's flash swap callback during FillOrder, data is the order being filled
+    #[opcode(73776170)]
+    SwapCallback {
+        caller: AlkaneId,
+        amount_0_out: u128,
+        amount_1_out: u128,
+        data: Vec<u128>,
+    },
+}
+
+impl LimitOrders {
+    fn factory(&self) -> Result<AlkaneId> {
+        let ptr = StoragePointer::from_keyword("/factory_id")
+            .get()
+            .as_ref()
+            .clone();
+        let mut cursor = std::io::Cursor::<Vec<u8>>::new(ptr);
+        Ok(AlkaneId::new(
+            consume_u128(&mut cursor)?,
+            consume_u128(&mut cursor)?,
+        ))
+    }
+
+    fn set_factory(&self, factory_id: AlkaneId) {
+        let mut factory_id_pointer = StoragePointer::from_keyword("/factory_id");
+        factory_id_pointer.set(Arc::new(factory_id.into()));
+    }
+
+    // the pool and the order of the fill in progress, empty outside of FillOrder
+    fn filling_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/filling")
+    }
+
+    fn order_pointer(&self, order_id: &AlkaneId) -> StoragePointer {
+        StoragePointer::from_keyword("/orders/").select(&order_id.clone().into())
+    }
+
+    fn order(&self, order_id: &AlkaneId) -> Result<LimitOrder> {
+        let bytes = self.order_pointer(order_id).get();
+        if bytes.len() == 0 {
+            return Err(anyhow!("order {:?} does not exist", order_id));
+        }
+        LimitOrder::from_vec(&bytes)
+    }
+
+    fn set_order(&self, order_id: &AlkaneId, order: &LimitOrder) {
+        self.order_pointer(order_id)
+            .set(Arc::new(order.try_to_vec()));
+    }
+
+    fn initialize(&self, factory: AlkaneId) -> Result<CallResponse> {
+        self.observe_initialization()?;
+        let context = self.context()?;
+        self.set_factory(factory);
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    fn place_order(
+        &self,
+        token_out: AlkaneId,
+        amount_out_min: u128,
+        keeper_tip: u128,
+        expiry: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        if context.incoming_alkanes.0.len() != 1 {
+            return Err(anyhow!("must send exactly one alkane to sell"));
+        }
+        let incoming = context.incoming_alkanes.0[0].clone();
+        if incoming.value == 0 {
+            return Err(anyhow!("INSUFFICIENT_INPUT_AMOUNT"));
+        }
+        if incoming.id == token_out {
+            return Err(anyhow!("token_in and token_out must differ"));
+        }
+        if amount_out_min == 0 {
+            return Err(anyhow!("amount_out_min must be positive"));
+        }
+        let order_token = create_ownership_token(self)?;
+        self.set_order(
+            &order_token.id,
+            &LimitOrder {
+                token_in: incoming.id,
+                token_out,
+                amount_in: incoming.value,
+                amount_out_min,
+                keeper_tip,
+                expiry,
+                amount_received: 0,
+            },
+        );
+        let mut response = CallResponse::default();
+        response.alkanes.pay(order_token);
+        Ok(response)
+    }
+
+    // anyone can fill an open order once the pool pays at least amount_out_min plus the tip for
+    // it. The pool sends the bought tokens first and is paid the order's tokens in
+    // swap_callback. The keeper gets the tip and the rest is kept for the order owner
+    fn fill_order(&self, order_id: AlkaneId) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut order = self.order(&order_id)?;
+        if order.is_filled() {
+            return Err(anyhow!("ORDER_ALREADY_FILLED"));
+        }
+        if order.expiry != 0 && self.height() as u128 > order.expiry {
+            return Err(anyhow!("ORDER_EXPIRED"));
+        }
+        let amount_out_min = order
+            .amount_out_min
+            .checked_add(order.keeper_tip)
+            .ok_or_else(|| anyhow!("keeper tip overflows"))?;
+        let pool = self._find_pool(order.token_in, order.token_out)?;
+        let pool_info = PoolInfo::from_vec(
+            &self
+                .call(
+                    &Cellpack {
+                        target: pool,
+                        inputs: vec![999],
+                    },
+                    &AlkaneTransferParcel::default(),
+                    self.fuel(),
+                )?
+                .data,
+        )?;
+        let (reserve_in, reserve_out) = if order.token_in == pool_info.token_a {
+            (pool_info.reserve_a, pool_info.reserve_b)
+        } else {
+            (pool_info.reserve_b, pool_info.reserve_a)
+        };
+        let amount_out = get_amount_out_per_1000000(
+            order.amount_in,
+            reserve_in,
+            reserve_out,
+            self._get_total_fee(pool)?,
+        )?;
+        if amount_out < amount_out_min {
+            return Err(anyhow!("predicate failed: insufficient output"));
+        }
+        let (amount_0_out, amount_1_out) = if order.token_out == pool_info.token_a {
+            (amount_out, 0)
+        } else {
+            (0, amount_out)
+        };
+
+        let mut filling: Vec<u8> = pool.into();
+        let order_bytes: Vec<u8> = order_id.into();
+        filling.extend(order_bytes);
+        self.filling_pointer().set(Arc::new(filling));
+        self.call(
+            &Cellpack {
+                target: pool,
+                inputs: vec![
+                    3,
+                    amount_0_out,
+                    amount_1_out,
+                    context.myself.block,
+                    context.myself.tx,
+                    2,
+                    order_id.block,
+                    order_id.tx,
+                ],
+            },
+            &AlkaneTransferParcel::default(),
+            self.fuel(),
+        )?;
+        self.filling_pointer().set(Arc::new(vec![]));
+
+        order.amount_received = amount_out - order.keeper_tip;
+        self.set_order(&order_id, &order);
+
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        if order.keeper_tip > 0 {
+            response.alkanes.pay(AlkaneTransfer {
+                id: order.token_out,
+                value: order.keeper_tip,
+            });
+        }
+        Ok(response)
+    }
+
+    // Pays the pool the tokens of the order being filled. Only the pool of the fill in progress
+    // gets here with this contract as the swap's caller, anyone else could otherwise take the
+    // tokens of an open order by swapping with this contract as `to`
+    fn swap_callback(
+        &self,
+        caller: AlkaneId,
+        _amount_0_out: u128,
+        _amount_1_out: u128,
+        data: Vec<u128>,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        let filling = self.filling_pointer().get();
+        if filling.len() != 64 || caller != context.myself || data.len() != 2 {
+            return Err(anyhow!("swap callback outside of a fill"));
+        }
+        let mut cursor = std::io::Cursor::<Vec<u8>>::new(filling.as_ref().clone());
+        let pool = AlkaneId::new(consume_u128(&mut cursor)?, consume_u128(&mut cursor)?);
+        let order_id = AlkaneId::new(consume_u128(&mut cursor)?, consume_u128(&mut cursor)?);
+        if context.caller != pool || AlkaneId::new(data[0], data[1]) != order_id {
+            return Err(anyhow!("swap callback outside of a fill"));
+        }
+        let order = self.order(&order_id)?;
+        let mut response = CallResponse::default();
+        response.alkanes.pay(AlkaneTransfer {
+            id: order.token_in,
+            value: order.amount_in,
+        });
+        Ok(response)
+    }
+
+    fn _find_pool(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<AlkaneId> {
+        let response = self.call(
+            &Cellpack {
+                target: self.factory()?,
+                inputs: vec![2, token_a.block, token_a.tx, token_b.block, token_b.tx],
+            },
+            &AlkaneTransferParcel::default(),
+            self.fuel(),
+        )?;
+        let mut cursor = std::io::Cursor::<Vec<u8>>::new(response.data);
+        Ok(AlkaneId::new(
+            consume_u128(&mut cursor)?,
+            consume_u128(&mut cursor)?,
+        ))
+    }
+
+    // per 1000000, pools in fee tiers can charge less than 0.1%
+    fn _get_total_fee(&self, pool: AlkaneId) -> Result<u128> {
+        let response = self.call(
+            &Cellpack {
+                target: pool,
+                inputs: vec![37],
+            },
+            &AlkaneTransferParcel::default(),
+            self.fuel(),
+        )?;
+        Ok(u128::from_le_bytes(response.data[..16].try_into()?))
+    }
+
+    fn withdraw_order(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        if context.incoming_alkanes.0.len() != 1 {
+            return Err(anyhow!("must send exactly one order token"));
+        }
+        if context.incoming_alkanes.0[0].value == 0 {
+            return Err(anyhow!("must send exactly one order token"));
+        }
+        let order_id = context.incoming_alkanes.0[0].id;
+        let order = self.order(&order_id)?;
+        // the order token stays with the contract, so the order can only be withdrawn once
+        self.order_pointer(&order_id).set(Arc::new(vec![]));
+
+        let mut response = CallResponse::default();
+        if order.is_filled() {
+            response.alkanes.pay(AlkaneTransfer {
+                id: order.token_out,
+                value: order.amount_received,
+            });
+        } else {
+            response.alkanes.pay(AlkaneTransfer {
+                id: order.token_in,
+                value: order.amount_in,
+            });
+        }
+        Ok(response)
+    }
+
+    fn get_order(&self, order_id: AlkaneId) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = self.order(&order_id)?.try_to_vec();
+        Ok(response)
+    }
+}
+
+impl AlkaneResponder for LimitOrders {}
+
+declare_alkane! {
+    impl AlkaneResponder for LimitOrders {
+        type Message = LimitOrdersMessage;
+    }
+}
@@ -0,0 +1,234 @@
+use alkanes_runtime::runtime::AlkaneResponder;
+use alkanes_runtime::storage::StoragePointer;
+use alkanes_runtime::{declare_alkane, message::MessageDispatch};
+#[allow(unused_imports)]
+use alkanes_runtime::{
+    println,
+    stdio::{stdout, Write},
+};
+use alkanes_support::id::AlkaneId;
+use alkanes_support::parcel::AlkaneTransfer;
+use alkanes_support::response::CallResponse;
+use anyhow::{anyhow, Result};
+use metashrew_support::compat::{to_arraybuffer_layout, to_passback_ptr};
+use metashrew_support::index_pointer::KeyValuePointer;
+use oylswap_library::{check_factory_pool, create_ownership_token, U256};
+use std::sync::Arc;
+
+// `amount` of `token` locked until `unlock_height`. With a vesting_end_height the amount then
+// vests linearly until that height instead of unlocking all at once.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct LpLock {
+    pub token: AlkaneId,
+    pub amount: u128,
+    pub claimed: u128,
+    pub unlock_height: u128,
+    pub vesting_end_height: u128,
+}
+
+impl LpLock {
+    pub fn try_to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.token.block.to_le_bytes());
+        bytes.extend_from_slice(&self.token.tx.to_le_bytes());
+        bytes.extend_from_slice(&self.amount.to_le_bytes());
+        bytes.extend_from_slice(&self.claimed.to_le_bytes());
+        bytes.extend_from_slice(&self.unlock_height.to_le_bytes());
+        bytes.extend_from_slice(&self.vesting_end_height.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_vec(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 96 {
+            return Err(anyhow!("Invalid bytes length for LpLock"));
+        }
+        Ok(LpLock {
+            token: AlkaneId::new(
+                u128::from_le_bytes(bytes[0..16].try_into()?),
+                u128::from_le_bytes(bytes[16..32].try_into()?),
+            ),
+            amount: u128::from_le_bytes(bytes[32..48].try_into()?),
+            claimed: u128::from_le_bytes(bytes[48..64].try_into()?),
+            unlock_height: u128::from_le_bytes(bytes[64..80].try_into()?),
+            vesting_end_height: u128::from_le_bytes(bytes[80..96].try_into()?),
+        })
+    }
+
+    // total amount released by `height`, claimed or not
+    pub fn vested(&self, height: u128) -> u128 {
+        if height < self.unlock_height {
+            0
+        } else if self.vesting_end_height == 0 || height >= self.vesting_end_height {
+            self.amount
+        } else {
+            let elapsed = height - self.unlock_height;
+            let duration = self.vesting_end_height - self.unlock_height;
+            // exact in U256, so what has vested never goes down as the height goes up
+            (U256::from(self.amount) * U256::from(elapsed) / U256::from(duration)).to::<u128>()
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct LpLocker(());
+
+#[derive(MessageDispatch)]
+enum LpLockerMessage {
+    #[opcode(0)]
+    Initialize { factory: AlkaneId },
+
+    // the lp tokens of a constant product pool of the factory are sent along with the call.
+    // vesting_end_height is 0 for a plain time lock. Returns the receipt token
+    #[opcode(1)]
+    Lock {
+        unlock_height: u128,
+        vesting_end_height: u128,
+    },
+
+    // the receipt token is sent along with the call. Pays out everything released so far and
+    // returns the receipt while some of the lock is still to be released
+    #[opcode(2)]
+    Redeem {},
+
+    #[opcode(3)]
+    #[returns(Vec<u8>)]
+    GetLock { lock_id: AlkaneId },
+
+    // amount of the lp token `token` currently held in locks, so a launch can prove its locked
+    // liquidity
+    #[opcode(4)]
+    #[returns(Vec<u8>)]
+    GetTotalLocked { token: AlkaneId },
+}
+
+impl LpLocker {
+    fn factory(&self) -> Result<AlkaneId> {
+        Ok(StoragePointer::from_keyword("/factory_id")
+            .get()
+            .as_ref()
+            .clone()
+            .try_into()?)
+    }
+
+    fn lock_pointer(&self, lock_id: &AlkaneId) -> StoragePointer {
+        StoragePointer::from_keyword("/locks/").select(&lock_id.clone().into())
+    }
+
+    fn lock_of(&self, lock_id: &AlkaneId) -> Result<LpLock> {
+        let bytes = self.lock_pointer(lock_id).get();
+        if bytes.len() == 0 {
+            return Err(anyhow!("lock {:?} does not exist", lock_id));
+        }
+        LpLock::from_vec(&bytes)
+    }
+
+    fn set_lock(&self, lock_id: &AlkaneId, lock: &LpLock) {
+        self.lock_pointer(lock_id).set(Arc::new(lock.try_to_vec()));
+    }
+
+    fn total_locked_pointer(&self, token: &AlkaneId) -> StoragePointer {
+        StoragePointer::from_keyword("/totallocked/").select(&token.clone().into())
+    }
+
+    fn initialize(&self, factory: AlkaneId) -> Result<CallResponse> {
+        self.observe_initialization()?;
+        let context = self.context()?;
+        StoragePointer::from_keyword("/factory_id").set(Arc::new(factory.into()));
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    fn lock(&self, unlock_height: u128, vesting_end_height: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        if context.incoming_alkanes.0.len() != 1 || context.incoming_alkanes.0[0].value == 0 {
+            return Err(anyhow!("must send exactly one alkane to lock"));
+        }
+        let incoming = context.incoming_alkanes.0[0].clone();
+        // only pool lp tokens, so the locked totals prove locked liquidity
+        check_factory_pool(self, self.factory()?, incoming.id)?;
+        if unlock_height <= self.height() as u128 {
+            return Err(anyhow!("unlock height must be in the future"));
+        }
+        if vesting_end_height != 0 && vesting_end_height <= unlock_height {
+            return Err(anyhow!("vesting must end after the unlock height"));
+        }
+
+        let receipt = create_ownership_token(self)?;
+        self.set_lock(
+            &receipt.id,
+            &LpLock {
+                token: incoming.id,
+                amount: incoming.value,
+                claimed: 0,
+                unlock_height,
+                vesting_end_height,
+            },
+        );
+        let mut total_locked = self.total_locked_pointer(&incoming.id);
+        let total = total_locked.get_value::<u128>();
+        total_locked.set_value::<u128>(total + incoming.value);
+
+        let mut response = CallResponse::default();
+        response.alkanes.pay(receipt);
+        Ok(response)
+    }
+
+    fn redeem(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        if context.incoming_alkanes.0.len() != 1 || context.incoming_alkanes.0[0].value == 0 {
+            return Err(anyhow!("must send exactly one receipt token"));
+        }
+        let lock_id = context.incoming_alkanes.0[0].id;
+        let mut lock = self.lock_of(&lock_id)?;
+        let claimable = lock.vested(self.height() as u128) - lock.claimed;
+        if claimable == 0 {
+            return Err(anyhow!("nothing to redeem yet"));
+        }
+        lock.claimed += claimable;
+        let mut total_locked = self.total_locked_pointer(&lock.token);
+        let total = total_locked.get_value::<u128>();
+        total_locked.set_value::<u128>(total - claimable);
+
+        let mut response = CallResponse::default();
+        response.alkanes.pay(AlkaneTransfer {
+            id: lock.token,
+            value: claimable,
+        });
+        if lock.claimed == lock.amount {
+            // the receipt stays with the contract once everything is released
+            self.lock_pointer(&lock_id).set(Arc::new(vec![]));
+        } else {
+            self.set_lock(&lock_id, &lock);
+            response.alkanes.pay(AlkaneTransfer {
+                id: lock_id,
+                value: 1,
+            });
+        }
+        Ok(response)
+    }
+
+    fn get_lock(&self, lock_id: AlkaneId) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = self.lock_of(&lock_id)?.try_to_vec();
+        Ok(response)
+    }
+
+    fn get_total_locked(&self, token: AlkaneId) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = self
+            .total_locked_pointer(&token)
+            .get_value::<u128>()
+            .to_le_bytes()
+            .to_vec();
+        Ok(response)
+    }
+}
+
+impl AlkaneResponder for LpLocker {}
+
+declare_alkane! {
+    impl AlkaneResponder for LpLocker {
+        type Message = LpLockerMessage;
+    }
+}
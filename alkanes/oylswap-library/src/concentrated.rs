@@ -0,0 +1,564 @@
+// Tick-based concentrated liquidity math, ported from the Uniswap v3 core libraries
+// (FullMath, TickMath, SqrtPriceMath, SwapMath, TickBitmap and LiquidityAmounts).
+// Prices are stored as sqrt(token1 / token0) in Q64.96 fixed point and fee growth
+// accumulators are Q128.128 values with wrapping semantics, exactly like v3.
+use crate::U256;
+use anyhow::{anyhow, Result};
+use ruint::Uint;
+
+pub type U512 = Uint<512, 8>;
+
+pub const MIN_TICK: i32 = -887272;
+pub const MAX_TICK: i32 = 887272;
+pub const RESOLUTION: usize = 96;
+// 1e6 denominator for pool fees, i.e. a fee of 3000 is 0.3%
+pub const FEE_DENOMINATOR: u128 = 1_000_000;
+
+pub fn q96() -> U256 {
+    U256::from(1) << RESOLUTION
+}
+
+pub fn q128() -> U256 {
+    U256::from(1) << 128usize
+}
+
+pub fn min_sqrt_ratio() -> U256 {
+    U256::from(4295128739u64)
+}
+
+// 1461446703485210103287273052203988822378723970342, the sqrt price of MAX_TICK
+pub fn max_sqrt_ratio() -> U256 {
+    U256::from_limbs([0x5d951d5263988d26, 0xefd1fc6a50648849, 0xfffd8963, 0])
+}
+
+fn narrow(value: U512) -> Result<U256> {
+    if value > U512::from(U256::MAX) {
+        return Err(anyhow!("math overflow"));
+    }
+    Ok(value.to())
+}
+
+pub fn mul_div(a: U256, b: U256, denominator: U256) -> Result<U256> {
+    if denominator.is_zero() {
+        return Err(anyhow!("division by zero"));
+    }
+    narrow(U512::from(a) * U512::from(b) / U512::from(denominator))
+}
+
+pub fn mul_div_rounding_up(a: U256, b: U256, denominator: U256) -> Result<U256> {
+    if denominator.is_zero() {
+        return Err(anyhow!("division by zero"));
+    }
+    let product = U512::from(a) * U512::from(b);
+    let denominator = U512::from(denominator);
+    let mut result = product / denominator;
+    if !(product % denominator).is_zero() {
+        result += U512::from(1);
+    }
+    narrow(result)
+}
+
+pub fn div_rounding_up(a: U256, b: U256) -> Result<U256> {
+    if b.is_zero() {
+        return Err(anyhow!("division by zero"));
+    }
+    let result = a / b;
+    if (a % b).is_zero() {
+        Ok(result)
+    } else {
+        Ok(result + U256::from(1))
+    }
+}
+
+pub fn add_delta(x: u128, y: i128) -> Result<u128> {
+    if y < 0 {
+        x.checked_sub(y.unsigned_abs())
+            .ok_or_else(|| anyhow!("LS: liquidity underflow"))
+    } else {
+        x.checked_add(y as u128)
+            .ok_or_else(|| anyhow!("LA: liquidity overflow"))
+    }
+}
+
+// sqrt(1.0001^tick) * 2^96, see TickMath.getSqrtRatioAtTick
+pub fn get_sqrt_ratio_at_tick(tick: i32) -> Result<U256> {
+    let abs_tick = tick.unsigned_abs();
+    if abs_tick > MAX_TICK as u32 {
+        return Err(anyhow!("T: tick out of range"));
+    }
+    // 2^128 / sqrt(1.0001^(2^i)) for i = 1..19, as Q128.128
+    const MULTIPLIERS: [U256; 19] = [
+        U256::from_limbs([0x59a46990580e213a, 0xfff97272373d4132, 0, 0]),
+        U256::from_limbs([0xef12357cf3c7fdcc, 0xfff2e50f5f656932, 0, 0]),
+        U256::from_limbs([0x1c3624eaa0941cd0, 0xffe5caca7e10e4e6, 0, 0]),
+        U256::from_limbs([0xc9db58835c926644, 0xffcb9843d60f6159, 0, 0]),
+        U256::from_limbs([0x472e6896dfb254c0, 0xff973b41fa98c081, 0, 0]),
+        U256::from_limbs([0x43ec78b326b52861, 0xff2ea16466c96a38, 0, 0]),
+        U256::from_limbs([0x11c461f1969c3053, 0xfe5dee046a99a2a8, 0, 0]),
+        U256::from_limbs([0xdcffc83b479aa3a4, 0xfcbe86c7900a88ae, 0, 0]),
+        U256::from_limbs([0x6f2b074cf7815e54, 0xf987a7253ac41317, 0, 0]),
+        U256::from_limbs([0x940c7a398e4b70f3, 0xf3392b0822b70005, 0, 0]),
+        U256::from_limbs([0x43b29c7fa6e889d9, 0xe7159475a2c29b74, 0, 0]),
+        U256::from_limbs([0x845ad8f792aa5825, 0xd097f3bdfd2022b8, 0, 0]),
+        U256::from_limbs([0x8a65dc1f90e061e5, 0xa9f746462d870fdf, 0, 0]),
+        U256::from_limbs([0x90bb3df62baf32f7, 0x70d869a156d2a1b8, 0, 0]),
+        U256::from_limbs([0x81231505542fcfa6, 0x31be135f97d08fd9, 0, 0]),
+        U256::from_limbs([0xc677de54f3e99bc9, 0x9aa508b5b7a84e1, 0, 0]),
+        U256::from_limbs([0x6699c329225ee604, 0x5d6af8dedb8119, 0, 0]),
+        U256::from_limbs([0x1ea926041bedfe98, 0x2216e584f5fa, 0, 0]),
+        U256::from_limbs([0x91f7dc42444e8fa2, 0x48a1703, 0, 0]),
+    ];
+    let mut ratio = if abs_tick & 0x1 != 0 {
+        U256::from_limbs([0xaa2d162d1a594001, 0xfffcb933bd6fad37, 0, 0])
+    } else {
+        U256::from(1) << 128usize
+    };
+    for (i, multiplier) in MULTIPLIERS.iter().enumerate() {
+        if abs_tick & (1 << (i + 1)) != 0 {
+            ratio = (ratio * *multiplier) >> 128usize;
+        }
+    }
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+    // round up so that get_tick_at_sqrt_ratio(get_sqrt_ratio_at_tick(tick)) == tick
+    let remainder = ratio & U256::from(u32::MAX);
+    let mut sqrt_price = ratio >> 32usize;
+    if !remainder.is_zero() {
+        sqrt_price += U256::from(1);
+    }
+    Ok(sqrt_price)
+}
+
+// greatest tick such that get_sqrt_ratio_at_tick(tick) <= sqrt_price_x96
+pub fn get_tick_at_sqrt_ratio(sqrt_price_x96: U256) -> Result<i32> {
+    if sqrt_price_x96 < min_sqrt_ratio() || sqrt_price_x96 >= max_sqrt_ratio() {
+        return Err(anyhow!("R: sqrt price out of range"));
+    }
+    let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        if get_sqrt_ratio_at_tick(mid)? <= sqrt_price_x96 {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    Ok(low)
+}
+
+// sqrt(price_numerator / price_denominator) * 2^96, used to initialize a pool at a given price
+pub fn encode_price_sqrt(price_numerator: u128, price_denominator: u128) -> Result<U256> {
+    if price_numerator == 0 || price_denominator == 0 {
+        return Err(anyhow!("price must be non-zero"));
+    }
+    let ratio_x192 = (U512::from(price_numerator) << 192usize) / U512::from(price_denominator);
+    let sqrt_price = narrow(ratio_x192.root(2))?;
+    if sqrt_price < min_sqrt_ratio() || sqrt_price >= max_sqrt_ratio() {
+        return Err(anyhow!("R: sqrt price out of range"));
+    }
+    Ok(sqrt_price)
+}
+
+pub fn get_next_sqrt_price_from_amount_0_rounding_up(
+    sqrt_price_x96: U256,
+    liquidity: u128,
+    amount: U256,
+    add: bool,
+) -> Result<U256> {
+    if amount.is_zero() {
+        return Ok(sqrt_price_x96);
+    }
+    let numerator_1 = U512::from(liquidity) << RESOLUTION;
+    let product = U512::from(amount) * U512::from(sqrt_price_x96);
+    let denominator = if add {
+        numerator_1 + product
+    } else {
+        if product >= numerator_1 {
+            return Err(anyhow!("insufficient liquidity for output"));
+        }
+        numerator_1 - product
+    };
+    let numerator = numerator_1 * U512::from(sqrt_price_x96);
+    let mut result = numerator / denominator;
+    if !(numerator % denominator).is_zero() {
+        result += U512::from(1);
+    }
+    narrow(result)
+}
+
+pub fn get_next_sqrt_price_from_amount_1_rounding_down(
+    sqrt_price_x96: U256,
+    liquidity: u128,
+    amount: U256,
+    add: bool,
+) -> Result<U256> {
+    if liquidity == 0 {
+        return Err(anyhow!("no liquidity"));
+    }
+    if add {
+        let quotient = mul_div(amount, q96(), U256::from(liquidity))?;
+        sqrt_price_x96
+            .checked_add(quotient)
+            .ok_or_else(|| anyhow!("math overflow"))
+    } else {
+        let quotient = mul_div_rounding_up(amount, q96(), U256::from(liquidity))?;
+        if sqrt_price_x96 <= quotient {
+            return Err(anyhow!("insufficient liquidity for output"));
+        }
+        Ok(sqrt_price_x96 - quotient)
+    }
+}
+
+pub fn get_next_sqrt_price_from_input(
+    sqrt_price_x96: U256,
+    liquidity: u128,
+    amount_in: U256,
+    zero_for_one: bool,
+) -> Result<U256> {
+    if zero_for_one {
+        get_next_sqrt_price_from_amount_0_rounding_up(sqrt_price_x96, liquidity, amount_in, true)
+    } else {
+        get_next_sqrt_price_from_amount_1_rounding_down(sqrt_price_x96, liquidity, amount_in, true)
+    }
+}
+
+pub fn get_next_sqrt_price_from_output(
+    sqrt_price_x96: U256,
+    liquidity: u128,
+    amount_out: U256,
+    zero_for_one: bool,
+) -> Result<U256> {
+    if zero_for_one {
+        get_next_sqrt_price_from_amount_1_rounding_down(
+            sqrt_price_x96,
+            liquidity,
+            amount_out,
+            false,
+        )
+    } else {
+        get_next_sqrt_price_from_amount_0_rounding_up(sqrt_price_x96, liquidity, amount_out, false)
+    }
+}
+
+pub fn get_amount_0_delta(
+    sqrt_ratio_a_x96: U256,
+    sqrt_ratio_b_x96: U256,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<U256> {
+    let (lower, upper) = if sqrt_ratio_a_x96 > sqrt_ratio_b_x96 {
+        (sqrt_ratio_b_x96, sqrt_ratio_a_x96)
+    } else {
+        (sqrt_ratio_a_x96, sqrt_ratio_b_x96)
+    };
+    if lower.is_zero() {
+        return Err(anyhow!("sqrt price cannot be zero"));
+    }
+    let numerator_1 = U256::from(liquidity) << RESOLUTION;
+    let numerator_2 = upper - lower;
+    if round_up {
+        div_rounding_up(mul_div_rounding_up(numerator_1, numerator_2, upper)?, lower)
+    } else {
+        Ok(mul_div(numerator_1, numerator_2, upper)? / lower)
+    }
+}
+
+pub fn get_amount_1_delta(
+    sqrt_ratio_a_x96: U256,
+    sqrt_ratio_b_x96: U256,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<U256> {
+    let (lower, upper) = if sqrt_ratio_a_x96 > sqrt_ratio_b_x96 {
+        (sqrt_ratio_b_x96, sqrt_ratio_a_x96)
+    } else {
+        (sqrt_ratio_a_x96, sqrt_ratio_b_x96)
+    };
+    if round_up {
+        mul_div_rounding_up(U256::from(liquidity), upper - lower, q96())
+    } else {
+        mul_div(U256::from(liquidity), upper - lower, q96())
+    }
+}
+
+// amounts owed to (positive liquidity delta, rounded up) or by (negative, rounded down) the pool
+pub fn get_amount_0_delta_signed(
+    sqrt_ratio_a_x96: U256,
+    sqrt_ratio_b_x96: U256,
+    liquidity_delta: i128,
+) -> Result<U256> {
+    get_amount_0_delta(
+        sqrt_ratio_a_x96,
+        sqrt_ratio_b_x96,
+        liquidity_delta.unsigned_abs(),
+        liquidity_delta > 0,
+    )
+}
+
+pub fn get_amount_1_delta_signed(
+    sqrt_ratio_a_x96: U256,
+    sqrt_ratio_b_x96: U256,
+    liquidity_delta: i128,
+) -> Result<U256> {
+    get_amount_1_delta(
+        sqrt_ratio_a_x96,
+        sqrt_ratio_b_x96,
+        liquidity_delta.unsigned_abs(),
+        liquidity_delta > 0,
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapStep {
+    pub sqrt_price_next_x96: U256,
+    pub amount_in: U256,
+    pub amount_out: U256,
+    pub fee_amount: U256,
+}
+
+// see SwapMath.computeSwapStep. `amount_remaining` is the unsigned amount left to swap, which is
+// an input amount when `exact_in` is set and an output amount otherwise.
+pub fn compute_swap_step(
+    sqrt_price_current_x96: U256,
+    sqrt_price_target_x96: U256,
+    liquidity: u128,
+    amount_remaining: U256,
+    exact_in: bool,
+    fee_pips: u128,
+) -> Result<SwapStep> {
+    if fee_pips >= FEE_DENOMINATOR {
+        return Err(anyhow!("invalid fee"));
+    }
+    let zero_for_one = sqrt_price_current_x96 >= sqrt_price_target_x96;
+    let mut amount_in = U256::ZERO;
+    let mut amount_out = U256::ZERO;
+    let sqrt_price_next_x96;
+    if exact_in {
+        let amount_remaining_less_fee = mul_div(
+            amount_remaining,
+            U256::from(FEE_DENOMINATOR - fee_pips),
+            U256::from(FEE_DENOMINATOR),
+        )?;
+        amount_in = if zero_for_one {
+            get_amount_0_delta(
+                sqrt_price_target_x96,
+                sqrt_price_current_x96,
+                liquidity,
+                true,
+            )?
+        } else {
+            get_amount_1_delta(
+                sqrt_price_current_x96,
+                sqrt_price_target_x96,
+                liquidity,
+                true,
+            )?
+        };
+        sqrt_price_next_x96 = if amount_remaining_less_fee >= amount_in {
+            sqrt_price_target_x96
+        } else {
+            get_next_sqrt_price_from_input(
+                sqrt_price_current_x96,
+                liquidity,
+                amount_remaining_less_fee,
+                zero_for_one,
+            )?
+        };
+    } else {
+        amount_out = if zero_for_one {
+            get_amount_1_delta(
+                sqrt_price_target_x96,
+                sqrt_price_current_x96,
+                liquidity,
+                false,
+            )?
+        } else {
+            get_amount_0_delta(
+                sqrt_price_current_x96,
+                sqrt_price_target_x96,
+                liquidity,
+                false,
+            )?
+        };
+        sqrt_price_next_x96 = if amount_remaining >= amount_out {
+            sqrt_price_target_x96
+        } else {
+            get_next_sqrt_price_from_output(
+                sqrt_price_current_x96,
+                liquidity,
+                amount_remaining,
+                zero_for_one,
+            )?
+        };
+    }
+
+    let max = sqrt_price_target_x96 == sqrt_price_next_x96;
+    if zero_for_one {
+        if !(max && exact_in) {
+            amount_in =
+                get_amount_0_delta(sqrt_price_next_x96, sqrt_price_current_x96, liquidity, true)?;
+        }
+        if !(max && !exact_in) {
+            amount_out = get_amount_1_delta(
+                sqrt_price_next_x96,
+                sqrt_price_current_x96,
+                liquidity,
+                false,
+            )?;
+        }
+    } else {
+        if !(max && exact_in) {
+            amount_in =
+                get_amount_1_delta(sqrt_price_current_x96, sqrt_price_next_x96, liquidity, true)?;
+        }
+        if !(max && !exact_in) {
+            amount_out = get_amount_0_delta(
+                sqrt_price_current_x96,
+                sqrt_price_next_x96,
+                liquidity,
+                false,
+            )?;
+        }
+    }
+
+    // cap the output amount to not exceed the remaining output amount
+    if !exact_in && amount_out > amount_remaining {
+        amount_out = amount_remaining;
+    }
+
+    let fee_amount = if exact_in && sqrt_price_next_x96 != sqrt_price_target_x96 {
+        // we didn't reach the target, so take the remainder of the maximum input as fee
+        amount_remaining - amount_in
+    } else {
+        mul_div_rounding_up(
+            amount_in,
+            U256::from(fee_pips),
+            U256::from(FEE_DENOMINATOR - fee_pips),
+        )?
+    };
+
+    Ok(SwapStep {
+        sqrt_price_next_x96,
+        amount_in,
+        amount_out,
+        fee_amount,
+    })
+}
+
+// word and bit position of a compressed tick in the tick bitmap
+pub fn tick_bitmap_position(compressed_tick: i32) -> (i16, u8) {
+    (
+        (compressed_tick >> 8) as i16,
+        (compressed_tick & 0xff) as u8,
+    )
+}
+
+pub fn compress_tick(tick: i32, tick_spacing: i32) -> i32 {
+    let mut compressed = tick / tick_spacing;
+    if tick < 0 && tick % tick_spacing != 0 {
+        compressed -= 1; // round towards negative infinity
+    }
+    compressed
+}
+
+// Given the bitmap word containing the compressed tick, returns the next initialized tick
+// within that word in the direction of the swap, or the word boundary if there is none.
+// `lte` searches to the left (less than or equal), which is the direction of a zero_for_one swap.
+pub fn next_initialized_tick_within_one_word(
+    word: U256,
+    tick: i32,
+    tick_spacing: i32,
+    lte: bool,
+) -> (i32, bool) {
+    let compressed = compress_tick(tick, tick_spacing);
+    if lte {
+        let (_, bit_pos) = tick_bitmap_position(compressed);
+        // all the 1s at or to the right of the current bit_pos
+        let mask = (U256::from(1) << bit_pos as usize) - U256::from(1)
+            + (U256::from(1) << bit_pos as usize);
+        let masked = word & mask;
+        let initialized = !masked.is_zero();
+        let next = if initialized {
+            let most_significant_bit = (255 - masked.leading_zeros()) as i32;
+            (compressed - (bit_pos as i32 - most_significant_bit)) * tick_spacing
+        } else {
+            (compressed - bit_pos as i32) * tick_spacing
+        };
+        (next, initialized)
+    } else {
+        // start from the word of the next tick, since the current tick state doesn't matter
+        let (_, bit_pos) = tick_bitmap_position(compressed + 1);
+        // all the 1s at or to the left of the bit_pos
+        let mask = !((U256::from(1) << bit_pos as usize) - U256::from(1));
+        let masked = word & mask;
+        let initialized = !masked.is_zero();
+        let next = if initialized {
+            let least_significant_bit = masked.trailing_zeros() as i32;
+            (compressed + 1 + (least_significant_bit - bit_pos as i32)) * tick_spacing
+        } else {
+            (compressed + 1 + (255 - bit_pos as i32)) * tick_spacing
+        };
+        (next, initialized)
+    }
+}
+
+pub fn tick_spacing_to_max_liquidity_per_tick(tick_spacing: i32) -> u128 {
+    let min_tick = (MIN_TICK / tick_spacing) * tick_spacing;
+    let max_tick = (MAX_TICK / tick_spacing) * tick_spacing;
+    let num_ticks = ((max_tick - min_tick) / tick_spacing) as u128 + 1;
+    u128::MAX / num_ticks
+}
+
+pub fn get_liquidity_for_amount_0(
+    sqrt_ratio_a_x96: U256,
+    sqrt_ratio_b_x96: U256,
+    amount_0: u128,
+) -> Result<U256> {
+    let (lower, upper) = if sqrt_ratio_a_x96 > sqrt_ratio_b_x96 {
+        (sqrt_ratio_b_x96, sqrt_ratio_a_x96)
+    } else {
+        (sqrt_ratio_a_x96, sqrt_ratio_b_x96)
+    };
+    let intermediate = mul_div(lower, upper, q96())?;
+    mul_div(U256::from(amount_0), intermediate, upper - lower)
+}
+
+pub fn get_liquidity_for_amount_1(
+    sqrt_ratio_a_x96: U256,
+    sqrt_ratio_b_x96: U256,
+    amount_1: u128,
+) -> Result<U256> {
+    let (lower, upper) = if sqrt_ratio_a_x96 > sqrt_ratio_b_x96 {
+        (sqrt_ratio_b_x96, sqrt_ratio_a_x96)
+    } else {
+        (sqrt_ratio_a_x96, sqrt_ratio_b_x96)
+    };
+    mul_div(U256::from(amount_1), q96(), upper - lower)
+}
+
+// maximum liquidity that can be minted for the given amounts at the current price
+pub fn get_liquidity_for_amounts(
+    sqrt_price_x96: U256,
+    sqrt_ratio_a_x96: U256,
+    sqrt_ratio_b_x96: U256,
+    amount_0: u128,
+    amount_1: u128,
+) -> Result<u128> {
+    let (lower, upper) = if sqrt_ratio_a_x96 > sqrt_ratio_b_x96 {
+        (sqrt_ratio_b_x96, sqrt_ratio_a_x96)
+    } else {
+        (sqrt_ratio_a_x96, sqrt_ratio_b_x96)
+    };
+    let liquidity = if sqrt_price_x96 <= lower {
+        get_liquidity_for_amount_0(lower, upper, amount_0)?
+    } else if sqrt_price_x96 < upper {
+        std::cmp::min(
+            get_liquidity_for_amount_0(sqrt_price_x96, upper, amount_0)?,
+            get_liquidity_for_amount_1(lower, sqrt_price_x96, amount_1)?,
+        )
+    } else {
+        get_liquidity_for_amount_1(lower, upper, amount_1)?
+    };
+    Ok(liquidity.try_into()?)
+}
@@ -0,0 +1,75 @@
+// Unsigned 128.128 binary fixed point numbers used for pool prices, the u128 reserve counterpart of
+// Uniswap v2's UQ112x112. A price reserve_1 / reserve_0 always fits in 256 bits, so encoding never
+// fails. Price cumulatives add up price * seconds elapsed and are meant to overflow: they wrap
+// modulo 2^256 and only the difference between two readings carries meaning.
+//
+// To compute a TWAP, read a price cumulative twice (through GetPriceCumulativeLast or Consult),
+// then call `average_price(start, end, seconds_between)`. `decode` gives the integer part of the
+// result and `mul_decode` quotes an amount of token 0 in token 1 (or the other way round for the
+// price 1 cumulative). Wrapping subtraction keeps the average exact across an overflow as long as
+// average price * window stays below 2^256, i.e. for any window under 136 years while the price
+// is below 2^96.
+use crate::{concentrated::mul_div, U256};
+use anyhow::{anyhow, Result};
+
+pub const RESOLUTION: usize = 128;
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UQ128x128(pub U256);
+
+impl UQ128x128 {
+    pub fn encode(value: u128) -> Self {
+        UQ128x128(U256::from(value) << RESOLUTION)
+    }
+
+    pub fn fraction(numerator: u128, denominator: u128) -> Result<Self> {
+        if denominator == 0 {
+            return Err(anyhow!("fixed point division by zero"));
+        }
+        Ok(UQ128x128(
+            (U256::from(numerator) << RESOLUTION) / U256::from(denominator),
+        ))
+    }
+
+    // numerator / denominator for 256 bit terms, e.g. reserves multiplied by weights. Fails if the
+    // quotient does not fit in 128 integer bits
+    pub fn ratio(numerator: U256, denominator: U256) -> Result<Self> {
+        if denominator.is_zero() {
+            return Err(anyhow!("fixed point division by zero"));
+        }
+        Ok(UQ128x128(mul_div(
+            numerator,
+            U256::from(1) << RESOLUTION,
+            denominator,
+        )?))
+    }
+
+    // integer part, rounded down
+    pub fn decode(self) -> u128 {
+        (self.0 >> RESOLUTION).to::<u128>()
+    }
+
+    // amount * self, rounded down
+    pub fn mul_decode(self, amount: u128) -> Result<u128> {
+        Ok(mul_div(self.0, U256::from(amount), U256::from(1) << RESOLUTION)?.try_into()?)
+    }
+}
+
+// amount a price cumulative grows by when `price` holds for `time_elapsed` seconds
+pub fn cumulative_increase(price: UQ128x128, time_elapsed: u32) -> U256 {
+    price.0.wrapping_mul(U256::from(time_elapsed))
+}
+
+// average price between two readings of a price cumulative taken `time_elapsed` seconds apart
+pub fn average_price(
+    cumulative_start: U256,
+    cumulative_end: U256,
+    time_elapsed: u32,
+) -> Result<UQ128x128> {
+    if time_elapsed == 0 {
+        return Err(anyhow!("time elapsed must be positive"));
+    }
+    Ok(UQ128x128(
+        cumulative_end.wrapping_sub(cumulative_start) / U256::from(time_elapsed),
+    ))
+}
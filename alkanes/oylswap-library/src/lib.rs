@@ -1,17 +1,101 @@
-use alkanes_runtime::storage::StoragePointer;
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
 
 #[allow(unused_imports)]
 use alkanes_runtime::{
     println,
     stdio::{stdout, Write},
 };
-use alkanes_support::{id::AlkaneId, response::CallResponse};
+use alkanes_support::{
+    cellpack::Cellpack,
+    constants::AUTH_TOKEN_FACTORY_ID,
+    id::AlkaneId,
+    parcel::{AlkaneTransfer, AlkaneTransferParcel},
+    response::CallResponse,
+};
 use anyhow::{anyhow, Result};
 use metashrew_support::{byte_view::ByteView, index_pointer::KeyValuePointer};
 use ruint::Uint;
 
+pub mod concentrated;
+pub mod fixed_point;
+pub mod oracle;
+pub mod stableswap;
+pub mod weighted;
+
 pub const DEFAULT_TOTAL_FEE_AMOUNT_PER_1000: u128 = 10;
 pub const PROTOCOL_FEE_AMOUNT_PER_1000: u128 = 2;
+pub const DEFAULT_FLASH_FEE_AMOUNT_PER_1000: u128 = 1;
+// constant product pools and fee tiers count fees per 1000000, so tiers below 0.1% can be expressed
+pub const DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000: u128 = DEFAULT_TOTAL_FEE_AMOUNT_PER_1000 * 1000;
+pub const PROTOCOL_FEE_AMOUNT_PER_1000000: u128 = PROTOCOL_FEE_AMOUNT_PER_1000 * 1000;
+// called on a contract recipient (`to`) to hand it alkanes, the recipient is expected to keep them.
+// Shared by the pools and the factory router
+pub const RECEIVE_EXTCALL_OPCODE: u128 = 72656376;
+
+// deploys a fresh single-unit token through the auth token factory. Holding it proves ownership of
+// whatever the caller keys to its id, like an order, a lock or a position
+pub fn create_ownership_token<T: AlkaneResponder + ?Sized>(
+    responder: &T,
+) -> Result<AlkaneTransfer> {
+    let response = responder.call(
+        &Cellpack {
+            target: AlkaneId {
+                block: 6,
+                tx: AUTH_TOKEN_FACTORY_ID,
+            },
+            inputs: vec![0x0, 1],
+        },
+        &AlkaneTransferParcel::default(),
+        responder.fuel(),
+    )?;
+    response
+        .alkanes
+        .0
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("ownership token not returned from factory"))
+}
+
+// makes sure `pool` is a constant product pool created by `factory`, so its lp token is real
+// liquidity. Pools in a fee tier charge the tier's fee, pools of the default tier can have had
+// their fee changed since, so they are looked up in the default tier as well
+pub fn check_factory_pool<T: AlkaneResponder + ?Sized>(
+    responder: &T,
+    factory: AlkaneId,
+    pool: AlkaneId,
+) -> Result<()> {
+    let not_a_pool = || anyhow!("{:?} is not a pool of the factory", pool);
+    let call = |target: AlkaneId, inputs: Vec<u128>| {
+        responder.call(
+            &Cellpack { target, inputs },
+            &AlkaneTransferParcel::default(),
+            responder.fuel(),
+        )
+    };
+    let pool_info = PoolInfo::from_vec(&call(pool, vec![999]).map_err(|_| not_a_pool())?.data)?;
+    let total_fee = u128::from_le_bytes(call(pool, vec![37])?.data[..16].try_into()?);
+    let in_factory = [total_fee, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000]
+        .into_iter()
+        .any(|fee_tier| {
+            call(
+                factory,
+                vec![
+                    19,
+                    pool_info.token_a.block,
+                    pool_info.token_a.tx,
+                    pool_info.token_b.block,
+                    pool_info.token_b.tx,
+                    fee_tier,
+                ],
+            )
+            .and_then(|response| Ok(AlkaneId::try_from(response.data)?))
+            .map_or(false, |factory_pool| factory_pool == pool)
+        });
+    if !in_factory {
+        return Err(not_a_pool());
+    }
+    Ok(())
+}
 
 pub type U256 = Uint<256, 4>;
 pub trait Sqrt {
@@ -84,11 +168,14 @@ impl Lock {
     pub fn set_lock(v: u128) {
         Lock::lock_pointer().set_value::<u128>(v);
     }
+    pub fn is_locked() -> bool {
+        Lock::lock_pointer().get().len() != 0 && Lock::get_lock() == 1
+    }
     pub fn lock<F>(func: F) -> Result<CallResponse>
     where
         F: FnOnce() -> Result<CallResponse>,
     {
-        if Lock::lock_pointer().get().len() != 0 && Lock::get_lock() == 1 {
+        if Lock::is_locked() {
             return Err(anyhow!("LOCKED"));
         }
 
@@ -103,6 +190,17 @@ impl Lock {
 
         ret
     }
+    // guards read only calls: while a locked call is running (e.g. from inside a flash swap
+    // callback) the state it reads may be half updated, so refuse to answer
+    pub fn view<F>(func: F) -> Result<CallResponse>
+    where
+        F: FnOnce() -> Result<CallResponse>,
+    {
+        if Lock::is_locked() {
+            return Err(anyhow!("LOCKED"));
+        }
+        func()
+    }
 }
 
 #[derive(Default)]
@@ -203,16 +301,72 @@ impl PoolInfo {
     }
 }
 
+// cumulative trading counters kept by a pool, indexed by token (0 or 1)
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct PoolStats {
+    pub amount_in: [u128; 2],
+    pub amount_out: [u128; 2],
+    pub lp_fees: [u128; 2],
+    pub protocol_fees: [u128; 2],
+    pub swap_count: u128,
+}
+
+impl PoolStats {
+    pub fn try_to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for v in self
+            .amount_in
+            .iter()
+            .chain(self.amount_out.iter())
+            .chain(self.lp_fees.iter())
+            .chain(self.protocol_fees.iter())
+        {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.swap_count.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_vec(bytes: &[u8]) -> Result<Self> {
+        // 9 u128 values of 16 bytes each
+        if bytes.len() < 144 {
+            return Err(anyhow!("Invalid bytes length for PoolStats"));
+        }
+        let value = |i: usize| u128::from_le_bytes(bytes[i * 16..(i + 1) * 16].try_into().unwrap());
+        Ok(PoolStats {
+            amount_in: [value(0), value(1)],
+            amount_out: [value(2), value(3)],
+            lp_fees: [value(4), value(5)],
+            protocol_fees: [value(6), value(7)],
+            swap_count: value(8),
+        })
+    }
+}
+
 pub fn get_amount_out(
     amount_in: u128,
     reserve_in: u128,
     reserve_out: u128,
     total_fee_per_1000: u128,
 ) -> Result<u128> {
-    let amount_in_with_fee = U256::from(1000 - total_fee_per_1000) * U256::from(amount_in);
+    get_amount_out_per_1000000(
+        amount_in,
+        reserve_in,
+        reserve_out,
+        total_fee_per_1000 * 1000,
+    )
+}
+
+pub fn get_amount_out_per_1000000(
+    amount_in: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    total_fee_per_1000000: u128,
+) -> Result<u128> {
+    let amount_in_with_fee = U256::from(1000000 - total_fee_per_1000000) * U256::from(amount_in);
 
     let numerator = amount_in_with_fee * U256::from(reserve_out);
-    let denominator = U256::from(1000) * U256::from(reserve_in) + amount_in_with_fee;
+    let denominator = U256::from(1000000) * U256::from(reserve_in) + amount_in_with_fee;
     Ok((numerator / denominator).try_into()?)
 }
 
@@ -221,6 +375,20 @@ pub fn get_amount_in(
     reserve_in: u128,
     reserve_out: u128,
     total_fee_per_1000: u128,
+) -> Result<u128> {
+    get_amount_in_per_1000000(
+        amount_out,
+        reserve_in,
+        reserve_out,
+        total_fee_per_1000 * 1000,
+    )
+}
+
+pub fn get_amount_in_per_1000000(
+    amount_out: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    total_fee_per_1000000: u128,
 ) -> Result<u128> {
     if amount_out == 0 {
         return Err(anyhow!("INSUFFICIENT_OUTPUT_AMOUNT"));
@@ -228,11 +396,48 @@ pub fn get_amount_in(
     if reserve_in == 0 || reserve_out == 0 {
         return Err(anyhow!("INSUFFICIENT_LIQUIDITY"));
     }
-    let numerator = U256::from(1000) * U256::from(reserve_in) * U256::from(amount_out);
-    let denominator = U256::from(1000 - total_fee_per_1000) * U256::from(reserve_out - amount_out);
+    let numerator = U256::from(1000000) * U256::from(reserve_in) * U256::from(amount_out);
+    let denominator =
+        U256::from(1000000 - total_fee_per_1000000) * U256::from(reserve_out - amount_out);
     Ok((numerator / denominator + U256::from(1)).try_into()?)
 }
 
+pub fn get_zap_swap_amount(
+    amount_in: u128,
+    reserve_in: u128,
+    total_fee_per_1000: u128,
+) -> Result<u128> {
+    get_zap_swap_amount_per_1000000(amount_in, reserve_in, total_fee_per_1000 * 1000)
+}
+
+// part of `amount_in` to swap so that the swap output and the rest of `amount_in` can be added
+// as liquidity in the pool's ratio after the swap (single sided zap into a constant product pool).
+// With D = 1000000 and g = D - fee, it solves for s in (amount_in - s) / (reserve_in + s) = out(s) / (reserve_out - out(s)):
+// s = (sqrt(((D + g) * reserve_in)^2 + 4 * D * g * amount_in * reserve_in) - (D + g) * reserve_in) / (2 * g)
+pub fn get_zap_swap_amount_per_1000000(
+    amount_in: u128,
+    reserve_in: u128,
+    total_fee_per_1000000: u128,
+) -> Result<u128> {
+    if amount_in == 0 {
+        return Err(anyhow!("INSUFFICIENT_INPUT_AMOUNT"));
+    }
+    if reserve_in == 0 {
+        return Err(anyhow!("INSUFFICIENT_LIQUIDITY"));
+    }
+    let g = U256::from(1000000 - total_fee_per_1000000);
+    let b = (U256::from(1000000) + g) * U256::from(reserve_in);
+    let discriminant = b
+        .checked_mul(b)
+        .and_then(|b_squared| {
+            (U256::from(4000000) * g * U256::from(amount_in))
+                .checked_mul(U256::from(reserve_in))
+                .and_then(|ac| b_squared.checked_add(ac))
+        })
+        .ok_or_else(|| anyhow!("zap amount overflows"))?;
+    Ok(((discriminant.sqrt() - b) / (U256::from(2) * g)).try_into()?)
+}
+
 pub fn sort_alkanes((a, b): (AlkaneId, AlkaneId)) -> (AlkaneId, AlkaneId) {
     if a < b {
         (a, b)
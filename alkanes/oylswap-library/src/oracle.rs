@@ -0,0 +1,80 @@
+// Price observations for pool TWAPs, modelled on the Uniswap v3 Oracle library. Pools keep a
+// ring buffer of (timestamp, price0 cumulative, price1 cumulative) snapshots and answer
+// "what was the cumulative price n seconds ago" by extrapolating from the newest
+// observation or interpolating between the two observations around the target time.
+use crate::{
+    fixed_point::{self, UQ128x128},
+    U256,
+};
+use anyhow::{anyhow, Result};
+
+pub const MAX_OBSERVATION_CARDINALITY: u128 = 65535;
+// Bitcoin block times are only loosely ordered, a block may carry an earlier time than its parent.
+// Pools therefore run their oracle on the block header time clamped so it never goes backwards:
+// a block timed before the last update accrues nothing and keeps the time of the last update.
+pub const ORACLE_CLOCK_CLAMPED_BLOCK_TIME: u128 = 0;
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Observation {
+    pub block_timestamp: u32,
+    pub price_0_cumulative: U256,
+    pub price_1_cumulative: U256,
+}
+
+impl Observation {
+    pub fn try_to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.block_timestamp.to_le_bytes());
+        bytes.extend_from_slice(&self.price_0_cumulative.to_le_bytes::<32>());
+        bytes.extend_from_slice(&self.price_1_cumulative.to_le_bytes::<32>());
+        bytes
+    }
+
+    pub fn from_vec(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 68 {
+            return Err(anyhow!("Invalid bytes length for Observation"));
+        }
+        Ok(Observation {
+            block_timestamp: u32::from_le_bytes(bytes[0..4].try_into()?),
+            price_0_cumulative: U256::from_le_bytes::<32>(bytes[4..36].try_into()?),
+            price_1_cumulative: U256::from_le_bytes::<32>(bytes[36..68].try_into()?),
+        })
+    }
+
+    // extrapolates the observation to a later timestamp, given the prices that held since it
+    // was written
+    pub fn transform(
+        &self,
+        block_timestamp: u32,
+        price_0: UQ128x128,
+        price_1: UQ128x128,
+    ) -> Observation {
+        let delta = block_timestamp - self.block_timestamp;
+        Observation {
+            block_timestamp,
+            price_0_cumulative: self
+                .price_0_cumulative
+                .wrapping_add(fixed_point::cumulative_increase(price_0, delta)),
+            price_1_cumulative: self
+                .price_1_cumulative
+                .wrapping_add(fixed_point::cumulative_increase(price_1, delta)),
+        }
+    }
+}
+
+// cumulative prices at `target`, which must lie between the timestamps of `before` and `after`
+pub fn interpolate(before: &Observation, after: &Observation, target: u32) -> Result<Observation> {
+    let observation_time_delta = after.block_timestamp - before.block_timestamp;
+    // the price held constant between two observations, so the average is exact
+    let price_0 = fixed_point::average_price(
+        before.price_0_cumulative,
+        after.price_0_cumulative,
+        observation_time_delta,
+    )?;
+    let price_1 = fixed_point::average_price(
+        before.price_1_cumulative,
+        after.price_1_cumulative,
+        observation_time_delta,
+    )?;
+    Ok(before.transform(target, price_0, price_1))
+}
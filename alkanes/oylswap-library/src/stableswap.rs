@@ -0,0 +1,173 @@
+// StableSwap invariant math for two asset pools, following Curve's get_D / get_y with n = 2:
+//   A * n^n * (x + y) + D = A * n^n * D + D^(n + 1) / (n^n * x * y)
+// The amplification coefficient A is stored without extra precision. Intermediate values are
+// computed in 512 bits since D^3 does not fit in 256 bits for large reserves.
+use crate::{
+    concentrated::{mul_div, U512},
+    U256,
+};
+use anyhow::{anyhow, Result};
+
+pub const N_COINS: u128 = 2;
+pub const MAX_AMP: u128 = 1_000_000;
+// pool and router math is done on balances scaled by the fee denominator so that the
+// fee-adjusted balances checked by the pool are exact integers
+pub const BALANCE_SCALE: u128 = 1000;
+const MAX_ITERATIONS: usize = 255;
+
+fn narrow(value: U512) -> Result<U256> {
+    if value > U512::from(U256::MAX) {
+        return Err(anyhow!("math overflow"));
+    }
+    Ok(value.to())
+}
+
+fn checked_mul(a: U512, b: U512) -> Result<U512> {
+    a.checked_mul(b).ok_or_else(|| anyhow!("math overflow"))
+}
+
+fn converged(a: U512, b: U512) -> bool {
+    if a > b {
+        a - b <= U512::from(1)
+    } else {
+        b - a <= U512::from(1)
+    }
+}
+
+pub fn check_amp(amp: u128) -> Result<()> {
+    if amp == 0 || amp > MAX_AMP {
+        return Err(anyhow!(
+            "amplification coefficient must be between 1 and {}",
+            MAX_AMP
+        ));
+    }
+    Ok(())
+}
+
+// solves the invariant D for the balances x and y
+pub fn get_d(x: U256, y: U256, amp: u128) -> Result<U256> {
+    check_amp(amp)?;
+    if x.is_zero() && y.is_zero() {
+        return Ok(U256::ZERO);
+    }
+    if x.is_zero() || y.is_zero() {
+        return Err(anyhow!("INSUFFICIENT_LIQUIDITY"));
+    }
+    let n = U512::from(N_COINS);
+    let (x, y) = (U512::from(x), U512::from(y));
+    let s = x + y;
+    let ann = U512::from(amp) * n;
+    let mut d = s;
+    for _ in 0..MAX_ITERATIONS {
+        // a single division keeps the rounding stable, chained divisions can make D oscillate
+        let d_p = checked_mul(d * d, d)? / (x * y * n * n);
+        let d_prev = d;
+        let numerator = checked_mul(ann * s + d_p * n, d)?;
+        let denominator = (ann - U512::from(1)) * d + (n + U512::from(1)) * d_p;
+        d = numerator / denominator;
+        if converged(d, d_prev) {
+            return narrow(d);
+        }
+    }
+    Err(anyhow!("StableSwap invariant did not converge"))
+}
+
+// solves the balance of one asset given the balance x of the other asset and the invariant D
+pub fn get_y(x: U256, d: U256, amp: u128) -> Result<U256> {
+    check_amp(amp)?;
+    if x.is_zero() {
+        return Err(anyhow!("INSUFFICIENT_LIQUIDITY"));
+    }
+    let n = U512::from(N_COINS);
+    let (x, d) = (U512::from(x), U512::from(d));
+    let ann = U512::from(amp) * n;
+    let c = checked_mul(d * d, d)? / (x * n * ann * n);
+    let b = x + d / ann;
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+        y = (y * y + c) / (U512::from(2) * y + b - d);
+        if converged(y, y_prev) {
+            return narrow(y);
+        }
+    }
+    Err(anyhow!("StableSwap invariant did not converge"))
+}
+
+fn scaled(value: u128) -> U256 {
+    U256::from(value) * U256::from(BALANCE_SCALE)
+}
+
+// invariant of a pool after a swap, where the fee is taken from the input amounts
+pub fn get_d_fee_adjusted(
+    balance_0: u128,
+    balance_1: u128,
+    amount_0_in: u128,
+    amount_1_in: u128,
+    total_fee_per_1000: u128,
+    amp: u128,
+) -> Result<U256> {
+    get_d(
+        scaled(balance_0) - U256::from(amount_0_in) * U256::from(total_fee_per_1000),
+        scaled(balance_1) - U256::from(amount_1_in) * U256::from(total_fee_per_1000),
+        amp,
+    )
+}
+
+// marginal price of token 0 in token 1, i.e. -dy/dx of the invariant, as a numerator and a
+// denominator: (4 Ann x^2 y^2 + D^3 y) / (4 Ann x^2 y^2 + D^3 x). Both are divided through by D^3
+// so that no term grows beyond the size of the balances
+pub fn get_marginal_price(reserve_0: u128, reserve_1: u128, amp: u128) -> Result<(U256, U256)> {
+    if reserve_0 == 0 || reserve_1 == 0 {
+        return Err(anyhow!("INSUFFICIENT_LIQUIDITY"));
+    }
+    let (x, y) = (scaled(reserve_0), scaled(reserve_1));
+    let d = get_d(x, y, amp)?;
+    let ann = U256::from(amp) * U256::from(N_COINS);
+    // x * y / D stays below the balances, and 4 Ann (x y / D)^2 / D = 4 Ann x^2 y^2 / D^3
+    let xy_over_d = mul_div(x, y, d)?;
+    let t = mul_div(xy_over_d * U256::from(4) * ann, xy_over_d, d)?;
+    Ok((t + y, t + x))
+}
+
+pub fn get_amount_out(
+    amount_in: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    total_fee_per_1000: u128,
+    amp: u128,
+) -> Result<u128> {
+    if amount_in == 0 {
+        return Err(anyhow!("INSUFFICIENT_INPUT_AMOUNT"));
+    }
+    if reserve_in == 0 || reserve_out == 0 {
+        return Err(anyhow!("INSUFFICIENT_LIQUIDITY"));
+    }
+    let d = get_d(scaled(reserve_in), scaled(reserve_out), amp)?;
+    let x =
+        scaled(reserve_in) + U256::from(BALANCE_SCALE - total_fee_per_1000) * U256::from(amount_in);
+    let y = get_y(x, d, amp)?;
+    // round the new balance up and keep one extra unit in the pool to absorb rounding in D
+    let y: u128 = ((y + U256::from(BALANCE_SCALE - 1)) / U256::from(BALANCE_SCALE)).try_into()?;
+    Ok(reserve_out.saturating_sub(y).saturating_sub(1))
+}
+
+pub fn get_amount_in(
+    amount_out: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    total_fee_per_1000: u128,
+    amp: u128,
+) -> Result<u128> {
+    if amount_out == 0 {
+        return Err(anyhow!("INSUFFICIENT_OUTPUT_AMOUNT"));
+    }
+    if reserve_in == 0 || reserve_out == 0 || amount_out >= reserve_out {
+        return Err(anyhow!("INSUFFICIENT_LIQUIDITY"));
+    }
+    let d = get_d(scaled(reserve_in), scaled(reserve_out), amp)?;
+    let x = get_y(scaled(reserve_out - amount_out), d, amp)?;
+    // same one unit margin as get_amount_out, taken on the input side
+    let dx = x + U256::from(BALANCE_SCALE) - scaled(reserve_in).min(x);
+    Ok((dx / U256::from(BALANCE_SCALE - total_fee_per_1000) + U256::from(1)).try_into()?)
+}
@@ -0,0 +1,261 @@
+// Weighted product math for two asset pools, following Balancer's weighted math:
+//   V = balance_0^(weight_0) * balance_1^(weight_1)
+// Weights are given per WEIGHT_DENOMINATOR and must add up to it. Fractional powers are
+// computed through log2 / exp2 in Q64.64 fixed point, and quotes keep a small relative margin
+// so that they always satisfy the invariant as checked by the pool.
+use crate::{concentrated::U512, U256};
+use anyhow::{anyhow, Result};
+
+pub const WEIGHT_DENOMINATOR: u128 = 1000;
+// weights below 1% make the pool price too sensitive to rounding
+pub const MIN_WEIGHT: u128 = 10;
+// same fee-scaled balances as the constant product check
+pub const BALANCE_SCALE: u128 = 1000;
+// quotes are moved against the trader by 1 / POW_ERROR_MARGIN to absorb the error of log2 / exp2
+pub const POW_ERROR_MARGIN: u128 = 1_000_000_000_000;
+const FRACTION_BITS: usize = 64;
+
+// fractional parts of 2^(2^-k) for k = 1..=64 in Q0.128
+const EXP2_FRACTIONS: [u128; 64] = [
+    0x6a09e667f3bcc908b2fb1366ea957d3e,
+    0x306fe0a31b7152de8d5a46305c85edec,
+    0x172b83c7d517adcdf7c8c50eb14a7920,
+    0x0b5586cf9890f6298b92b71842a98364,
+    0x059b0d31585743ae7c548eb68ca417fe,
+    0x02c9a3e778060ee6f7caca4f7a29bde9,
+    0x0163da9fb33356d84a66ae336dcdfa40,
+    0x00b1afa5abcbed6129ab13ec11dc9544,
+    0x0058c86da1c09ea1ff19d294cf2f679c,
+    0x002c605e2e8cec506d21bfc89a23a010,
+    0x00162f3904051fa128bca9c55c31e5e0,
+    0x000b175effdc76ba38e31671ca939726,
+    0x00058ba01fb9f96d6cacd4b180917c3e,
+    0x0002c5cc37da9491d0985c348c68e7b3,
+    0x000162e525ee054754457d5995292026,
+    0x0000b17255775c040618bf4a4ade83fc,
+    0x000058b91b5bc9ae2eed81e9b7d4cfac,
+    0x00002c5c89d5ec6ca4d7c8acc017b7c9,
+    0x0000162e43f4f831060e02d839a9d16d,
+    0x00000b1721bcfc99d9f890ea06911763,
+    0x0000058b90cf1e6d97f9ca14dbcc1628,
+    0x000002c5c863b73f016468f6bac5ca2c,
+    0x00000162e430e5a18f6119e3c02282a5,
+    0x000000b1721835514b86e6d96efd1bff,
+    0x00000058b90c0b48c6be5df846c5b2f0,
+    0x0000002c5c8601cc6b9e94213c72737a,
+    0x000000162e42fff037df38aa2b219f06,
+    0x0000000b17217fba9c739aa5819f44f9,
+    0x000000058b90bfcdee5acd3c1cedc823,
+    0x00000002c5c85fe31f35a6a30da1be50,
+    0x0000000162e42ff0999ce3541b9fffcf,
+    0x00000000b17217f80f4ef5aadda45554,
+    0x0000000058b90bfbf8479bd5a81b51ad,
+    0x000000002c5c85fdf84bd62ae30a74cc,
+    0x00000000162e42fefb2fed257559bdaa,
+    0x000000000b17217f7d5a7716bba4a9af,
+    0x00000000058b90bfbe9ddbac5e109ccf,
+    0x0000000002c5c85fdf4b15de6f17eb0d,
+    0x000000000162e42fefa494f1478fde05,
+    0x0000000000b17217f7d20cf927c8e94c,
+    0x000000000058b90bfbe8f71cb4e4b33e,
+    0x00000000002c5c85fdf477b662b26945,
+    0x0000000000162e42fefa3ae53369388c,
+    0x00000000000b17217f7d1d351a389d40,
+    0x0000000000058b90bfbe8e8b2d3d4ede,
+    0x000000000002c5c85fdf4741bea6e77f,
+    0x00000000000162e42fefa39fe95583c3,
+    0x000000000000b17217f7d1cfb72b45e2,
+    0x00000000000058b90bfbe8e7cc35c3f1,
+    0x0000000000002c5c85fdf473e242ea38,
+    0x000000000000162e42fefa39f02b772c,
+    0x0000000000000b17217f7d1cf7d83c1a,
+    0x000000000000058b90bfbe8e7bdcbe2e,
+    0x00000000000002c5c85fdf473dea871f,
+    0x0000000000000162e42fefa39ef44d91,
+    0x00000000000000b17217f7d1cf79e949,
+    0x0000000000000058b90bfbe8e7bce544,
+    0x000000000000002c5c85fdf473de6eca,
+    0x00000000000000162e42fefa39ef366f,
+    0x000000000000000b17217f7d1cf79afa,
+    0x00000000000000058b90bfbe8e7bcd6d,
+    0x0000000000000002c5c85fdf473de6b2,
+    0x000000000000000162e42fefa39ef358,
+    0x0000000000000000b17217f7d1cf79ac,
+];
+
+fn q128() -> U512 {
+    U512::from(1) << 128usize
+}
+
+pub fn check_weights(weight_0: u128, weight_1: u128) -> Result<()> {
+    if weight_0 < MIN_WEIGHT
+        || weight_1 < MIN_WEIGHT
+        || weight_0.checked_add(weight_1) != Some(WEIGHT_DENOMINATOR)
+    {
+        return Err(anyhow!(
+            "weights must each be at least {} and add up to {}",
+            MIN_WEIGHT,
+            WEIGHT_DENOMINATOR
+        ));
+    }
+    Ok(())
+}
+
+// log2 of a non zero integer as a Q64.64 value, rounded down
+pub fn log2(x: U256) -> Result<U256> {
+    if x.is_zero() {
+        return Err(anyhow!("log2 of zero"));
+    }
+    let msb = 255 - x.leading_zeros();
+    // normalize x into [1, 2) as a Q128 value, then square it to get one bit at a time
+    let mut y = (U512::from(x) << 128usize) >> msb;
+    let mut fraction = U256::ZERO;
+    for bit in (0..FRACTION_BITS).rev() {
+        y = y * y >> 128usize;
+        if y >= q128() << 1usize {
+            y >>= 1usize;
+            fraction |= U256::from(1) << bit;
+        }
+    }
+    Ok((U256::from(msb) << FRACTION_BITS) | fraction)
+}
+
+// 2^x for a Q64.64 value x, rounded down to an integer
+pub fn exp2(x: U256) -> Result<U256> {
+    let integer = x >> FRACTION_BITS;
+    if integer >= U256::from(256) {
+        return Err(anyhow!("math overflow"));
+    }
+    let mut result = q128();
+    for (i, fraction) in EXP2_FRACTIONS.iter().enumerate() {
+        if x.bit(FRACTION_BITS - 1 - i) {
+            result = result * (q128() + U512::from(*fraction)) >> 128usize;
+        }
+    }
+    let result = result << integer.to::<usize>() >> 128usize;
+    if result > U512::from(U256::MAX) {
+        return Err(anyhow!("math overflow"));
+    }
+    Ok(result.to())
+}
+
+fn div_rounding_up(a: U256, b: U256) -> U256 {
+    let result = a / b;
+    if (a % b).is_zero() {
+        result
+    } else {
+        result + U256::from(1)
+    }
+}
+
+// rounds a result of exp2 up and widens it by the error margin
+fn with_margin(value: U256) -> U256 {
+    let value = value + U256::from(1);
+    value + value / U256::from(POW_ERROR_MARGIN) + U256::from(1)
+}
+
+// weight_0 * log2(balance_0) + weight_1 * log2(balance_1), i.e. log2(V) scaled by the weight
+// denominator. Comparing these avoids computing the fractional powers in the pool.
+pub fn get_log_invariant(
+    balance_0: U256,
+    balance_1: U256,
+    weight_0: u128,
+    weight_1: u128,
+) -> Result<U256> {
+    Ok(log2(balance_0)? * U256::from(weight_0) + log2(balance_1)? * U256::from(weight_1))
+}
+
+// log invariant of a pool after a swap, where the fee is taken from the input amounts
+pub fn get_log_invariant_fee_adjusted(
+    balance_0: u128,
+    balance_1: u128,
+    amount_0_in: u128,
+    amount_1_in: u128,
+    total_fee_per_1000: u128,
+    weight_0: u128,
+    weight_1: u128,
+) -> Result<U256> {
+    get_log_invariant(
+        U256::from(balance_0) * U256::from(BALANCE_SCALE)
+            - U256::from(amount_0_in) * U256::from(total_fee_per_1000),
+        U256::from(balance_1) * U256::from(BALANCE_SCALE)
+            - U256::from(amount_1_in) * U256::from(total_fee_per_1000),
+        weight_0,
+        weight_1,
+    )
+}
+
+// the invariant V itself, which grows linearly with liquidity
+pub fn get_invariant(
+    balance_0: u128,
+    balance_1: u128,
+    weight_0: u128,
+    weight_1: u128,
+) -> Result<U256> {
+    if balance_0 == 0 || balance_1 == 0 {
+        return Ok(U256::ZERO);
+    }
+    exp2(
+        get_log_invariant(
+            U256::from(balance_0),
+            U256::from(balance_1),
+            weight_0,
+            weight_1,
+        )? / U256::from(WEIGHT_DENOMINATOR),
+    )
+}
+
+pub fn get_amount_out(
+    amount_in: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    total_fee_per_1000: u128,
+    weight_in: u128,
+    weight_out: u128,
+) -> Result<u128> {
+    check_weights(weight_in, weight_out)?;
+    if amount_in == 0 {
+        return Err(anyhow!("INSUFFICIENT_INPUT_AMOUNT"));
+    }
+    if reserve_in == 0 || reserve_out == 0 {
+        return Err(anyhow!("INSUFFICIENT_LIQUIDITY"));
+    }
+    // reserve_out' = reserve_out * (x / x')^(weight_in / weight_out)
+    let x = U256::from(reserve_in) * U256::from(BALANCE_SCALE);
+    let x_new = x + U256::from(BALANCE_SCALE - total_fee_per_1000) * U256::from(amount_in);
+    let positive =
+        log2(U256::from(reserve_out))? * U256::from(weight_out) + log2(x)? * U256::from(weight_in);
+    let negative = log2(x_new)? * U256::from(weight_in);
+    let log_y = if negative >= positive {
+        U256::ZERO
+    } else {
+        div_rounding_up(positive - negative, U256::from(weight_out))
+    };
+    let y: u128 = with_margin(exp2(log_y)?).try_into()?;
+    Ok(reserve_out.saturating_sub(y))
+}
+
+pub fn get_amount_in(
+    amount_out: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    total_fee_per_1000: u128,
+    weight_in: u128,
+    weight_out: u128,
+) -> Result<u128> {
+    check_weights(weight_in, weight_out)?;
+    if amount_out == 0 {
+        return Err(anyhow!("INSUFFICIENT_OUTPUT_AMOUNT"));
+    }
+    if reserve_in == 0 || reserve_out == 0 || amount_out >= reserve_out {
+        return Err(anyhow!("INSUFFICIENT_LIQUIDITY"));
+    }
+    // x' = x * (reserve_out / (reserve_out - amount_out))^(weight_out / weight_in)
+    let x = U256::from(reserve_in) * U256::from(BALANCE_SCALE);
+    let log_ratio = log2(U256::from(reserve_out))? - log2(U256::from(reserve_out - amount_out))?;
+    let log_x_new =
+        log2(x)? + div_rounding_up(log_ratio * U256::from(weight_out), U256::from(weight_in));
+    let x_new = with_margin(exp2(log_x_new)?);
+    let dx = x_new - x.min(x_new);
+    Ok((dx / U256::from(BALANCE_SCALE - total_fee_per_1000) + U256::from(1)).try_into()?)
+}
@@ -34,6 +34,15 @@ pub enum AMMPoolMessage {
         data: Vec<u128>,
     },
 
+    // lends the amounts to `receiver`, which must pay them back plus the flash fee from its callback
+    #[opcode(4)]
+    FlashLoan {
+        amount_0: u128,
+        amount_1: u128,
+        receiver: AlkaneId,
+        data: Vec<u128>,
+    },
+
     #[opcode(10)]
     CollectFees {},
 
@@ -44,6 +53,64 @@ pub enum AMMPoolMessage {
     #[opcode(21)]
     SetTotalFee { total_fee_per_1000: u128 },
 
+    #[opcode(24)]
+    #[returns(u128)]
+    GetProtocolFee,
+
+    #[opcode(25)]
+    SetProtocolFee { protocol_fee_per_1000: u128 },
+
+    #[opcode(26)]
+    Skim { to: AlkaneId },
+
+    #[opcode(27)]
+    Sync,
+
+    #[opcode(28)]
+    #[returns(u128)]
+    GetFlashFee,
+
+    #[opcode(29)]
+    SetFlashFee { flash_fee_per_1000: u128 },
+
+    #[opcode(30)]
+    IncreaseObservationCardinalityNext { observation_cardinality_next: u128 },
+
+    #[opcode(31)]
+    #[returns(Vec<u8>)]
+    Consult { seconds_ago: Vec<u128> },
+
+    #[opcode(32)]
+    #[returns(u128, u128)]
+    GetOracleClock,
+
+    #[opcode(33)]
+    #[returns(u128)]
+    GetPaused,
+
+    #[opcode(34)]
+    SetPaused { paused: u128 },
+
+    #[opcode(35)]
+    #[returns(Vec<u8>)]
+    GetStats,
+
+    #[opcode(36)]
+    #[returns(Vec<u8>)]
+    GetFeeGrowthPerLp,
+
+    // the total fee at the resolution fee tiers use, GetTotalFee rounds it down to per 1000
+    #[opcode(37)]
+    #[returns(u128)]
+    GetTotalFeePerMillion,
+
+    // the factory sets the fees of pools in a fee tier with this
+    #[opcode(38)]
+    SetFeesPerMillion {
+        total_fee_per_1000000: u128,
+        protocol_fee_per_1000000: u128,
+    },
+
     #[opcode(50)]
     ForwardIncoming,
 
@@ -0,0 +1,225 @@
+use alkanes_runtime::{
+    declare_alkane, message::MessageDispatch, runtime::AlkaneResponder, storage::StoragePointer,
+};
+#[allow(unused_imports)]
+use alkanes_runtime::{
+    println,
+    stdio::{stdout, Write},
+};
+use alkanes_runtime_pool::AMMPoolBase;
+use alkanes_std_factory_support::MintableToken;
+use alkanes_support::{id::AlkaneId, response::CallResponse};
+use anyhow::{anyhow, Result};
+use metashrew_support::{
+    compat::{to_arraybuffer_layout, to_passback_ptr},
+    index_pointer::KeyValuePointer,
+};
+use oylswap_library::{fixed_point::UQ128x128, stableswap, U256};
+
+#[derive(MessageDispatch)]
+pub enum StablePoolMessage {
+    #[opcode(0)]
+    InitPool {
+        alkane_a: AlkaneId,
+        alkane_b: AlkaneId,
+        factory: AlkaneId,
+        amp: u128,
+    },
+
+    #[opcode(1)]
+    AddLiquidity,
+
+    #[opcode(2)]
+    WithdrawAndBurn,
+
+    // this low level function should generally not be called directly unless the user is experienced with alkanes contracts
+    #[opcode(3)]
+    Swap {
+        amount_0_out: u128,
+        amount_1_out: u128,
+        to: AlkaneId,
+        data: Vec<u128>,
+    },
+
+    // lends the amounts to `receiver`, which must pay them back plus the flash fee from its callback
+    #[opcode(4)]
+    FlashLoan {
+        amount_0: u128,
+        amount_1: u128,
+        receiver: AlkaneId,
+        data: Vec<u128>,
+    },
+
+    #[opcode(10)]
+    CollectFees {},
+
+    #[opcode(20)]
+    #[returns(u128)]
+    GetTotalFee {},
+
+    #[opcode(21)]
+    SetTotalFee { total_fee_per_1000: u128 },
+
+    #[opcode(22)]
+    #[returns(u128)]
+    GetAmplification,
+
+    #[opcode(24)]
+    #[returns(u128)]
+    GetProtocolFee,
+
+    #[opcode(25)]
+    SetProtocolFee { protocol_fee_per_1000: u128 },
+
+    #[opcode(26)]
+    Skim { to: AlkaneId },
+
+    #[opcode(27)]
+    Sync,
+
+    #[opcode(28)]
+    #[returns(u128)]
+    GetFlashFee,
+
+    #[opcode(29)]
+    SetFlashFee { flash_fee_per_1000: u128 },
+
+    #[opcode(30)]
+    IncreaseObservationCardinalityNext { observation_cardinality_next: u128 },
+
+    #[opcode(31)]
+    #[returns(Vec<u8>)]
+    Consult { seconds_ago: Vec<u128> },
+
+    #[opcode(32)]
+    #[returns(u128, u128)]
+    GetOracleClock,
+
+    #[opcode(33)]
+    #[returns(u128)]
+    GetPaused,
+
+    #[opcode(34)]
+    SetPaused { paused: u128 },
+
+    #[opcode(35)]
+    #[returns(Vec<u8>)]
+    GetStats,
+
+    #[opcode(36)]
+    #[returns(Vec<u8>)]
+    GetFeeGrowthPerLp,
+
+    // the total fee at the resolution fee tiers use, GetTotalFee rounds it down to per 1000.
+    // Stable pools are only created in the default fee tier, so there is no SetFeesPerMillion
+    #[opcode(37)]
+    #[returns(u128)]
+    GetTotalFeePerMillion,
+
+    #[opcode(50)]
+    ForwardIncoming,
+
+    #[opcode(97)]
+    #[returns(u128, u128)]
+    GetReserves,
+
+    #[opcode(98)]
+    #[returns(u128, u128)]
+    GetPriceCumulativeLast,
+
+    #[opcode(99)]
+    #[returns(String)]
+    GetName,
+
+    #[opcode(999)]
+    #[returns(Vec<u8>)]
+    PoolDetails,
+}
+
+// AMM pool for pegged pairs that prices swaps with the StableSwap invariant instead of the
+// constant product. Liquidity, fees and the protocol fee work exactly like the regular pool.
+#[derive(Default)]
+pub struct StablePool();
+
+impl StablePool {
+    fn amp_pointer(&self) -> StoragePointer {
+        StoragePointer::from_keyword("/amp")
+    }
+
+    fn amp(&self) -> u128 {
+        self.amp_pointer().get_value::<u128>()
+    }
+
+    fn init_pool(
+        &self,
+        alkane_a: AlkaneId,
+        alkane_b: AlkaneId,
+        factory: AlkaneId,
+        amp: u128,
+    ) -> Result<CallResponse> {
+        stableswap::check_amp(amp)?;
+        self.amp_pointer().set_value::<u128>(amp);
+        AMMPoolBase::init_pool(self, alkane_a, alkane_b, factory)
+    }
+
+    fn get_amplification(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = self.amp().to_le_bytes().to_vec();
+        Ok(response)
+    }
+}
+
+impl MintableToken for StablePool {}
+impl AMMPoolBase for StablePool {
+    // D is linear in the reserves, so D^2 plays the role of the constant product k
+    fn _k(&self, reserve_a: u128, reserve_b: u128) -> Result<U256> {
+        let d = stableswap::get_d(U256::from(reserve_a), U256::from(reserve_b), self.amp())?;
+        d.checked_mul(d).ok_or_else(|| anyhow!("math overflow"))
+    }
+
+    fn _check_invariant(
+        &self,
+        balance_0: u128,
+        balance_1: u128,
+        amount_0_in: u128,
+        amount_1_in: u128,
+        reserve_0: u128,
+        reserve_1: u128,
+    ) -> Result<()> {
+        let amp = self.amp();
+        let d_after = stableswap::get_d_fee_adjusted(
+            balance_0,
+            balance_1,
+            amount_0_in,
+            amount_1_in,
+            self.total_fee_per_1000(),
+            amp,
+        )?;
+        let d_before = stableswap::get_d_fee_adjusted(reserve_0, reserve_1, 0, 0, 0, amp)?;
+        if d_after < d_before {
+            return Err(anyhow!("D is not increasing"));
+        }
+        Ok(())
+    }
+
+    // away from balance the reserve ratio is not the price, the curve's slope is
+    fn _prices(&self, reserve0: u128, reserve1: u128) -> Result<(UQ128x128, UQ128x128)> {
+        if reserve0 == 0 || reserve1 == 0 {
+            return Ok((UQ128x128::default(), UQ128x128::default()));
+        }
+        let (numerator, denominator) =
+            stableswap::get_marginal_price(reserve0, reserve1, self.amp())?;
+        Ok((
+            UQ128x128::ratio(numerator, denominator)?,
+            UQ128x128::ratio(denominator, numerator)?,
+        ))
+    }
+}
+
+impl AlkaneResponder for StablePool {}
+declare_alkane! {
+    impl AlkaneResponder for StablePool {
+        type Message = StablePoolMessage;
+    }
+}
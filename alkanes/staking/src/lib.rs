@@ -0,0 +1,517 @@
+use alkanes_runtime::runtime::AlkaneResponder;
+use alkanes_runtime::storage::StoragePointer;
+use alkanes_runtime::{declare_alkane, message::MessageDispatch};
+#[allow(unused_imports)]
+use alkanes_runtime::{
+    println,
+    stdio::{stdout, Write},
+};
+use alkanes_support::id::AlkaneId;
+use alkanes_support::parcel::AlkaneTransfer;
+use alkanes_support::response::CallResponse;
+use anyhow::{anyhow, Result};
+use metashrew_support::compat::{to_arraybuffer_layout, to_passback_ptr};
+use metashrew_support::index_pointer::KeyValuePointer;
+use oylswap_library::{
+    check_factory_pool, create_ownership_token, fixed_point::UQ128x128, StorableU256, U256,
+};
+use std::sync::Arc;
+
+// A reward programme pays reward_per_block of reward_token to the stakers of one pool until
+// end_height. Rewards of blocks in which nothing was staked are kept as undistributed and given
+// back to the factory owner the next time the programme is configured.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct RewardProgramme {
+    pub reward_token: AlkaneId,
+    pub reward_per_block: u128,
+    pub end_height: u128,
+    pub last_reward_height: u128,
+    pub undistributed: u128,
+}
+
+impl RewardProgramme {
+    pub fn try_to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.reward_token.block.to_le_bytes());
+        bytes.extend_from_slice(&self.reward_token.tx.to_le_bytes());
+        bytes.extend_from_slice(&self.reward_per_block.to_le_bytes());
+        bytes.extend_from_slice(&self.end_height.to_le_bytes());
+        bytes.extend_from_slice(&self.last_reward_height.to_le_bytes());
+        bytes.extend_from_slice(&self.undistributed.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_vec(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 96 {
+            return Err(anyhow!("Invalid bytes length for RewardProgramme"));
+        }
+        Ok(RewardProgramme {
+            reward_token: AlkaneId::new(
+                u128::from_le_bytes(bytes[0..16].try_into()?),
+                u128::from_le_bytes(bytes[16..32].try_into()?),
+            ),
+            reward_per_block: u128::from_le_bytes(bytes[32..48].try_into()?),
+            end_height: u128::from_le_bytes(bytes[48..64].try_into()?),
+            last_reward_height: u128::from_le_bytes(bytes[64..80].try_into()?),
+            undistributed: u128::from_le_bytes(bytes[80..96].try_into()?),
+        })
+    }
+
+    // rewards not yet issued to the stakers between `height` and the end of the programme
+    pub fn unissued(&self, height: u128) -> u128 {
+        if self.end_height > height {
+            self.reward_per_block * (self.end_height - height)
+        } else {
+            0
+        }
+    }
+}
+
+// `amount` LP tokens of `pool` staked. reward_debt is the pool's reward per share the position
+// was last paid up to, so its pending rewards are amount * (reward per share - reward_debt).
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct StakePosition {
+    pub pool: AlkaneId,
+    pub amount: u128,
+    pub reward_debt: U256,
+}
+
+impl StakePosition {
+    pub fn try_to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.pool.block.to_le_bytes());
+        bytes.extend_from_slice(&self.pool.tx.to_le_bytes());
+        bytes.extend_from_slice(&self.amount.to_le_bytes());
+        bytes.extend_from_slice(&self.reward_debt.to_le_bytes::<32>());
+        bytes
+    }
+
+    pub fn from_vec(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 80 {
+            return Err(anyhow!("Invalid bytes length for StakePosition"));
+        }
+        Ok(StakePosition {
+            pool: AlkaneId::new(
+                u128::from_le_bytes(bytes[0..16].try_into()?),
+                u128::from_le_bytes(bytes[16..32].try_into()?),
+            ),
+            amount: u128::from_le_bytes(bytes[32..48].try_into()?),
+            reward_debt: U256::from_le_bytes::<32>(bytes[48..80].try_into()?),
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct Staking(());
+
+#[derive(MessageDispatch)]
+enum StakingMessage {
+    // factory_auth_token is the factory's auth token, held by the factory owner
+    #[opcode(0)]
+    Initialize {
+        factory: AlkaneId,
+        factory_auth_token: AlkaneId,
+    },
+
+    // factory owner only. Pays reward_per_block to the stakers of `pool` from now until
+    // end_height. The reward tokens not already held for the programme are sent along with the
+    // call, and anything left over from the previous configuration is returned
+    #[opcode(1)]
+    SetRewardProgramme {
+        pool: AlkaneId,
+        reward_per_block: u128,
+        end_height: u128,
+    },
+
+    // the lp tokens are sent along with the call, with a position token to add to that position.
+    // Returns the position token and the rewards pending on it
+    #[opcode(2)]
+    Stake {},
+
+    // the position token is sent along with the call and returned while some lp is still staked
+    #[opcode(3)]
+    Unstake { amount: u128 },
+
+    #[opcode(4)]
+    Harvest {},
+
+    #[opcode(5)]
+    #[returns(u128)]
+    GetPendingRewards { position_id: AlkaneId },
+
+    #[opcode(6)]
+    #[returns(Vec<u8>)]
+    GetRewardProgramme { pool: AlkaneId },
+
+    #[opcode(7)]
+    #[returns(Vec<u8>)]
+    GetPosition { position_id: AlkaneId },
+}
+
+impl Staking {
+    fn factory(&self) -> Result<AlkaneId> {
+        Ok(StoragePointer::from_keyword("/factory_id")
+            .get()
+            .as_ref()
+            .clone()
+            .try_into()?)
+    }
+
+    fn factory_auth_token(&self) -> Result<AlkaneId> {
+        Ok(StoragePointer::from_keyword("/factory_auth_token")
+            .get()
+            .as_ref()
+            .clone()
+            .try_into()?)
+    }
+
+    fn programme_pointer(&self, pool: &AlkaneId) -> StoragePointer {
+        StoragePointer::from_keyword("/programmes/").select(&pool.clone().into())
+    }
+
+    fn programme_of(&self, pool: &AlkaneId) -> Result<Option<RewardProgramme>> {
+        let bytes = self.programme_pointer(pool).get();
+        if bytes.len() == 0 {
+            return Ok(None);
+        }
+        Ok(Some(RewardProgramme::from_vec(&bytes)?))
+    }
+
+    fn reward_per_share_pointer(&self, pool: &AlkaneId) -> StoragePointer {
+        StoragePointer::from_keyword("/rewardpershare/").select(&pool.clone().into())
+    }
+
+    fn total_staked_pointer(&self, pool: &AlkaneId) -> StoragePointer {
+        StoragePointer::from_keyword("/totalstaked/").select(&pool.clone().into())
+    }
+
+    fn position_pointer(&self, position_id: &AlkaneId) -> StoragePointer {
+        StoragePointer::from_keyword("/positions/").select(&position_id.clone().into())
+    }
+
+    fn position_of(&self, position_id: &AlkaneId) -> Result<Option<StakePosition>> {
+        let bytes = self.position_pointer(position_id).get();
+        if bytes.len() == 0 {
+            return Ok(None);
+        }
+        Ok(Some(StakePosition::from_vec(&bytes)?))
+    }
+
+    fn initialize(&self, factory: AlkaneId, factory_auth_token: AlkaneId) -> Result<CallResponse> {
+        self.observe_initialization()?;
+        let context = self.context()?;
+        StoragePointer::from_keyword("/factory_id").set(Arc::new(factory.into()));
+        StoragePointer::from_keyword("/factory_auth_token")
+            .set(Arc::new(factory_auth_token.into()));
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    fn only_factory_owner(&self) -> Result<()> {
+        let context = self.context()?;
+        let auth_token = self.factory_auth_token()?;
+        if !context
+            .incoming_alkanes
+            .0
+            .iter()
+            .any(|transfer| transfer.id == auth_token && transfer.value > 0)
+        {
+            return Err(anyhow!("factory auth token is not in incoming alkanes"));
+        }
+        Ok(())
+    }
+
+    // the programme and reward per share of `pool` brought up to the current block
+    fn _accrued(&self, pool: &AlkaneId) -> Result<Option<(RewardProgramme, U256)>> {
+        let mut programme = match self.programme_of(pool)? {
+            Some(programme) => programme,
+            None => return Ok(None),
+        };
+        let mut reward_per_share: U256 = self
+            .reward_per_share_pointer(pool)
+            .get_value::<StorableU256>()
+            .into();
+        let now = self.height() as u128;
+        let to = now.min(programme.end_height);
+        if to > programme.last_reward_height {
+            let reward = programme.reward_per_block * (to - programme.last_reward_height);
+            let total_staked = self.total_staked_pointer(pool).get_value::<u128>();
+            if total_staked == 0 {
+                programme.undistributed += reward;
+            } else {
+                reward_per_share += UQ128x128::fraction(reward, total_staked)?.0;
+            }
+        }
+        if now > programme.last_reward_height {
+            programme.last_reward_height = now;
+        }
+        Ok(Some((programme, reward_per_share)))
+    }
+
+    fn _update_pool(&self, pool: &AlkaneId) -> Result<Option<(RewardProgramme, U256)>> {
+        let accrued = self._accrued(pool)?;
+        if let Some((programme, reward_per_share)) = &accrued {
+            self.programme_pointer(pool)
+                .set(Arc::new(programme.try_to_vec()));
+            self.reward_per_share_pointer(pool)
+                .set_value::<StorableU256>((*reward_per_share).into());
+        }
+        Ok(accrued)
+    }
+
+    fn _pending(&self, position: &StakePosition, reward_per_share: U256) -> Result<u128> {
+        UQ128x128(reward_per_share - position.reward_debt).mul_decode(position.amount)
+    }
+
+    fn _pay(&self, response: &mut CallResponse, id: AlkaneId, value: u128) {
+        if value > 0 {
+            response.alkanes.pay(AlkaneTransfer { id, value });
+        }
+    }
+
+    fn set_reward_programme(
+        &self,
+        pool: AlkaneId,
+        reward_per_block: u128,
+        end_height: u128,
+    ) -> Result<CallResponse> {
+        self.only_factory_owner()?;
+        let context = self.context()?;
+        let auth_token = self.factory_auth_token()?;
+        let now = self.height() as u128;
+        if end_height <= now {
+            return Err(anyhow!("programme must end in the future"));
+        }
+        // programmes can only reward real lp tokens
+        check_factory_pool(self, self.factory()?, pool)?;
+
+        let funding: Vec<AlkaneTransfer> = context
+            .incoming_alkanes
+            .0
+            .iter()
+            .filter(|transfer| transfer.id != auth_token)
+            .cloned()
+            .collect();
+        if funding.len() > 1 {
+            return Err(anyhow!("a programme pays out a single reward token"));
+        }
+        let (reward_token, funded) = match (self._update_pool(&pool)?, funding.first()) {
+            (Some((programme, _)), Some(transfer)) if transfer.id != programme.reward_token => {
+                return Err(anyhow!(
+                    "programme rewards {:?}, not {:?}",
+                    programme.reward_token,
+                    transfer.id
+                ));
+            }
+            (Some((programme, _)), transfer) => (
+                programme.reward_token,
+                programme.unissued(now)
+                    + programme.undistributed
+                    + transfer.map(|t| t.value).unwrap_or(0),
+            ),
+            (None, Some(transfer)) => (transfer.id, transfer.value),
+            (None, None) => {
+                return Err(anyhow!("must send the reward token to fund the programme"))
+            }
+        };
+        let required = reward_per_block
+            .checked_mul(end_height - now)
+            .ok_or_else(|| anyhow!("reward_per_block overflow"))?;
+        if funded < required {
+            return Err(anyhow!(
+                "INSUFFICIENT_REWARDS: funded({}) < required({})",
+                funded,
+                required
+            ));
+        }
+        self.programme_pointer(&pool).set(Arc::new(
+            RewardProgramme {
+                reward_token,
+                reward_per_block,
+                end_height,
+                last_reward_height: now,
+                undistributed: 0,
+            }
+            .try_to_vec(),
+        ));
+
+        let mut response = CallResponse::default();
+        for transfer in context.incoming_alkanes.0.iter() {
+            if transfer.id == auth_token {
+                response.alkanes.pay(transfer.clone());
+            }
+        }
+        self._pay(&mut response, reward_token, funded - required);
+        Ok(response)
+    }
+
+    fn _incoming_position(&self) -> Result<(AlkaneId, StakePosition)> {
+        let context = self.context()?;
+        if context.incoming_alkanes.0.len() != 1 || context.incoming_alkanes.0[0].value == 0 {
+            return Err(anyhow!("must send exactly one position token"));
+        }
+        let position_id = context.incoming_alkanes.0[0].id;
+        let position = self
+            .position_of(&position_id)?
+            .ok_or_else(|| anyhow!("position {:?} does not exist", position_id))?;
+        Ok((position_id, position))
+    }
+
+    fn stake(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut existing = None;
+        let mut lp = None;
+        for transfer in context.incoming_alkanes.0.iter() {
+            if transfer.value == 0 {
+                continue;
+            }
+            match self.position_of(&transfer.id)? {
+                Some(position) if existing.is_none() => existing = Some((transfer.id, position)),
+                None if lp.is_none() => lp = Some(transfer.clone()),
+                _ => {
+                    return Err(anyhow!(
+                        "must send one lp token and at most one position token"
+                    ))
+                }
+            }
+        }
+        let lp = lp.ok_or_else(|| anyhow!("must send lp tokens to stake"))?;
+        let (programme, reward_per_share) = self
+            ._update_pool(&lp.id)?
+            .ok_or_else(|| anyhow!("no reward programme for {:?}", lp.id))?;
+
+        let mut response = CallResponse::default();
+        let (position_id, mut position) = match existing {
+            Some((position_id, position)) => {
+                if position.pool != lp.id {
+                    return Err(anyhow!("position {:?} stakes another pool", position_id));
+                }
+                self._pay(
+                    &mut response,
+                    programme.reward_token,
+                    self._pending(&position, reward_per_share)?,
+                );
+                (position_id, position)
+            }
+            None => (
+                create_ownership_token(self)?.id,
+                StakePosition {
+                    pool: lp.id,
+                    ..Default::default()
+                },
+            ),
+        };
+        position.amount += lp.value;
+        position.reward_debt = reward_per_share;
+        self.position_pointer(&position_id)
+            .set(Arc::new(position.try_to_vec()));
+        let mut total_staked = self.total_staked_pointer(&lp.id);
+        let total = total_staked.get_value::<u128>();
+        total_staked.set_value::<u128>(total + lp.value);
+
+        response.alkanes.pay(AlkaneTransfer {
+            id: position_id,
+            value: 1,
+        });
+        Ok(response)
+    }
+
+    // pays the pending rewards of the position and brings its reward debt up to date
+    fn _harvest(&self, response: &mut CallResponse, position: &mut StakePosition) -> Result<()> {
+        let (programme, reward_per_share) = self
+            ._update_pool(&position.pool)?
+            .ok_or_else(|| anyhow!("no reward programme for {:?}", position.pool))?;
+        self._pay(
+            response,
+            programme.reward_token,
+            self._pending(position, reward_per_share)?,
+        );
+        position.reward_debt = reward_per_share;
+        Ok(())
+    }
+
+    fn unstake(&self, amount: u128) -> Result<CallResponse> {
+        let (position_id, mut position) = self._incoming_position()?;
+        if amount == 0 || amount > position.amount {
+            return Err(anyhow!(
+                "can not unstake {} of {} staked",
+                amount,
+                position.amount
+            ));
+        }
+        let mut response = CallResponse::default();
+        self._harvest(&mut response, &mut position)?;
+        position.amount -= amount;
+        let mut total_staked = self.total_staked_pointer(&position.pool);
+        let total = total_staked.get_value::<u128>();
+        total_staked.set_value::<u128>(total - amount);
+        self._pay(&mut response, position.pool, amount);
+
+        if position.amount == 0 {
+            // the position token stays with the contract once everything is unstaked
+            self.position_pointer(&position_id).set(Arc::new(vec![]));
+        } else {
+            self.position_pointer(&position_id)
+                .set(Arc::new(position.try_to_vec()));
+            response.alkanes.pay(AlkaneTransfer {
+                id: position_id,
+                value: 1,
+            });
+        }
+        Ok(response)
+    }
+
+    fn harvest(&self) -> Result<CallResponse> {
+        let (position_id, mut position) = self._incoming_position()?;
+        let mut response = CallResponse::default();
+        self._harvest(&mut response, &mut position)?;
+        self.position_pointer(&position_id)
+            .set(Arc::new(position.try_to_vec()));
+        response.alkanes.pay(AlkaneTransfer {
+            id: position_id,
+            value: 1,
+        });
+        Ok(response)
+    }
+
+    fn get_pending_rewards(&self, position_id: AlkaneId) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        let position = self
+            .position_of(&position_id)?
+            .ok_or_else(|| anyhow!("position {:?} does not exist", position_id))?;
+        let pending = match self._accrued(&position.pool)? {
+            Some((_, reward_per_share)) => self._pending(&position, reward_per_share)?,
+            None => 0,
+        };
+        response.data = pending.to_le_bytes().to_vec();
+        Ok(response)
+    }
+
+    fn get_reward_programme(&self, pool: AlkaneId) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = self
+            .programme_of(&pool)?
+            .ok_or_else(|| anyhow!("no reward programme for {:?}", pool))?
+            .try_to_vec();
+        Ok(response)
+    }
+
+    fn get_position(&self, position_id: AlkaneId) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = self
+            .position_of(&position_id)?
+            .ok_or_else(|| anyhow!("position {:?} does not exist", position_id))?
+            .try_to_vec();
+        Ok(response)
+    }
+}
+
+impl AlkaneResponder for Staking {}
+
+declare_alkane! {
+    impl AlkaneResponder for Staking {
+        type Message = StakingMessage;
+    }
+}
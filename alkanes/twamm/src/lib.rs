@@ -0,0 +1,624 @@
+use alkanes_runtime::runtime::AlkaneResponder;
+use alkanes_runtime::storage::StoragePointer;
+use alkanes_runtime::{declare_alkane, message::MessageDispatch};
+#[allow(unused_imports)]
+use alkanes_runtime::{
+    println,
+    stdio::{stdout, Write},
+};
+use alkanes_support::cellpack::Cellpack;
+use alkanes_support::id::AlkaneId;
+use alkanes_support::parcel::{AlkaneTransfer, AlkaneTransferParcel};
+use alkanes_support::response::CallResponse;
+use anyhow::{anyhow, Result};
+use metashrew_support::compat::{to_arraybuffer_layout, to_passback_ptr};
+use metashrew_support::index_pointer::KeyValuePointer;
+use metashrew_support::utils::consume_u128;
+use oylswap_library::{
+    create_ownership_token, fixed_point::UQ128x128, get_amount_out_per_1000000, PoolInfo,
+    StorableU256, U256,
+};
+use std::sync::Arc;
+
+// long term orders expire on multiples of this many blocks, so settling only has to look at
+// one boundary per interval for expiring orders
+pub const ORDER_BLOCK_INTERVAL: u128 = 10;
+// a settlement swap has to get at least the pool's TWAP since the previous settlement, less this
+// much per 1000 to leave room for the pool fee and the swap's own price impact
+pub const MAX_SETTLEMENT_DISCOUNT_PER_1000: u128 = 50;
+// the factory pool template the twamm trades against
+const POOL_TEMPLATE_CONSTANT_PRODUCT: u128 = 0;
+
+// A long term order sells sale_rate of one pool token per block until `expiry`. Direction 0 sells
+// token 0 for token 1 and direction 1 the reverse. reward_factor_last is the reward factor of
+// the order's direction when its proceeds were last paid out.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct LongTermOrder {
+    pub direction: u128,
+    pub sale_rate: u128,
+    pub expiry: u128,
+    pub reward_factor_last: U256,
+}
+
+impl LongTermOrder {
+    pub fn try_to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.direction.to_le_bytes());
+        bytes.extend_from_slice(&self.sale_rate.to_le_bytes());
+        bytes.extend_from_slice(&self.expiry.to_le_bytes());
+        bytes.extend_from_slice(&self.reward_factor_last.to_le_bytes::<32>());
+        bytes
+    }
+
+    pub fn from_vec(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 80 {
+            return Err(anyhow!("Invalid bytes length for LongTermOrder"));
+        }
+        Ok(LongTermOrder {
+            direction: u128::from_le_bytes(bytes[0..16].try_into()?),
+            sale_rate: u128::from_le_bytes(bytes[16..32].try_into()?),
+            expiry: u128::from_le_bytes(bytes[32..48].try_into()?),
+            reward_factor_last: U256::from_le_bytes::<32>(bytes[48..80].try_into()?),
+        })
+    }
+}
+
+// the block ranges between `from` and `to`, cut at every order expiry boundary. Each entry is
+// the block the range ends at and its length
+pub fn settlement_segments(from: u128, to: u128) -> Vec<(u128, u128)> {
+    let mut segments = Vec::new();
+    let mut cursor = from;
+    while cursor < to {
+        let boundary = (cursor / ORDER_BLOCK_INTERVAL + 1) * ORDER_BLOCK_INTERVAL;
+        let end = boundary.min(to);
+        segments.push((end, end - cursor));
+        cursor = end;
+    }
+    segments
+}
+
+#[derive(Default)]
+pub struct Twamm(());
+
+// Settlement is not triggered by the pool: swaps made directly on the pool do not settle the
+// orders first, so keepers are expected to call ExecuteVirtualOrders every block to keep each
+// settlement swap small. Each settlement swap is checked against the pool's TWAP since the
+// previous one, see MAX_SETTLEMENT_DISCOUNT_PER_1000.
+#[derive(MessageDispatch)]
+enum TwammMessage {
+    // the pool has to be a constant product pool of `factory`
+    #[opcode(0)]
+    Initialize { factory: AlkaneId, pool: AlkaneId },
+
+    // settles each direction of the long term orders up to the current block and returns the
+    // block each one is settled up to. A direction that cannot be settled stays where it was.
+    // PlaceOrder settles the direction of the order first and fails if it cannot be settled,
+    // WithdrawProceeds and CancelOrder go ahead on the last settlement
+    #[opcode(1)]
+    #[returns(Vec<u8>)]
+    ExecuteVirtualOrders {},
+
+    // the pool token to sell is sent along with the call. Returns the order token and whatever
+    // does not divide evenly over the blocks until expiry
+    #[opcode(2)]
+    PlaceOrder { num_intervals: u128 },
+
+    // the order token is sent along with the call and returned until the order has expired
+    #[opcode(3)]
+    WithdrawProceeds {},
+
+    #[opcode(4)]
+    CancelOrder {},
+
+    #[opcode(5)]
+    #[returns(Vec<u8>)]
+    GetOrder { order_id: AlkaneId },
+}
+
+impl Twamm {
+    fn pool(&self) -> Result<AlkaneId> {
+        let ptr = StoragePointer::from_keyword("/pool_id")
+            .get()
+            .as_ref()
+            .clone();
+        let mut cursor = std::io::Cursor::<Vec<u8>>::new(ptr);
+        Ok(AlkaneId::new(
+            consume_u128(&mut cursor)?,
+            consume_u128(&mut cursor)?,
+        ))
+    }
+    fn set_pool(&self, pool: AlkaneId) {
+        StoragePointer::from_keyword("/pool_id").set(Arc::new(pool.into()));
+    }
+    fn alkanes_for_self(&self) -> Result<(AlkaneId, AlkaneId)> {
+        Ok((
+            StoragePointer::from_keyword("/alkane/0")
+                .get()
+                .as_ref()
+                .clone()
+                .try_into()?,
+            StoragePointer::from_keyword("/alkane/1")
+                .get()
+                .as_ref()
+                .clone()
+                .try_into()?,
+        ))
+    }
+    // each direction settles on its own, so one that cannot be settled does not hold up the other
+    fn last_executed_pointer(&self, direction: u128) -> StoragePointer {
+        StoragePointer::from_keyword(&format!("/lastexecuted/{}", direction))
+    }
+    // the pool's price cumulative of the direction and its oracle time at the last settlement
+    fn settled_cumulative_pointer(&self, direction: u128) -> StoragePointer {
+        StoragePointer::from_keyword(&format!("/settledcumulative/{}", direction))
+    }
+    fn settled_time_pointer(&self, direction: u128) -> StoragePointer {
+        StoragePointer::from_keyword(&format!("/settledtime/{}", direction))
+    }
+    // total sale rate of the open orders in a direction
+    fn sale_rate_pointer(&self, direction: u128) -> StoragePointer {
+        StoragePointer::from_keyword(&format!("/salerate/{}", direction))
+    }
+    // sale rate of the orders in a direction that expire at `block`
+    fn sale_rate_ending_pointer(&self, direction: u128, block: u128) -> StoragePointer {
+        StoragePointer::from_keyword(&format!("/salerateending/{}/", direction))
+            .select(&block.to_le_bytes().to_vec())
+    }
+    // tokens received per unit of sale rate since the contract was created, as UQ128x128
+    fn reward_factor_pointer(&self, direction: u128) -> StoragePointer {
+        StoragePointer::from_keyword(&format!("/rewardfactor/{}", direction))
+    }
+    // reward factor of a direction frozen at an expiry boundary
+    fn reward_factor_at_pointer(&self, direction: u128, block: u128) -> StoragePointer {
+        StoragePointer::from_keyword(&format!("/rewardfactorat/{}/", direction))
+            .select(&block.to_le_bytes().to_vec())
+    }
+    fn order_pointer(&self, order_id: &AlkaneId) -> StoragePointer {
+        StoragePointer::from_keyword("/orders/").select(&order_id.clone().into())
+    }
+    fn order(&self, order_id: &AlkaneId) -> Result<LongTermOrder> {
+        let bytes = self.order_pointer(order_id).get();
+        if bytes.len() == 0 {
+            return Err(anyhow!("order {:?} does not exist", order_id));
+        }
+        LongTermOrder::from_vec(&bytes)
+    }
+    fn set_order(&self, order_id: &AlkaneId, order: &LongTermOrder) {
+        self.order_pointer(order_id)
+            .set(Arc::new(order.try_to_vec()));
+    }
+
+    fn initialize(&self, factory: AlkaneId, pool: AlkaneId) -> Result<CallResponse> {
+        self.observe_initialization()?;
+        let context = self.context()?;
+        let template = u128::from_le_bytes(
+            self.call(
+                &Cellpack {
+                    target: factory,
+                    inputs: vec![35, pool.block, pool.tx],
+                },
+                &AlkaneTransferParcel::default(),
+                self.fuel(),
+            )?
+            .data[..16]
+                .try_into()?,
+        );
+        if template != POOL_TEMPLATE_CONSTANT_PRODUCT {
+            return Err(anyhow!("twamm only supports constant product pools"));
+        }
+        let pool_info = PoolInfo::from_vec(
+            &self
+                .call(
+                    &Cellpack {
+                        target: pool,
+                        inputs: vec![999],
+                    },
+                    &AlkaneTransferParcel::default(),
+                    self.fuel(),
+                )?
+                .data,
+        )?;
+        self.set_pool(pool);
+        StoragePointer::from_keyword("/alkane/0").set(Arc::new(pool_info.token_a.into()));
+        StoragePointer::from_keyword("/alkane/1").set(Arc::new(pool_info.token_b.into()));
+        for direction in 0..2 {
+            let (cumulative, time) = self._price_cumulative(direction)?;
+            self.last_executed_pointer(direction)
+                .set_value::<u128>(self.height() as u128);
+            self.settled_cumulative_pointer(direction)
+                .set_value::<StorableU256>(cumulative.into());
+            self.settled_time_pointer(direction).set_value::<u128>(time);
+        }
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    // the pool's price cumulative of the direction's token as of now and the oracle time it is
+    // for. The pool only moves the cumulative once time passes, so reserves pushed around in the
+    // current block do not count towards it
+    fn _price_cumulative(&self, direction: u128) -> Result<(U256, u128)> {
+        let pool = self.pool()?;
+        let consult = self.call(
+            &Cellpack {
+                target: pool,
+                inputs: vec![31, 1, 0],
+            },
+            &AlkaneTransferParcel::default(),
+            self.fuel(),
+        )?;
+        let offset = if direction == 0 { 0 } else { 32 };
+        let cumulative = U256::from_le_bytes::<32>(consult.data[offset..offset + 32].try_into()?);
+        let clock = self.call(
+            &Cellpack {
+                target: pool,
+                inputs: vec![32],
+            },
+            &AlkaneTransferParcel::default(),
+            self.fuel(),
+        )?;
+        Ok((
+            cumulative,
+            u128::from_le_bytes(clock.data[16..32].try_into()?),
+        ))
+    }
+
+    // the lowest price a settlement swap of the direction may get, None if no time has passed on
+    // the pool's clock since the last settlement so there is nothing to average over
+    fn _min_settlement_price(
+        &self,
+        direction: u128,
+        cumulative: U256,
+        time: u128,
+    ) -> Result<Option<UQ128x128>> {
+        let settled_time = self.settled_time_pointer(direction).get_value::<u128>();
+        if time <= settled_time {
+            return Ok(None);
+        }
+        let settled_cumulative: U256 = self
+            .settled_cumulative_pointer(direction)
+            .get_value::<StorableU256>()
+            .into();
+        let twap = cumulative.wrapping_sub(settled_cumulative) / U256::from(time - settled_time);
+        Ok(Some(UQ128x128(
+            twap * U256::from(1000 - MAX_SETTLEMENT_DISCOUNT_PER_1000) / U256::from(1000),
+        )))
+    }
+
+    fn _get_reserves(&self, pool: AlkaneId) -> Result<(u128, u128)> {
+        let response = self.call(
+            &Cellpack {
+                target: pool,
+                inputs: vec![97],
+            },
+            &AlkaneTransferParcel::default(),
+            self.fuel(),
+        )?;
+        Ok((
+            u128::from_le_bytes(response.data[0..16].try_into()?),
+            u128::from_le_bytes(response.data[16..32].try_into()?),
+        ))
+    }
+
+    // per 1000000, pools in fee tiers can charge less than 0.1%
+    fn _get_total_fee(&self, pool: AlkaneId) -> Result<u128> {
+        let response = self.call(
+            &Cellpack {
+                target: pool,
+                inputs: vec![37],
+            },
+            &AlkaneTransferParcel::default(),
+            self.fuel(),
+        )?;
+        Ok(u128::from_le_bytes(response.data[..16].try_into()?))
+    }
+
+    // the amount selling `amount_in` of the direction's token through the pool buys. If it buys
+    // nothing or sells below min_price this fails, so the direction is left unsettled and the
+    // amount carries over to the next settlement
+    fn _quote(
+        &self,
+        direction: u128,
+        amount_in: u128,
+        min_price: Option<UQ128x128>,
+    ) -> Result<u128> {
+        let pool = self.pool()?;
+        let (reserve_0, reserve_1) = self._get_reserves(pool)?;
+        let total_fee = self._get_total_fee(pool)?;
+        let (reserve_in, reserve_out) = if direction == 0 {
+            (reserve_0, reserve_1)
+        } else {
+            (reserve_1, reserve_0)
+        };
+        let amount_out = get_amount_out_per_1000000(amount_in, reserve_in, reserve_out, total_fee)?;
+        if amount_out == 0 {
+            return Err(anyhow!("settlement is too small to buy anything"));
+        }
+        if let Some(min_price) = min_price {
+            if UQ128x128::fraction(amount_out, amount_in)?.0 < min_price.0 {
+                return Err(anyhow!("settlement price is below the pool TWAP"));
+            }
+        }
+        Ok(amount_out)
+    }
+
+    // sells `amount_in` of the direction's token through the pool for `amount_out`
+    fn _swap(&self, direction: u128, amount_in: u128, amount_out: u128) -> Result<()> {
+        let pool = self.pool()?;
+        let (token_0, token_1) = self.alkanes_for_self()?;
+        let token_in = if direction == 0 { token_0 } else { token_1 };
+        let (amount_0_out, amount_1_out) = if direction == 0 {
+            (0, amount_out)
+        } else {
+            (amount_out, 0)
+        };
+        self.call(
+            &Cellpack {
+                target: pool,
+                inputs: vec![3, amount_0_out, amount_1_out, 0, 0, 0],
+            },
+            &AlkaneTransferParcel(vec![AlkaneTransfer {
+                id: token_in,
+                value: amount_in,
+            }]),
+            self.fuel(),
+        )?;
+        Ok(())
+    }
+
+    // Sells everything the open orders of the direction have accrued since its last settlement
+    // in one swap, and credits the proceeds to the blocks they were sold in at the average price
+    // of the swap. Orders that expired in between stop selling at their expiry boundary. Nothing
+    // can fail after the swap and nothing is written before it, so a failed settlement leaves
+    // the direction as it was.
+    fn _execute_virtual_orders_for(&self, direction: u128) -> Result<()> {
+        let now = self.height() as u128;
+        let last = self.last_executed_pointer(direction).get_value::<u128>();
+        if now <= last {
+            return Ok(());
+        }
+        let segments = settlement_segments(last, now);
+        let mut sale_rate = self.sale_rate_pointer(direction).get_value::<u128>();
+        let mut amount_sold: u128 = 0;
+        for (end, length) in segments.iter() {
+            amount_sold = sale_rate
+                .checked_mul(*length)
+                .and_then(|sold| amount_sold.checked_add(sold))
+                .ok_or_else(|| anyhow!("amount sold overflows"))?;
+            if end % ORDER_BLOCK_INTERVAL == 0 {
+                sale_rate -= self
+                    .sale_rate_ending_pointer(direction, *end)
+                    .get_value::<u128>();
+            }
+        }
+        let (cumulative, time) = self._price_cumulative(direction)?;
+        let (amount_out, price) = if amount_sold == 0 {
+            (0, UQ128x128::default())
+        } else {
+            let min_price = self._min_settlement_price(direction, cumulative, time)?;
+            let amount_out = self._quote(direction, amount_sold, min_price)?;
+            (amount_out, UQ128x128::fraction(amount_out, amount_sold)?)
+        };
+
+        let mut sale_rate = self.sale_rate_pointer(direction).get_value::<u128>();
+        let mut reward_factor: U256 = self
+            .reward_factor_pointer(direction)
+            .get_value::<StorableU256>()
+            .into();
+        let mut reward_factors_at = Vec::new();
+        for (end, length) in segments.iter() {
+            if sale_rate != 0 {
+                reward_factor = price
+                    .0
+                    .checked_mul(U256::from(*length))
+                    .and_then(|reward| reward_factor.checked_add(reward))
+                    .ok_or_else(|| anyhow!("reward factor overflows"))?;
+            }
+            if end % ORDER_BLOCK_INTERVAL == 0 {
+                reward_factors_at.push((*end, reward_factor));
+                sale_rate -= self
+                    .sale_rate_ending_pointer(direction, *end)
+                    .get_value::<u128>();
+            }
+        }
+        if amount_sold != 0 {
+            self._swap(direction, amount_sold, amount_out)?;
+        }
+
+        for (end, reward_factor) in reward_factors_at {
+            self.reward_factor_at_pointer(direction, end)
+                .set_value::<StorableU256>(reward_factor.into());
+        }
+        self.sale_rate_pointer(direction)
+            .set_value::<u128>(sale_rate);
+        self.reward_factor_pointer(direction)
+            .set_value::<StorableU256>(reward_factor.into());
+        self.settled_cumulative_pointer(direction)
+            .set_value::<StorableU256>(cumulative.into());
+        self.settled_time_pointer(direction).set_value::<u128>(time);
+        self.last_executed_pointer(direction).set_value::<u128>(now);
+        Ok(())
+    }
+
+    // Settles the direction as far as possible and returns the block it is settled up to. A
+    // paused pool or a settlement below the TWAP must not lock the orders in, so if the
+    // settlement fails the direction is handled as of the last one.
+    fn _settle(&self, direction: u128) -> u128 {
+        let _ = self._execute_virtual_orders_for(direction);
+        self.last_executed_pointer(direction).get_value::<u128>()
+    }
+
+    // proceeds of the order since they were last paid out, and the reward factor they run up to
+    fn _proceeds(&self, order: &LongTermOrder, settled: u128) -> Result<(u128, U256)> {
+        let reward_factor: U256 = if settled >= order.expiry {
+            self.reward_factor_at_pointer(order.direction, order.expiry)
+                .get_value::<StorableU256>()
+                .into()
+        } else {
+            self.reward_factor_pointer(order.direction)
+                .get_value::<StorableU256>()
+                .into()
+        };
+        let proceeds =
+            UQ128x128(reward_factor - order.reward_factor_last).mul_decode(order.sale_rate)?;
+        Ok((proceeds, reward_factor))
+    }
+
+    fn _tokens_of(&self, direction: u128) -> Result<(AlkaneId, AlkaneId)> {
+        let (token_0, token_1) = self.alkanes_for_self()?;
+        Ok(if direction == 0 {
+            (token_0, token_1)
+        } else {
+            (token_1, token_0)
+        })
+    }
+
+    fn _pay(&self, response: &mut CallResponse, id: AlkaneId, value: u128) {
+        if value > 0 {
+            response.alkanes.pay(AlkaneTransfer { id, value });
+        }
+    }
+
+    fn _incoming_order_id(&self) -> Result<AlkaneId> {
+        let context = self.context()?;
+        if context.incoming_alkanes.0.len() != 1 || context.incoming_alkanes.0[0].value == 0 {
+            return Err(anyhow!("must send exactly one order token"));
+        }
+        Ok(context.incoming_alkanes.0[0].id)
+    }
+
+    fn execute_virtual_orders(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        for direction in 0..2 {
+            response
+                .data
+                .extend_from_slice(&self._settle(direction).to_le_bytes());
+        }
+        Ok(response)
+    }
+
+    fn place_order(&self, num_intervals: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        if context.incoming_alkanes.0.len() != 1 {
+            return Err(anyhow!("must send exactly one pool token to sell"));
+        }
+        let incoming = context.incoming_alkanes.0[0].clone();
+        let (token_0, token_1) = self.alkanes_for_self()?;
+        let direction = if incoming.id == token_0 {
+            0
+        } else if incoming.id == token_1 {
+            1
+        } else {
+            return Err(anyhow!("unsupported alkane sent to twamm"));
+        };
+        // the order starts selling now, so its direction has to be settled up to now
+        self._execute_virtual_orders_for(direction)?;
+        if num_intervals == 0 {
+            return Err(anyhow!("order must run for at least one interval"));
+        }
+        let now = self.height() as u128;
+        let expiry = (now / ORDER_BLOCK_INTERVAL)
+            .checked_add(num_intervals)
+            .and_then(|v| v.checked_mul(ORDER_BLOCK_INTERVAL))
+            .ok_or_else(|| anyhow!("order expiry overflows"))?;
+        let sale_rate = incoming.value / (expiry - now);
+        if sale_rate == 0 {
+            return Err(anyhow!("order too small for its duration"));
+        }
+
+        let mut sale_rate_pointer = self.sale_rate_pointer(direction);
+        let total_sale_rate = sale_rate_pointer.get_value::<u128>();
+        sale_rate_pointer.set_value::<u128>(total_sale_rate + sale_rate);
+        let mut ending_pointer = self.sale_rate_ending_pointer(direction, expiry);
+        let ending = ending_pointer.get_value::<u128>();
+        ending_pointer.set_value::<u128>(ending + sale_rate);
+
+        let order_token = create_ownership_token(self)?;
+        self.set_order(
+            &order_token.id,
+            &LongTermOrder {
+                direction,
+                sale_rate,
+                expiry,
+                reward_factor_last: self
+                    .reward_factor_pointer(direction)
+                    .get_value::<StorableU256>()
+                    .into(),
+            },
+        );
+        let mut response = CallResponse::default();
+        response.alkanes.pay(order_token);
+        self._pay(
+            &mut response,
+            incoming.id,
+            incoming.value - sale_rate * (expiry - now),
+        );
+        Ok(response)
+    }
+
+    fn withdraw_proceeds(&self) -> Result<CallResponse> {
+        let order_id = self._incoming_order_id()?;
+        let mut order = self.order(&order_id)?;
+        let settled = self._settle(order.direction);
+        let (_, token_out) = self._tokens_of(order.direction)?;
+        let (proceeds, reward_factor) = self._proceeds(&order, settled)?;
+
+        let mut response = CallResponse::default();
+        if settled >= order.expiry {
+            // the order token stays with the contract once the order is done
+            self.order_pointer(&order_id).set(Arc::new(vec![]));
+        } else {
+            order.reward_factor_last = reward_factor;
+            self.set_order(&order_id, &order);
+            response.alkanes.pay(AlkaneTransfer {
+                id: order_id,
+                value: 1,
+            });
+        }
+        self._pay(&mut response, token_out, proceeds);
+        Ok(response)
+    }
+
+    fn cancel_order(&self) -> Result<CallResponse> {
+        let order_id = self._incoming_order_id()?;
+        let order = self.order(&order_id)?;
+        // what has not been settled yet has not been sold either, so it is refunded as well
+        let settled = self._settle(order.direction);
+        if settled >= order.expiry {
+            return Err(anyhow!("order has expired, withdraw its proceeds instead"));
+        }
+        let (token_in, token_out) = self._tokens_of(order.direction)?;
+        let (proceeds, _) = self._proceeds(&order, settled)?;
+
+        let mut sale_rate_pointer = self.sale_rate_pointer(order.direction);
+        let total_sale_rate = sale_rate_pointer.get_value::<u128>();
+        sale_rate_pointer.set_value::<u128>(total_sale_rate - order.sale_rate);
+        let mut ending_pointer = self.sale_rate_ending_pointer(order.direction, order.expiry);
+        let ending = ending_pointer.get_value::<u128>();
+        ending_pointer.set_value::<u128>(ending - order.sale_rate);
+        self.order_pointer(&order_id).set(Arc::new(vec![]));
+
+        let mut response = CallResponse::default();
+        self._pay(
+            &mut response,
+            token_in,
+            order.sale_rate * (order.expiry - settled),
+        );
+        self._pay(&mut response, token_out, proceeds);
+        Ok(response)
+    }
+
+    fn get_order(&self, order_id: AlkaneId) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = self.order(&order_id)?.try_to_vec();
+        Ok(response)
+    }
+}
+
+impl AlkaneResponder for Twamm {}
+
+declare_alkane! {
+    impl AlkaneResponder for Twamm {
+        type Message = TwammMessage;
+    }
+}
@@ -0,0 +1,244 @@
+use alkanes_runtime::{
+    declare_alkane, message::MessageDispatch, runtime::AlkaneResponder, storage::StoragePointer,
+};
+#[allow(unused_imports)]
+use alkanes_runtime::{
+    println,
+    stdio::{stdout, Write},
+};
+use alkanes_runtime_pool::AMMPoolBase;
+use alkanes_std_factory_support::MintableToken;
+use alkanes_support::{id::AlkaneId, response::CallResponse};
+use anyhow::{anyhow, Result};
+use metashrew_support::{
+    compat::{to_arraybuffer_layout, to_passback_ptr},
+    index_pointer::KeyValuePointer,
+};
+use oylswap_library::{fixed_point::UQ128x128, weighted, U256};
+
+#[derive(MessageDispatch)]
+pub enum WeightedPoolMessage {
+    #[opcode(0)]
+    InitPool {
+        alkane_a: AlkaneId,
+        alkane_b: AlkaneId,
+        factory: AlkaneId,
+        weight_0: u128,
+        weight_1: u128,
+    },
+
+    #[opcode(1)]
+    AddLiquidity,
+
+    #[opcode(2)]
+    WithdrawAndBurn,
+
+    // this low level function should generally not be called directly unless the user is experienced with alkanes contracts
+    #[opcode(3)]
+    Swap {
+        amount_0_out: u128,
+        amount_1_out: u128,
+        to: AlkaneId,
+        data: Vec<u128>,
+    },
+
+    // lends the amounts to `receiver`, which must pay them back plus the flash fee from its callback
+    #[opcode(4)]
+    FlashLoan {
+        amount_0: u128,
+        amount_1: u128,
+        receiver: AlkaneId,
+        data: Vec<u128>,
+    },
+
+    #[opcode(10)]
+    CollectFees {},
+
+    #[opcode(20)]
+    #[returns(u128)]
+    GetTotalFee {},
+
+    #[opcode(21)]
+    SetTotalFee { total_fee_per_1000: u128 },
+
+    #[opcode(23)]
+    #[returns(u128, u128)]
+    GetWeights,
+
+    #[opcode(24)]
+    #[returns(u128)]
+    GetProtocolFee,
+
+    #[opcode(25)]
+    SetProtocolFee { protocol_fee_per_1000: u128 },
+
+    #[opcode(26)]
+    Skim { to: AlkaneId },
+
+    #[opcode(27)]
+    Sync,
+
+    #[opcode(28)]
+    #[returns(u128)]
+    GetFlashFee,
+
+    #[opcode(29)]
+    SetFlashFee { flash_fee_per_1000: u128 },
+
+    #[opcode(30)]
+    IncreaseObservationCardinalityNext { observation_cardinality_next: u128 },
+
+    #[opcode(31)]
+    #[returns(Vec<u8>)]
+    Consult { seconds_ago: Vec<u128> },
+
+    #[opcode(32)]
+    #[returns(u128, u128)]
+    GetOracleClock,
+
+    #[opcode(33)]
+    #[returns(u128)]
+    GetPaused,
+
+    #[opcode(34)]
+    SetPaused { paused: u128 },
+
+    #[opcode(35)]
+    #[returns(Vec<u8>)]
+    GetStats,
+
+    #[opcode(36)]
+    #[returns(Vec<u8>)]
+    GetFeeGrowthPerLp,
+
+    // the total fee at the resolution fee tiers use, GetTotalFee rounds it down to per 1000.
+    // Weighted pools are only created in the default fee tier, so there is no SetFeesPerMillion
+    #[opcode(37)]
+    #[returns(u128)]
+    GetTotalFeePerMillion,
+
+    #[opcode(50)]
+    ForwardIncoming,
+
+    #[opcode(97)]
+    #[returns(u128, u128)]
+    GetReserves,
+
+    #[opcode(98)]
+    #[returns(u128, u128)]
+    GetPriceCumulativeLast,
+
+    #[opcode(99)]
+    #[returns(String)]
+    GetName,
+
+    #[opcode(999)]
+    #[returns(Vec<u8>)]
+    PoolDetails,
+}
+
+// AMM pool where each token has its own weight in the pool value, e.g. 80/20, and swaps keep
+// the weighted product balance_0^weight_0 * balance_1^weight_1 from decreasing. Liquidity, fees
+// and the protocol fee work exactly like the regular pool.
+#[derive(Default)]
+pub struct WeightedPool();
+
+impl WeightedPool {
+    fn weight_pointers(&self) -> (StoragePointer, StoragePointer) {
+        (
+            StoragePointer::from_keyword("/weight/0"),
+            StoragePointer::from_keyword("/weight/1"),
+        )
+    }
+
+    fn weights(&self) -> (u128, u128) {
+        let (w0, w1) = self.weight_pointers();
+        (w0.get_value::<u128>(), w1.get_value::<u128>())
+    }
+
+    // weights are given per 1000 in the order of the sorted alkanes
+    fn init_pool(
+        &self,
+        alkane_a: AlkaneId,
+        alkane_b: AlkaneId,
+        factory: AlkaneId,
+        weight_0: u128,
+        weight_1: u128,
+    ) -> Result<CallResponse> {
+        weighted::check_weights(weight_0, weight_1)?;
+        let (mut w0, mut w1) = self.weight_pointers();
+        w0.set_value::<u128>(weight_0);
+        w1.set_value::<u128>(weight_1);
+        AMMPoolBase::init_pool(self, alkane_a, alkane_b, factory)
+    }
+
+    fn get_weights(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        let (weight_0, weight_1) = self.weights();
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&weight_0.to_le_bytes());
+        bytes.extend_from_slice(&weight_1.to_le_bytes());
+        response.data = bytes;
+        Ok(response)
+    }
+}
+
+impl MintableToken for WeightedPool {}
+impl AMMPoolBase for WeightedPool {
+    // V is linear in the reserves, so V^2 plays the role of the constant product k
+    fn _k(&self, reserve_a: u128, reserve_b: u128) -> Result<U256> {
+        let (weight_0, weight_1) = self.weights();
+        let v = weighted::get_invariant(reserve_a, reserve_b, weight_0, weight_1)?;
+        v.checked_mul(v).ok_or_else(|| anyhow!("math overflow"))
+    }
+
+    fn _check_invariant(
+        &self,
+        balance_0: u128,
+        balance_1: u128,
+        amount_0_in: u128,
+        amount_1_in: u128,
+        reserve_0: u128,
+        reserve_1: u128,
+    ) -> Result<()> {
+        let (weight_0, weight_1) = self.weights();
+        let v_after = weighted::get_log_invariant_fee_adjusted(
+            balance_0,
+            balance_1,
+            amount_0_in,
+            amount_1_in,
+            self.total_fee_per_1000(),
+            weight_0,
+            weight_1,
+        )?;
+        let v_before = weighted::get_log_invariant_fee_adjusted(
+            reserve_0, reserve_1, 0, 0, 0, weight_0, weight_1,
+        )?;
+        if v_after < v_before {
+            return Err(anyhow!("V is not increasing"));
+        }
+        Ok(())
+    }
+
+    // the marginal price of token 0 is (reserve_1 / weight_1) / (reserve_0 / weight_0)
+    fn _prices(&self, reserve0: u128, reserve1: u128) -> Result<(UQ128x128, UQ128x128)> {
+        if reserve0 == 0 || reserve1 == 0 {
+            return Ok((UQ128x128::default(), UQ128x128::default()));
+        }
+        let (weight_0, weight_1) = self.weights();
+        let value_0 = U256::from(reserve0) * U256::from(weight_1);
+        let value_1 = U256::from(reserve1) * U256::from(weight_0);
+        Ok((
+            UQ128x128::ratio(value_1, value_0)?,
+            UQ128x128::ratio(value_0, value_1)?,
+        ))
+    }
+}
+
+impl AlkaneResponder for WeightedPool {}
+declare_alkane! {
+    impl AlkaneResponder for WeightedPool {
+        type Message = WeightedPoolMessage;
+    }
+}
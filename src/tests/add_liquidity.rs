@@ -8,7 +8,10 @@ use bitcoin::Witness;
 use init_pools::{calc_lp_balance_from_pool_init, test_amm_pool_init_fixture};
 use metashrew_support::byte_view::ByteView;
 use num::integer::Roots;
-use oylswap_library::{StorableU256, U256};
+use oylswap_library::{
+    get_amount_out, get_zap_swap_amount, StorableU256, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000, U256,
+};
 use protorune::test_helpers::create_block_with_coinbase_tx;
 use protorune_support::protostone::ProtostoneEdict;
 use remove_liquidity::test_amm_burn_fixture;
@@ -17,7 +20,7 @@ use swap::{
     insert_swap_exact_tokens_for_tokens_deadline,
 };
 
-use crate::tests::helper::add_liquidity::insert_add_liquidity_checked_txs;
+use crate::tests::helper::add_liquidity::{insert_add_liquidity_checked_txs, insert_zap_in_txs};
 use crate::tests::helper::*;
 use alkane_helpers::clear;
 use alkanes::indexer::index_block;
@@ -97,6 +100,7 @@ fn test_amm_pool_add_more_liquidity_checked() -> Result<()> {
     insert_add_liquidity_checked_txs(
         deployment_ids.owned_token_1_deployment,
         deployment_ids.owned_token_2_deployment,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
         amount1,
         amount2,
         amount1,
@@ -147,6 +151,7 @@ fn test_amm_pool_add_more_liquidity_checked_ordering() -> Result<()> {
     insert_add_liquidity_checked_txs(
         deployment_ids.owned_token_2_deployment,
         deployment_ids.owned_token_1_deployment,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
         amount2,
         amount1,
         amount2,
@@ -236,6 +241,7 @@ fn test_amm_pool_add_more_liquidity_one_sided_checked_1() -> Result<()> {
     insert_add_liquidity_checked_txs(
         deployment_ids.owned_token_1_deployment,
         deployment_ids.owned_token_2_deployment,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
         amount1 / 2,
         amount2,
         amount1,
@@ -285,6 +291,7 @@ fn test_amm_pool_add_more_liquidity_one_sided_checked_2() -> Result<()> {
     insert_add_liquidity_checked_txs(
         deployment_ids.owned_token_1_deployment,
         deployment_ids.owned_token_2_deployment,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
         amount1,
         amount2 / 2,
         amount1 / 2,
@@ -332,6 +339,7 @@ fn test_amm_pool_add_more_liquidity_one_sided_checked_1_fail() -> Result<()> {
     insert_add_liquidity_checked_txs(
         deployment_ids.owned_token_1_deployment,
         deployment_ids.owned_token_2_deployment,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
         amount1 / 2,
         amount2,
         amount1,
@@ -368,6 +376,7 @@ fn test_amm_pool_add_more_liquidity_one_sided_checked_2_fail() -> Result<()> {
     insert_add_liquidity_checked_txs(
         deployment_ids.owned_token_1_deployment,
         deployment_ids.owned_token_2_deployment,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
         amount1,
         amount2 / 2,
         amount1,
@@ -435,3 +444,87 @@ fn test_amm_pool_add_more_liquidity_to_wrong_pool() -> Result<()> {
     )?;
     Ok(())
 }
+
+#[wasm_bindgen_test]
+fn test_amm_pool_zap_in() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (1000000, 1000000);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let block_height = 840_001;
+    let mut zap_block = create_block_with_coinbase_tx(block_height);
+    let amount_in = 10000;
+    insert_zap_in_txs(
+        amount_in,
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
+        0,
+        &mut zap_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&zap_block, block_height)?;
+
+    // half of the input is swapped, the rest is deposited at the post swap ratio
+    let swap_amount = get_zap_swap_amount(amount_in, amount1, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000)?;
+    let amount_out = get_amount_out(
+        swap_amount,
+        amount1,
+        amount2,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+    let (reserve1, reserve2) = (amount1 + swap_amount, amount2 - amount_out);
+    let remaining = amount_in - swap_amount;
+    let (deposit1, deposit2) = if remaining * reserve2 / reserve1 <= amount_out {
+        (remaining, remaining * reserve2 / reserve1)
+    } else {
+        (amount_out * reserve1 / reserve2, amount_out)
+    };
+
+    let sheet = get_last_outpoint_sheet(&zap_block)?;
+    assert!(sheet.get_cached(&deployment_ids.amm_pool_1_deployment.into()) > 0);
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_1_deployment.into()),
+        remaining - deposit1
+    );
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_2_deployment.into()),
+        amount_out - deposit2
+    );
+    // the dust left over from rounding is tiny compared to the input
+    assert!(remaining - deposit1 + amount_out - deposit2 < amount_in / 1000);
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_zap_in_min_lp_out_fail() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let block_height = 840_001;
+    let mut zap_block = create_block_with_coinbase_tx(block_height);
+    insert_zap_in_txs(
+        10000,
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
+        10000,
+        &mut zap_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&zap_block, block_height)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: zap_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        "ALKANES: revert: Error: INSUFFICIENT_LP_AMOUNT",
+    )?;
+    Ok(())
+}
@@ -5,10 +5,15 @@ use alkanes_support::trace::{Trace, TraceEvent};
 use anyhow::Result;
 use bitcoin::blockdata::transaction::OutPoint;
 use bitcoin::Witness;
-use init_pools::{calc_lp_balance_from_pool_init, test_amm_pool_init_fixture};
+use init_pools::{
+    calc_lp_balance_from_pool_init, test_amm_pool_init_fixture, INIT_AMT_TOKEN1, INIT_AMT_TOKEN2,
+};
 use metashrew_support::byte_view::ByteView;
 use num::integer::Roots;
-use oylswap_library::{StorableU256, U256};
+use oylswap_library::{
+    get_amount_out, StorableU256, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000, U256,
+};
 use protorune::test_helpers::create_block_with_coinbase_tx;
 use protorune_support::protostone::ProtostoneEdict;
 use remove_liquidity::test_amm_burn_fixture;
@@ -16,7 +21,8 @@ use remove_liquidity::test_amm_burn_fixture;
 use crate::tests::helper::add_liquidity::insert_add_liquidity_checked_txs;
 use crate::tests::helper::remove_liquidity::{
     check_burn_balances, check_remove_liquidity_runtime_balance,
-    insert_remove_liquidity_checked_txs,
+    insert_remove_liquidity_checked_to_txs, insert_remove_liquidity_checked_txs,
+    insert_zap_out_txs,
 };
 use crate::tests::helper::*;
 use alkane_helpers::clear;
@@ -79,6 +85,7 @@ fn test_amm_pool_checked() -> Result<()> {
     insert_remove_liquidity_checked_txs(
         deployment_ids.owned_token_1_deployment,
         deployment_ids.owned_token_2_deployment,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
         amount_burn,
         (amount1 - MINIMUM_LIQUIDITY) / 2,
         (amount2 - MINIMUM_LIQUIDITY) / 2,
@@ -130,6 +137,7 @@ fn test_amm_pool_checked_fail_1() -> Result<()> {
     insert_remove_liquidity_checked_txs(
         deployment_ids.owned_token_1_deployment,
         deployment_ids.owned_token_2_deployment,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
         amount_burn,
         (amount1 - MINIMUM_LIQUIDITY) / 2 + 1,
         (amount2 - MINIMUM_LIQUIDITY) / 2,
@@ -170,6 +178,7 @@ fn test_amm_pool_checked_fail_2() -> Result<()> {
     insert_remove_liquidity_checked_txs(
         deployment_ids.owned_token_2_deployment,
         deployment_ids.owned_token_1_deployment,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
         amount_burn,
         (amount2 - MINIMUM_LIQUIDITY) / 2,
         (amount1 - MINIMUM_LIQUIDITY) / 2 + 1,
@@ -210,6 +219,7 @@ fn test_amm_pool_checked_fail_3() -> Result<()> {
     insert_remove_liquidity_checked_txs(
         deployment_ids.owned_token_1_deployment,
         deployment_ids.owned_token_2_deployment,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
         amount_burn,
         (amount1 - MINIMUM_LIQUIDITY) / 2,
         (amount2 - MINIMUM_LIQUIDITY) / 2 + 1,
@@ -230,3 +240,142 @@ fn test_amm_pool_checked_fail_3() -> Result<()> {
     )?;
     Ok(())
 }
+
+#[wasm_bindgen_test]
+fn test_amm_pool_zap_out() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (1000000, 1000000);
+    let total_lp = calc_lp_balance_from_pool_init(amount1, amount2);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let block_height = 840_001;
+    let mut test_block = create_block_with_coinbase_tx(block_height);
+    let amount_burn = total_lp / 2;
+    insert_zap_out_txs(
+        amount_burn,
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
+        0,
+        &mut test_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&test_block, block_height)?;
+
+    // the token 2 share of the burn is sold into the pool that is left
+    let total_supply = (amount1 * amount2).sqrt();
+    let (burned1, burned2) = (
+        amount_burn * amount1 / total_supply,
+        amount_burn * amount2 / total_supply,
+    );
+    let amount_swapped = get_amount_out(
+        burned2,
+        amount1 - burned1,
+        amount2 - burned2,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+    let sheet = get_last_outpoint_sheet(&test_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_1_deployment.into()),
+        burned1 + amount_swapped
+    );
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_2_deployment.into()),
+        0
+    );
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.amm_pool_1_deployment.into()),
+        0
+    );
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_zap_out_amount_out_min_fail() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (1000000, 1000000);
+    let total_lp = calc_lp_balance_from_pool_init(amount1, amount2);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let block_height = 840_001;
+    let mut test_block = create_block_with_coinbase_tx(block_height);
+    let amount_burn = total_lp / 2;
+    // without any price impact or fees the zap would return twice the token 1 share
+    insert_zap_out_txs(
+        amount_burn,
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
+        2 * amount_burn,
+        &mut test_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&test_block, block_height)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: test_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        "ALKANES: revert: Error: INSUFFICIENT_OUTPUT_AMOUNT",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_checked_burn_to_recipient() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (1000000, 1000000);
+    let total_lp = calc_lp_balance_from_pool_init(amount1, amount2);
+    let amount_burn = total_lp / 2;
+    let (init_block, mut runtime_balances, deployment_ids) =
+        test_amm_pool_init_fixture(amount1, amount2)?;
+    let block_height = 840_001;
+    let mut test_block = create_block_with_coinbase_tx(block_height);
+    let deadline = test_block.header.time as u128;
+    insert_remove_liquidity_checked_to_txs(
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000,
+        amount_burn,
+        0,
+        0,
+        deployment_ids.example_flashswap,
+        deadline,
+        &mut test_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&test_block, block_height)?;
+
+    // the unburned lp tokens come back, the withdrawn tokens stay with the recipient contract
+    let sheet = get_last_outpoint_sheet(&test_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.amm_pool_1_deployment.into()),
+        total_lp - amount_burn
+    );
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_1_deployment.into()),
+        INIT_AMT_TOKEN1 - amount1
+    );
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_2_deployment.into()),
+        INIT_AMT_TOKEN2 - 2 * amount2
+    );
+    check_remove_liquidity_runtime_balance(
+        &mut runtime_balances,
+        0,
+        0,
+        amount_burn,
+        &deployment_ids,
+    )?;
+    Ok(())
+}
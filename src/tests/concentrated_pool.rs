@@ -0,0 +1,423 @@
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::{
+    self as alkane_helpers, assert_id_points_to_alkane_id, assert_revert_context,
+    get_last_outpoint_sheet,
+};
+use alkanes::vm::utils::sequence_pointer;
+use alkanes_support::id::AlkaneId;
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use metashrew_core::index_pointer::AtomicPointer;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use metashrew_support::index_pointer::KeyValuePointer;
+use oylswap_library::concentrated::{
+    compute_swap_step, encode_price_sqrt, get_amount_0_delta, get_amount_1_delta,
+    get_liquidity_for_amounts, get_sqrt_ratio_at_tick, MAX_TICK, MIN_TICK,
+};
+use oylswap_library::U256;
+use protorune::test_helpers::create_block_with_coinbase_tx;
+use protorune_support::balance_sheet::BalanceSheetOperations;
+use std::fmt::Write;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use crate::tests::helper::common::{create_deployment_ids, insert_pool_call_txs};
+use crate::tests::helper::concentrated_pool::*;
+use crate::tests::helper::init_pools::{init_factories, init_factory_proxy};
+use alkane_helpers::clear;
+
+#[wasm_bindgen_test]
+fn test_concentrated_pool_init() -> Result<()> {
+    clear();
+    let (_, deployment_ids, pool) = test_concentrated_pool_init_fixture()?;
+    assert_id_points_to_alkane_id(pool, deployment_ids.pool_beacon_proxy)?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_concentrated_pool_init_duplicate_fail() -> Result<()> {
+    clear();
+    let (pool_block, deployment_ids, _) = test_concentrated_pool_init_fixture()?;
+    let (duplicate_block, _) = create_concentrated_pool_txs(
+        deployment_ids.owned_token_3_deployment,
+        deployment_ids.owned_token_1_deployment,
+        1,
+        1,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    assert_revert_context(
+        &OutPoint {
+            txid: duplicate_block.txdata.last().unwrap().compute_txid(),
+            vout: 3,
+        },
+        "ALKANES: revert: Error: pool already exists",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_concentrated_pool_without_template_fail() -> Result<()> {
+    clear();
+    let mut deployment_ids = create_deployment_ids();
+    let init_block = init_factories(&deployment_ids)?;
+    let proxy_block = init_factory_proxy(
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &mut deployment_ids,
+    )?;
+    let (pool_block, _) = create_concentrated_pool_txs(
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_3_deployment,
+        1,
+        1,
+        OutPoint {
+            txid: proxy_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    assert_revert_context(
+        &OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 3,
+        },
+        "ALKANES: revert: Error: pool template 1 is not registered",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_concentrated_pool_mint_swap_burn() -> Result<()> {
+    clear();
+    let (pool_block, deployment_ids, pool) = test_concentrated_pool_init_fixture()?;
+    let token_0 = deployment_ids.owned_token_1_deployment;
+    let token_1 = deployment_ids.owned_token_3_deployment;
+    let (tick_lower, tick_upper) = (-600, 600);
+    let amount_desired = 1_000_000;
+
+    let block_height = 840_002;
+    let mut mint_block = create_block_with_coinbase_tx(block_height);
+    let position = AlkaneId {
+        block: 2,
+        tx: sequence_pointer(&mut AtomicPointer::default()).get_value::<u128>(),
+    };
+    insert_mint_position_txs(
+        vec![(token_0, amount_desired), (token_1, amount_desired)],
+        tick_lower,
+        tick_upper,
+        &mut mint_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        pool,
+    );
+    index_block(&mint_block, block_height)?;
+
+    let sqrt_price = encode_price_sqrt(1, 1)?;
+    let sqrt_lower = get_sqrt_ratio_at_tick(tick_lower)?;
+    let sqrt_upper = get_sqrt_ratio_at_tick(tick_upper)?;
+    let liquidity = get_liquidity_for_amounts(
+        sqrt_price,
+        sqrt_lower,
+        sqrt_upper,
+        amount_desired,
+        amount_desired,
+    )?;
+    let amount_0: u128 = get_amount_0_delta(sqrt_price, sqrt_upper, liquidity, true)?.try_into()?;
+    let amount_1: u128 = get_amount_1_delta(sqrt_lower, sqrt_price, liquidity, true)?.try_into()?;
+
+    let mint_sheet = get_last_outpoint_sheet(&mint_block)?;
+    assert_eq!(mint_sheet.get_cached(&position.into()), 1);
+    assert_eq!(
+        mint_sheet.get_cached(&token_0.into()),
+        amount_desired - amount_0
+    );
+    assert_eq!(
+        mint_sheet.get_cached(&token_1.into()),
+        amount_desired - amount_1
+    );
+
+    // swap token 0 for token 1, which stays within the position range
+    let block_height = 840_003;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    let amount_in = 10_000;
+    insert_concentrated_swap_exact_input_txs(
+        token_0,
+        amount_in,
+        0,
+        MIN_TICK,
+        0,
+        &mut swap_block,
+        OutPoint {
+            txid: mint_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+        pool,
+    );
+    index_block(&swap_block, block_height)?;
+
+    let step = compute_swap_step(
+        sqrt_price,
+        sqrt_lower,
+        liquidity,
+        U256::from(amount_in),
+        true,
+        CL_FEE_PER_1000000,
+    )?;
+    let amount_out: u128 = step.amount_out.try_into()?;
+    let swap_sheet = get_last_outpoint_sheet(&swap_block)?;
+    assert_eq!(swap_sheet.get_cached(&token_1.into()), amount_out);
+    assert_eq!(swap_sheet.get_cached(&token_0.into()), 0);
+
+    // burning the whole position returns the principal plus the swap input, minus rounding
+    let block_height = 840_004;
+    let mut burn_block = create_block_with_coinbase_tx(block_height);
+    insert_concentrated_burn_txs(
+        position,
+        liquidity,
+        &mut burn_block,
+        OutPoint {
+            txid: mint_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        pool,
+    );
+    index_block(&burn_block, block_height)?;
+
+    let burn_sheet = get_last_outpoint_sheet(&burn_block)?;
+    let received_0 = burn_sheet.get_cached(&token_0.into());
+    let received_1 = burn_sheet.get_cached(&token_1.into());
+    println!("burn received {} {}", received_0, received_1);
+    assert_eq!(burn_sheet.get_cached(&position.into()), 1);
+    assert!(received_0 <= amount_0 + amount_in && received_0 + 2 >= amount_0 + amount_in);
+    assert!(received_1 <= amount_1 - amount_out && received_1 + 2 >= amount_1 - amount_out);
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_concentrated_pool_swap_exact_output() -> Result<()> {
+    clear();
+    let (pool_block, deployment_ids, pool) = test_concentrated_pool_init_fixture()?;
+    let token_0 = deployment_ids.owned_token_1_deployment;
+    let token_1 = deployment_ids.owned_token_3_deployment;
+
+    let block_height = 840_002;
+    let mut mint_block = create_block_with_coinbase_tx(block_height);
+    insert_mint_position_txs(
+        vec![(token_0, 1_000_000), (token_1, 1_000_000)],
+        -600,
+        600,
+        &mut mint_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        pool,
+    );
+    index_block(&mint_block, block_height)?;
+
+    let block_height = 840_003;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    let (amount_in_max, amount_out) = (10_000, 5_000);
+    insert_concentrated_swap_exact_output_txs(
+        token_1,
+        amount_in_max,
+        amount_out,
+        MAX_TICK,
+        0,
+        &mut swap_block,
+        OutPoint {
+            txid: mint_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+        pool,
+    );
+    index_block(&swap_block, block_height)?;
+
+    let swap_sheet = get_last_outpoint_sheet(&swap_block)?;
+    assert_eq!(swap_sheet.get_cached(&token_0.into()), amount_out);
+    // the unused input is refunded, and at least the fee was charged on top of the output
+    let amount_in = amount_in_max - swap_sheet.get_cached(&token_1.into());
+    assert!(amount_in > amount_out + amount_out * CL_FEE_PER_1000000 / 1_000_000);
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_concentrated_pool_swap_stops_at_price_limit() -> Result<()> {
+    clear();
+    let (pool_block, deployment_ids, pool) = test_concentrated_pool_init_fixture()?;
+    let token_0 = deployment_ids.owned_token_1_deployment;
+    let token_1 = deployment_ids.owned_token_3_deployment;
+    let (tick_lower, tick_upper) = (-600, 600);
+    let amount_desired = 1_000_000;
+
+    let block_height = 840_002;
+    let mut mint_block = create_block_with_coinbase_tx(block_height);
+    insert_mint_position_txs(
+        vec![(token_0, amount_desired), (token_1, amount_desired)],
+        tick_lower,
+        tick_upper,
+        &mut mint_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        pool,
+    );
+    index_block(&mint_block, block_height)?;
+
+    // the input would move the price well past tick -20
+    let block_height = 840_003;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    let (amount_in, tick_limit) = (100_000, -20);
+    insert_concentrated_swap_exact_input_txs(
+        token_0,
+        amount_in,
+        0,
+        tick_limit,
+        0,
+        &mut swap_block,
+        OutPoint {
+            txid: mint_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+        pool,
+    );
+    index_block(&swap_block, block_height)?;
+
+    let sqrt_price = encode_price_sqrt(1, 1)?;
+    let liquidity = get_liquidity_for_amounts(
+        sqrt_price,
+        get_sqrt_ratio_at_tick(tick_lower)?,
+        get_sqrt_ratio_at_tick(tick_upper)?,
+        amount_desired,
+        amount_desired,
+    )?;
+    let step = compute_swap_step(
+        sqrt_price,
+        get_sqrt_ratio_at_tick(tick_limit)?,
+        liquidity,
+        U256::from(amount_in),
+        true,
+        CL_FEE_PER_1000000,
+    )?;
+    let used: u128 = (step.amount_in + step.fee_amount).try_into()?;
+    assert!(used < amount_in);
+    // the input the swap did not use is refunded
+    let swap_sheet = get_last_outpoint_sheet(&swap_block)?;
+    assert_eq!(
+        swap_sheet.get_cached(&token_1.into()),
+        u128::try_from(step.amount_out)?
+    );
+    assert_eq!(swap_sheet.get_cached(&token_0.into()), amount_in - used);
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_concentrated_pool_swap_expired_deadline_fail() -> Result<()> {
+    clear();
+    let (pool_block, deployment_ids, pool) = test_concentrated_pool_init_fixture()?;
+    let block_height = 840_002;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    insert_concentrated_swap_exact_input_txs(
+        deployment_ids.owned_token_1_deployment,
+        10_000,
+        0,
+        MIN_TICK,
+        block_height as u128 - 1,
+        &mut swap_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        pool,
+    );
+    index_block(&swap_block, block_height)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: swap_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        "ALKANES: revert: Error: EXPIRED deadline: block height (840002) > deadline(840001)",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_concentrated_pool_paused_swap_fail() -> Result<()> {
+    clear();
+    let (pool_block, deployment_ids, pool) = test_concentrated_pool_init_fixture()?;
+    let block_height = 840_002;
+    let mut test_block = create_block_with_coinbase_tx(block_height);
+    insert_pool_call_txs(
+        deployment_ids.amm_factory_proxy,
+        vec![24, pool.block, pool.tx, 1],
+        &mut test_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    let input_outpoint = OutPoint {
+        txid: test_block.txdata.last().unwrap().compute_txid(),
+        vout: 0,
+    };
+    insert_concentrated_swap_exact_input_txs(
+        deployment_ids.owned_token_1_deployment,
+        10_000,
+        0,
+        MIN_TICK,
+        0,
+        &mut test_block,
+        input_outpoint,
+        pool,
+    );
+    index_block(&test_block, block_height)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: test_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        "ALKANES: revert: Error: PAUSED",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_concentrated_pool_mint_invalid_ticks_fail() -> Result<()> {
+    clear();
+    let (pool_block, deployment_ids, pool) = test_concentrated_pool_init_fixture()?;
+    let block_height = 840_002;
+    let mut mint_block = create_block_with_coinbase_tx(block_height);
+    // ticks must be multiples of the tick spacing
+    insert_mint_position_txs(
+        vec![
+            (deployment_ids.owned_token_1_deployment, 1_000_000),
+            (deployment_ids.owned_token_3_deployment, 1_000_000),
+        ],
+        -600,
+        610,
+        &mut mint_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        pool,
+    );
+    index_block(&mint_block, block_height)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: mint_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        "ALKANES: revert: Error: ticks must be multiples of the tick spacing",
+    )?;
+    Ok(())
+}
@@ -0,0 +1,311 @@
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::{
+    self as alkane_helpers, assert_id_points_to_alkane_id, assert_revert_context,
+    get_last_outpoint_sheet,
+};
+use alkanes_support::id::AlkaneId;
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use init_pools::test_amm_pool_init_fixture;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use oylswap_library::{get_amount_out_per_1000000, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000};
+use protorune::test_helpers::create_block_with_coinbase_tx;
+use std::fmt::Write;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use crate::tests::helper::add_liquidity::insert_add_liquidity_checked_txs;
+use crate::tests::helper::common::{get_last_return_data, insert_pool_call_txs};
+use crate::tests::helper::fee_tiers::{create_pool_with_fee_tier_txs, set_fee_tier_txs};
+use crate::tests::helper::swap::insert_swap_exact_tokens_for_tokens;
+use crate::tests::helper::*;
+use alkane_helpers::clear;
+
+#[wasm_bindgen_test]
+fn test_create_pool_in_fee_tier() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    // 0.05%, below the protocol fee of the default tier
+    let fee_tier = 500;
+    let tier_block = set_fee_tier_txs(
+        fee_tier,
+        1,
+        840_001,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    // the pair already has a pool in the default tier
+    let (pool_block, pool) = create_pool_with_fee_tier_txs(
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        1000000,
+        1000000,
+        fee_tier,
+        840_002,
+        OutPoint {
+            txid: tier_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    assert_id_points_to_alkane_id(pool, deployment_ids.pool_beacon_proxy)?;
+    assert_ne!(pool, deployment_ids.amm_pool_1_deployment);
+
+    let mut find_block = create_block_with_coinbase_tx(840_003);
+    insert_pool_call_txs(
+        deployment_ids.amm_factory_proxy,
+        vec![
+            19,
+            deployment_ids.owned_token_2_deployment.block,
+            deployment_ids.owned_token_2_deployment.tx,
+            deployment_ids.owned_token_1_deployment.block,
+            deployment_ids.owned_token_1_deployment.tx,
+            fee_tier,
+        ],
+        &mut find_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&find_block, 840_003)?;
+    let found: AlkaneId = get_last_return_data(&find_block)?.try_into()?;
+    assert_eq!(found, pool);
+
+    let mut fee_block = create_block_with_coinbase_tx(840_004);
+    insert_pool_call_txs(
+        pool,
+        vec![37],
+        &mut fee_block,
+        OutPoint {
+            txid: find_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&fee_block, 840_004)?;
+    let data = get_last_return_data(&fee_block)?;
+    assert_eq!(u128::from_le_bytes(data[0..16].try_into()?), fee_tier);
+
+    let (duplicate_block, _) = create_pool_with_fee_tier_txs(
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        1000000,
+        1000000,
+        fee_tier,
+        840_005,
+        OutPoint {
+            txid: fee_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    assert_revert_context(
+        &OutPoint {
+            txid: duplicate_block.txdata.last().unwrap().compute_txid(),
+            vout: 3,
+        },
+        "ALKANES: revert: Error: pool already exists",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_create_pool_in_disabled_fee_tier_fails() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let (pool_block, _) = create_pool_with_fee_tier_txs(
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        1000000,
+        1000000,
+        5,
+        840_001,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    assert_revert_context(
+        &OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 3,
+        },
+        "ALKANES: revert: Error: fee tier 5 is not enabled",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_swap_routes_through_best_fee_tier() -> Result<()> {
+    clear();
+    let amount = 1000000;
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount, amount)?;
+    let fee_tier = 3000;
+    let tier_block = set_fee_tier_txs(
+        fee_tier,
+        1,
+        840_001,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    let (pool_block, _) = create_pool_with_fee_tier_txs(
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        amount,
+        amount,
+        fee_tier,
+        840_002,
+        OutPoint {
+            txid: tier_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+
+    let mut swap_block = create_block_with_coinbase_tx(840_003);
+    let amount_in = 10000;
+    insert_swap_exact_tokens_for_tokens(
+        amount_in,
+        vec![
+            deployment_ids.owned_token_1_deployment,
+            deployment_ids.owned_token_2_deployment,
+        ],
+        0,
+        &mut swap_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&swap_block, 840_003)?;
+
+    // both pools have the same reserves, so the cheaper tier pays out more
+    let expected = get_amount_out_per_1000000(amount_in, amount, amount, fee_tier)?;
+    assert!(
+        expected
+            > get_amount_out_per_1000000(
+                amount_in,
+                amount,
+                amount,
+                DEFAULT_TOTAL_FEE_AMOUNT_PER_1000000
+            )?
+    );
+    let sheet = get_last_outpoint_sheet(&swap_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_2_deployment.into()),
+        expected
+    );
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_add_liquidity_in_fee_tier() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let fee_tier = 500;
+    let tier_block = set_fee_tier_txs(
+        fee_tier,
+        1,
+        840_001,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    let (pool_block, pool) = create_pool_with_fee_tier_txs(
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        1000000,
+        1000000,
+        fee_tier,
+        840_002,
+        OutPoint {
+            txid: tier_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+
+    let mut add_liquidity_block = create_block_with_coinbase_tx(840_003);
+    insert_add_liquidity_checked_txs(
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        fee_tier,
+        10000,
+        10000,
+        0,
+        0,
+        add_liquidity_block.header.time as u128,
+        &mut add_liquidity_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&add_liquidity_block, 840_003)?;
+
+    // the lp tokens are minted by the pool of the tier, not the default one
+    let sheet = get_last_outpoint_sheet(&add_liquidity_block)?;
+    assert_eq!(sheet.get_cached(&pool.into()), 10000);
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_set_total_fee_for_pool_in_fee_tier_fails() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let fee_tier = 3000;
+    let tier_block = set_fee_tier_txs(
+        fee_tier,
+        1,
+        840_001,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    let (pool_block, pool) = create_pool_with_fee_tier_txs(
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        1000000,
+        1000000,
+        fee_tier,
+        840_002,
+        OutPoint {
+            txid: tier_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+
+    let mut fee_block = create_block_with_coinbase_tx(840_003);
+    insert_pool_call_txs(
+        deployment_ids.amm_factory_proxy,
+        vec![21, pool.block, pool.tx, 5],
+        &mut fee_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&fee_block, 840_003)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: fee_block.txdata.last().unwrap().compute_txid(),
+            vout: 3,
+        },
+        "ALKANES: revert: Error: the total fee of a pool in a fee tier cannot be changed",
+    )?;
+    Ok(())
+}
@@ -4,25 +4,29 @@ use anyhow::Result;
 use bitcoin::blockdata::transaction::OutPoint;
 use bitcoin::Witness;
 use init_pools::test_amm_pool_init_fixture;
+use num::integer::Roots;
 use protorune::test_helpers::create_block_with_coinbase_tx;
 use protorune_support::protostone::ProtostoneEdict;
 
-use crate::tests::helper::common::divide_round_u128;
+use crate::tests::helper::common::{divide_round_u128, get_last_return_data, insert_pool_call_txs};
 use crate::tests::helper::remove_liquidity::insert_remove_liquidity_txs;
 use crate::tests::helper::swap::insert_swap_exact_tokens_for_tokens;
 use crate::tests::helper::*;
 use alkane_helpers::clear;
 use alkanes::indexer::index_block;
 use alkanes::tests::helpers::{
-    self as alkane_helpers, get_last_outpoint_sheet, get_sheet_for_outpoint,
+    self as alkane_helpers, assert_revert_context, get_last_outpoint_sheet, get_sheet_for_outpoint,
 };
 #[allow(unused_imports)]
 use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
 use wasm_bindgen_test::wasm_bindgen_test;
 
-use oylswap_library::{DEFAULT_TOTAL_FEE_AMOUNT_PER_1000, PROTOCOL_FEE_AMOUNT_PER_1000};
+use oylswap_library::{
+    fixed_point::UQ128x128, get_amount_out, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    PROTOCOL_FEE_AMOUNT_PER_1000, U256,
+};
 
-fn test_fee_fixture(custom_fee: u128) -> Result<()> {
+fn test_fee_fixture(custom_fee: u128, protocol_fee: u128) -> Result<()> {
     let (amount1, amount2) = (500000000, 500000000);
     let (init_block, mut runtime_balances, deployment_ids) =
         test_amm_pool_init_fixture(amount1, amount2)?;
@@ -63,6 +67,26 @@ fn test_fee_fixture(custom_fee: u128) -> Result<()> {
             false,
         ),
     );
+    let input_outpoint = OutPoint {
+        txid: change_fee_block.txdata[change_fee_block.txdata.len() - 1].compute_txid(),
+        vout: 0,
+    };
+    change_fee_block.txdata.push(
+        alkane_helpers::create_multiple_cellpack_with_witness_and_in(
+            Witness::new(),
+            vec![Cellpack {
+                target: deployment_ids.amm_factory_proxy,
+                inputs: vec![
+                    22,
+                    deployment_ids.amm_pool_1_deployment.block,
+                    deployment_ids.amm_pool_1_deployment.tx,
+                    protocol_fee,
+                ],
+            }],
+            input_outpoint,
+            false,
+        ),
+    );
     index_block(&change_fee_block, 840_001)?;
 
     let block_height = 840_002;
@@ -175,11 +199,11 @@ fn test_fee_fixture(custom_fee: u128) -> Result<()> {
         .get_cached(&deployment_ids.owned_token_1_deployment.into())
         + fees_sheet.get_cached(&deployment_ids.owned_token_2_deployment.into()))
         * custom_fee
-        / PROTOCOL_FEE_AMOUNT_PER_1000;
+        / protocol_fee;
 
-    // 60% goes to LPs, half of that goes to this LP position (recall init also has a lp position that isn't unraveled)
+    // the rest goes to LPs, half of that goes to this LP position (recall init also has a lp position that isn't unraveled)
     let implied_user_fees_earned =
-        implied_total_fees * (custom_fee - PROTOCOL_FEE_AMOUNT_PER_1000) / custom_fee / 2;
+        implied_total_fees * (custom_fee - protocol_fee) / custom_fee / 2;
 
     assert!(
         implied_user_fees_earned.abs_diff(user_total_fees_earned) * 100 / implied_user_fees_earned
@@ -192,11 +216,172 @@ fn test_fee_fixture(custom_fee: u128) -> Result<()> {
 #[wasm_bindgen_test]
 fn test_amm_pool_swap_fee_claim() -> Result<()> {
     clear();
-    test_fee_fixture(DEFAULT_TOTAL_FEE_AMOUNT_PER_1000)
+    test_fee_fixture(
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+        PROTOCOL_FEE_AMOUNT_PER_1000,
+    )
 }
 
 #[wasm_bindgen_test]
 fn test_amm_pool_swap_fee_claim_large_fee() -> Result<()> {
     clear();
-    test_fee_fixture(200)
+    test_fee_fixture(200, PROTOCOL_FEE_AMOUNT_PER_1000)
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_swap_fee_claim_custom_protocol_fee() -> Result<()> {
+    clear();
+    test_fee_fixture(DEFAULT_TOTAL_FEE_AMOUNT_PER_1000, 5)
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_protocol_fee_exceeds_total_fee_fail() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let mut change_fee_block = create_block_with_coinbase_tx(840_001);
+    change_fee_block.txdata.push(
+        alkane_helpers::create_multiple_cellpack_with_witness_and_in(
+            Witness::new(),
+            vec![Cellpack {
+                target: deployment_ids.amm_factory_proxy,
+                inputs: vec![
+                    22,
+                    deployment_ids.amm_pool_1_deployment.block,
+                    deployment_ids.amm_pool_1_deployment.tx,
+                    DEFAULT_TOTAL_FEE_AMOUNT_PER_1000 + 1,
+                ],
+            }],
+            OutPoint {
+                txid: init_block.txdata[init_block.txdata.len() - 1].compute_txid(),
+                vout: 0,
+            },
+            false,
+        ),
+    );
+    index_block(&change_fee_block, 840_001)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: change_fee_block.txdata[change_fee_block.txdata.len() - 1].compute_txid(),
+            vout: 3,
+        },
+        "ALKANES: revert: Error: protocol fee cannot exceed the total fee",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_fee_growth_per_lp() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (1000000, 1000000);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let mut swap_block = create_block_with_coinbase_tx(840_001);
+    let amount_to_swap = 10000;
+    insert_swap_exact_tokens_for_tokens(
+        amount_to_swap,
+        vec![
+            deployment_ids.owned_token_1_deployment,
+            deployment_ids.owned_token_2_deployment,
+        ],
+        0,
+        &mut swap_block,
+        OutPoint {
+            txid: init_block.txdata[init_block.txdata.len() - 1].compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&swap_block, 840_001)?;
+
+    let mut growth_block = create_block_with_coinbase_tx(840_002);
+    insert_pool_call_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![36],
+        &mut growth_block,
+        OutPoint {
+            txid: swap_block.txdata[swap_block.txdata.len() - 1].compute_txid(),
+            vout: 2,
+        },
+    );
+    index_block(&growth_block, 840_002)?;
+    let data = get_last_return_data(&growth_block)?;
+    let growth_0 = U256::from_le_bytes::<32>(data[0..32].try_into()?);
+    let growth_1 = U256::from_le_bytes::<32>(data[32..64].try_into()?);
+
+    // the protocol's cut is left out, it is paid by minting lp tokens instead
+    let lp_fee =
+        amount_to_swap * (DEFAULT_TOTAL_FEE_AMOUNT_PER_1000 - PROTOCOL_FEE_AMOUNT_PER_1000) / 1000;
+    let total_supply = (amount1 * amount2).sqrt();
+    assert_eq!(growth_0, UQ128x128::fraction(lp_fee, total_supply)?.0);
+    assert_eq!(growth_1, U256::ZERO);
+    // an lp holding the whole supply earned the whole lp fee, give or take rounding
+    assert_eq!(UQ128x128(growth_0).mul_decode(total_supply)?, lp_fee - 1);
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_fee_growth_per_lp_counts_pending_protocol_liquidity() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (1000000, 1000000);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let amount_to_swap = 10000;
+    let mut swap_block = create_block_with_coinbase_tx(840_001);
+    let mut input_outpoint = OutPoint {
+        txid: init_block.txdata[init_block.txdata.len() - 1].compute_txid(),
+        vout: 0,
+    };
+    for _ in 0..2 {
+        insert_swap_exact_tokens_for_tokens(
+            amount_to_swap,
+            vec![
+                deployment_ids.owned_token_1_deployment,
+                deployment_ids.owned_token_2_deployment,
+            ],
+            0,
+            &mut swap_block,
+            input_outpoint,
+            &deployment_ids,
+        );
+        input_outpoint = OutPoint {
+            txid: swap_block.txdata[swap_block.txdata.len() - 1].compute_txid(),
+            vout: 2,
+        };
+    }
+    index_block(&swap_block, 840_001)?;
+
+    let mut growth_block = create_block_with_coinbase_tx(840_002);
+    insert_pool_call_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![36],
+        &mut growth_block,
+        input_outpoint,
+    );
+    index_block(&growth_block, 840_002)?;
+    let data = get_last_return_data(&growth_block)?;
+    let growth_0 = U256::from_le_bytes::<32>(data[0..32].try_into()?);
+
+    // k_last is set by the initial deposit, so the first swap's fees are shared by the supply
+    // alone. By the second swap the protocol is owed the lp tokens _mint_fee would mint for the
+    // growth of k in the first one.
+    let lp_fee =
+        amount_to_swap * (DEFAULT_TOTAL_FEE_AMOUNT_PER_1000 - PROTOCOL_FEE_AMOUNT_PER_1000) / 1000;
+    let total_supply = (amount1 * amount2).sqrt();
+    let amount_out = get_amount_out(
+        amount_to_swap,
+        amount1,
+        amount2,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+    let root_k_last = (amount1 * amount2).sqrt();
+    let root_k = ((amount1 + amount_to_swap) * (amount2 - amount_out)).sqrt();
+    let protocol_liquidity = total_supply * (root_k - root_k_last)
+        / (root_k * (DEFAULT_TOTAL_FEE_AMOUNT_PER_1000 - PROTOCOL_FEE_AMOUNT_PER_1000)
+            / PROTOCOL_FEE_AMOUNT_PER_1000
+            + root_k_last);
+    assert!(protocol_liquidity > 0);
+    assert_eq!(
+        growth_0,
+        UQ128x128::fraction(lp_fee, total_supply)?.0
+            + UQ128x128::fraction(lp_fee, total_supply + protocol_liquidity)?.0
+    );
+    Ok(())
 }
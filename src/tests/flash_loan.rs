@@ -0,0 +1,208 @@
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::{self as alkane_helpers, assert_revert_context};
+use alkanes_support::cellpack::Cellpack;
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use bitcoin::Witness;
+use init_pools::test_amm_pool_init_fixture;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use oylswap_library::DEFAULT_FLASH_FEE_AMOUNT_PER_1000;
+use protorune::test_helpers::create_block_with_coinbase_tx;
+use protorune_support::protostone::ProtostoneEdict;
+use std::fmt::Write;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use crate::tests::helper::common::{get_last_return_data, insert_pool_call_txs};
+use crate::tests::helper::swap::insert_flash_loan_txs;
+use crate::tests::helper::*;
+use alkane_helpers::clear;
+
+fn flash_fee(amount: u128) -> u128 {
+    (amount * DEFAULT_FLASH_FEE_AMOUNT_PER_1000 + 999) / 1000
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_flash_loan() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (500000, 500000);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let block_height = 840_001;
+    let mut loan_block = create_block_with_coinbase_tx(block_height);
+    let amount_borrowed = 10000;
+    insert_flash_loan_txs(
+        vec![ProtostoneEdict {
+            id: deployment_ids.owned_token_2_deployment.into(),
+            amount: flash_fee(amount_borrowed),
+            output: 0,
+        }],
+        &mut loan_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        deployment_ids.amm_pool_1_deployment,
+        0,
+        amount_borrowed,
+        deployment_ids.example_flashswap,
+        vec![1], // refund the loan
+    );
+    index_block(&loan_block, block_height)?;
+
+    // the fee stays in the pool
+    let mut reserves_block = create_block_with_coinbase_tx(block_height + 1);
+    insert_pool_call_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![97],
+        &mut reserves_block,
+        OutPoint {
+            txid: loan_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+    );
+    index_block(&reserves_block, block_height + 1)?;
+    let data = get_last_return_data(&reserves_block)?;
+    assert_eq!(u128::from_le_bytes(data[0..16].try_into()?), amount1);
+    assert_eq!(
+        u128::from_le_bytes(data[16..32].try_into()?),
+        amount2 + flash_fee(amount_borrowed)
+    );
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_flash_loan_fee_not_paid_fail() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(500000, 500000)?;
+    let block_height = 840_001;
+    let mut loan_block = create_block_with_coinbase_tx(block_height);
+    let amount_borrowed = 10000;
+    insert_flash_loan_txs(
+        vec![ProtostoneEdict {
+            id: deployment_ids.owned_token_2_deployment.into(),
+            amount: flash_fee(amount_borrowed) - 1,
+            output: 0,
+        }],
+        &mut loan_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        deployment_ids.amm_pool_1_deployment,
+        0,
+        amount_borrowed,
+        deployment_ids.example_flashswap,
+        vec![1],
+    );
+    index_block(&loan_block, block_height)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: loan_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        "ALKANES: revert: Error: FLASH_LOAN_NOT_REPAID",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_flash_loan_no_refund_fail() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(500000, 500000)?;
+    let block_height = 840_001;
+    let mut loan_block = create_block_with_coinbase_tx(block_height);
+    let amount_borrowed = 10000;
+    insert_flash_loan_txs(
+        vec![ProtostoneEdict {
+            id: deployment_ids.owned_token_2_deployment.into(),
+            amount: flash_fee(amount_borrowed),
+            output: 0,
+        }],
+        &mut loan_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        deployment_ids.amm_pool_1_deployment,
+        0,
+        amount_borrowed,
+        deployment_ids.example_flashswap,
+        vec![0], // keep the loan
+    );
+    index_block(&loan_block, block_height)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: loan_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        "ALKANES: revert: Error: FLASH_LOAN_NOT_REPAID",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_set_flash_fee() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(500000, 500000)?;
+    let mut change_fee_block = create_block_with_coinbase_tx(840_001);
+    change_fee_block.txdata.push(
+        alkane_helpers::create_multiple_cellpack_with_witness_and_in(
+            Witness::new(),
+            vec![Cellpack {
+                target: deployment_ids.amm_factory_proxy,
+                inputs: vec![
+                    23,
+                    deployment_ids.amm_pool_1_deployment.block,
+                    deployment_ids.amm_pool_1_deployment.tx,
+                    5,
+                ],
+            }],
+            OutPoint {
+                txid: init_block.txdata.last().unwrap().compute_txid(),
+                vout: 0,
+            },
+            false,
+        ),
+    );
+    index_block(&change_fee_block, 840_001)?;
+
+    let mut flash_fee_block = create_block_with_coinbase_tx(840_002);
+    insert_pool_call_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![28],
+        &mut flash_fee_block,
+        OutPoint {
+            txid: change_fee_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&flash_fee_block, 840_002)?;
+    let data = get_last_return_data(&flash_fee_block)?;
+    assert_eq!(u128::from_le_bytes(data[0..16].try_into()?), 5);
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_set_flash_fee_not_factory_fail() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(500000, 500000)?;
+    let mut change_fee_block = create_block_with_coinbase_tx(840_001);
+    insert_pool_call_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![29, 0],
+        &mut change_fee_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&change_fee_block, 840_001)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: change_fee_block.txdata.last().unwrap().compute_txid(),
+            vout: 3,
+        },
+        "ALKANES: revert: Error: Caller is not factory",
+    )?;
+    Ok(())
+}
@@ -79,6 +79,7 @@ pub fn insert_add_liquidity_txs(
 pub fn insert_add_liquidity_checked_txs(
     token1_address: AlkaneId,
     token2_address: AlkaneId,
+    fee_tier: u128,
     amount_a_desired: u128,
     amount_b_desired: u128,
     amount_a_min: u128,
@@ -100,10 +101,13 @@ pub fn insert_add_liquidity_checked_txs(
                     token1_address.tx,
                     token2_address.block,
                     token2_address.tx,
+                    fee_tier,
                     amount_a_desired,
                     amount_b_desired,
                     amount_a_min,
                     amount_b_min,
+                    0,
+                    0,
                     deadline,
                 ],
             }],
@@ -112,6 +116,49 @@ pub fn insert_add_liquidity_checked_txs(
         ));
 }
 
+pub fn insert_zap_in_txs(
+    amount_in: u128,
+    token_a: AlkaneId,
+    token_b: AlkaneId,
+    fee_tier: u128,
+    min_lp_out: u128,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) {
+    test_block.txdata.push(
+        create_multiple_cellpack_with_witness_and_in_with_edicts_and_leftovers(
+            Witness::new(),
+            vec![
+                CellpackOrEdict::Edict(vec![ProtostoneEdict {
+                    amount: amount_in,
+                    output: 0,
+                    id: token_a.into(),
+                }]),
+                CellpackOrEdict::Cellpack(Cellpack {
+                    target: deployment_ids.amm_factory_proxy,
+                    inputs: vec![
+                        16,
+                        token_a.block,
+                        token_a.tx,
+                        token_b.block,
+                        token_b.tx,
+                        fee_tier,
+                        amount_in,
+                        min_lp_out,
+                        0,
+                        0,
+                        test_block.header.time as u128,
+                    ],
+                }),
+            ],
+            input_outpoint,
+            false,
+            true,
+        ),
+    );
+}
+
 pub fn calc_lp_balance_from_add_liquidity(
     prev_amount1: u128,
     prev_amount2: u128,
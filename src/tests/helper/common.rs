@@ -1,6 +1,8 @@
 use alkanes::tests::helpers::{self as alkane_helpers};
+use alkanes::view;
+use alkanes_support::trace::{Trace, TraceEvent};
 use alkanes_support::{cellpack::Cellpack, id::AlkaneId};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use bitcoin::address::NetworkChecked;
 use bitcoin::blockdata::transaction::OutPoint;
 use bitcoin::transaction::Version;
@@ -32,6 +34,16 @@ pub struct AmmTestDeploymentIds {
     pub owned_token_3_deployment: AlkaneId,
     pub oyl_token_deployment: AlkaneId,
     pub example_flashswap: AlkaneId,
+    pub concentrated_pool_logic_impl: AlkaneId,
+    pub concentrated_pool_upgradeable_beacon: AlkaneId,
+    pub stable_pool_logic_impl: AlkaneId,
+    pub stable_pool_upgradeable_beacon: AlkaneId,
+    pub weighted_pool_logic_impl: AlkaneId,
+    pub weighted_pool_upgradeable_beacon: AlkaneId,
+    pub limit_orders: AlkaneId,
+    pub twamm: AlkaneId,
+    pub lp_locker: AlkaneId,
+    pub staking: AlkaneId,
     // below are modified once init
     pub amm_factory_auth_token: AlkaneId,
     pub amm_pool_1_deployment: AlkaneId,
@@ -48,6 +60,16 @@ pub const OWNED_TOKEN_2_DEPLOYMENT_TX: u128 = 5;
 pub const OWNED_TOKEN_3_DEPLOYMENT_TX: u128 = 7;
 pub const OYL_TOKEN_DEPLOYMENT_TX: u128 = 9;
 pub const EXAMPLE_FLASHSWAP_TX: u128 = 10;
+pub const CONCENTRATED_POOL_LOGIC_IMPL_TX: u128 = 11;
+pub const CONCENTRATED_POOL_UPGRADEABLE_BEACON_TX: u128 = 0xbeac2;
+pub const STABLE_POOL_LOGIC_IMPL_TX: u128 = 12;
+pub const STABLE_POOL_UPGRADEABLE_BEACON_TX: u128 = 0xbeac3;
+pub const WEIGHTED_POOL_LOGIC_IMPL_TX: u128 = 13;
+pub const WEIGHTED_POOL_UPGRADEABLE_BEACON_TX: u128 = 0xbeac4;
+pub const LIMIT_ORDERS_TX: u128 = 14;
+pub const TWAMM_TX: u128 = 15;
+pub const LP_LOCKER_TX: u128 = 16;
+pub const STAKING_TX: u128 = 17;
 
 pub fn create_deployment_ids() -> AmmTestDeploymentIds {
     AmmTestDeploymentIds {
@@ -96,6 +118,46 @@ pub fn create_deployment_ids() -> AmmTestDeploymentIds {
             block: 4,
             tx: EXAMPLE_FLASHSWAP_TX,
         },
+        concentrated_pool_logic_impl: AlkaneId {
+            block: 4,
+            tx: CONCENTRATED_POOL_LOGIC_IMPL_TX,
+        },
+        concentrated_pool_upgradeable_beacon: AlkaneId {
+            block: 4,
+            tx: CONCENTRATED_POOL_UPGRADEABLE_BEACON_TX,
+        },
+        stable_pool_logic_impl: AlkaneId {
+            block: 4,
+            tx: STABLE_POOL_LOGIC_IMPL_TX,
+        },
+        stable_pool_upgradeable_beacon: AlkaneId {
+            block: 4,
+            tx: STABLE_POOL_UPGRADEABLE_BEACON_TX,
+        },
+        weighted_pool_logic_impl: AlkaneId {
+            block: 4,
+            tx: WEIGHTED_POOL_LOGIC_IMPL_TX,
+        },
+        weighted_pool_upgradeable_beacon: AlkaneId {
+            block: 4,
+            tx: WEIGHTED_POOL_UPGRADEABLE_BEACON_TX,
+        },
+        limit_orders: AlkaneId {
+            block: 4,
+            tx: LIMIT_ORDERS_TX,
+        },
+        twamm: AlkaneId {
+            block: 4,
+            tx: TWAMM_TX,
+        },
+        lp_locker: AlkaneId {
+            block: 4,
+            tx: LP_LOCKER_TX,
+        },
+        staking: AlkaneId {
+            block: 4,
+            tx: STAKING_TX,
+        },
         amm_pool_1_deployment: AlkaneId { block: 0, tx: 0 },
         amm_pool_2_deployment: AlkaneId { block: 0, tx: 0 },
     }
@@ -324,3 +386,61 @@ pub fn check_input_tokens_refunded(
     }
     Ok(())
 }
+
+pub fn insert_pool_call_txs(
+    pool: AlkaneId,
+    inputs: Vec<u128>,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+) {
+    test_block.txdata.push(
+        alkane_helpers::create_multiple_cellpack_with_witness_and_in(
+            Witness::new(),
+            vec![Cellpack {
+                target: pool,
+                inputs,
+            }],
+            input_outpoint,
+            false,
+        ),
+    );
+}
+
+pub fn insert_set_pool_paused_txs(
+    paused: bool,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) {
+    test_block.txdata.push(
+        alkane_helpers::create_multiple_cellpack_with_witness_and_in(
+            Witness::new(),
+            vec![Cellpack {
+                target: deployment_ids.amm_factory_proxy,
+                inputs: vec![
+                    24,
+                    deployment_ids.amm_pool_1_deployment.block,
+                    deployment_ids.amm_pool_1_deployment.tx,
+                    paused as u128,
+                ],
+            }],
+            input_outpoint,
+            false,
+        ),
+    );
+}
+
+// data returned by the last tx of the block, for calls made through
+// create_multiple_cellpack_with_witness_and_in
+pub fn get_last_return_data(test_block: &Block) -> Result<Vec<u8>> {
+    let outpoint = OutPoint {
+        txid: test_block.txdata.last().unwrap().compute_txid(),
+        vout: 3,
+    };
+    let trace_data: Trace = view::trace(&outpoint)?.try_into()?;
+    let last_trace_event = trace_data.0.lock().expect("Mutex poisoned").last().cloned();
+    match last_trace_event {
+        Some(TraceEvent::ReturnContext(trace_response)) => Ok(trace_response.inner.data),
+        _ => Err(anyhow!("call did not return")),
+    }
+}
@@ -0,0 +1,265 @@
+use crate::tests::std::concentrated_pool_build;
+use alkanes::indexer::index_block;
+use alkanes::precompiled::alkanes_std_upgradeable_beacon_build;
+use alkanes::tests::helpers::{
+    self as alkane_helpers, create_multiple_cellpack_with_witness_and_in, BinaryAndCellpack,
+};
+use alkanes::vm::utils::sequence_pointer;
+use alkanes_runtime_concentrated_pool::tick_to_u128;
+use alkanes_runtime_factory::POOL_TEMPLATE_CONCENTRATED;
+use alkanes_support::cellpack::Cellpack;
+use alkanes_support::id::AlkaneId;
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use bitcoin::{Block, Witness};
+use metashrew_core::index_pointer::AtomicPointer;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use metashrew_support::index_pointer::KeyValuePointer;
+use protorune::test_helpers::create_block_with_coinbase_tx;
+use protorune_support::protostone::ProtostoneEdict;
+use std::fmt::Write;
+
+use super::common::{
+    create_multiple_cellpack_with_witness_and_in_with_edicts_and_leftovers, AmmTestDeploymentIds,
+    CellpackOrEdict,
+};
+use super::init_pools::test_amm_pool_init_fixture;
+
+pub const CL_FEE_PER_1000000: u128 = 3000;
+pub const CL_TICK_SPACING: u128 = 60;
+
+// deploys the concentrated pool logic behind its own beacon and registers it as a factory template
+pub fn init_concentrated_pool_template(
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) -> Result<Block> {
+    let block_height = 840_000;
+    let cellpack_pairs: Vec<BinaryAndCellpack> = [
+        BinaryAndCellpack {
+            binary: concentrated_pool_build::get_bytes(),
+            cellpack: Cellpack {
+                target: AlkaneId {
+                    block: 3,
+                    tx: deployment_ids.concentrated_pool_logic_impl.tx,
+                },
+                inputs: vec![50],
+            },
+        },
+        BinaryAndCellpack {
+            binary: alkanes_std_upgradeable_beacon_build::get_bytes(),
+            cellpack: Cellpack {
+                target: AlkaneId {
+                    block: 3,
+                    tx: deployment_ids.concentrated_pool_upgradeable_beacon.tx,
+                },
+                inputs: vec![
+                    0x7fff,
+                    deployment_ids.concentrated_pool_logic_impl.block,
+                    deployment_ids.concentrated_pool_logic_impl.tx,
+                    1,
+                ],
+            },
+        },
+        BinaryAndCellpack::cellpack_only(Cellpack {
+            target: deployment_ids.amm_factory_proxy,
+            inputs: vec![
+                8,
+                POOL_TEMPLATE_CONCENTRATED,
+                deployment_ids.concentrated_pool_upgradeable_beacon.block,
+                deployment_ids.concentrated_pool_upgradeable_beacon.tx,
+            ],
+        }),
+    ]
+    .into();
+    let test_block =
+        alkane_helpers::init_with_cellpack_pairs_w_input(cellpack_pairs, input_outpoint);
+    index_block(&test_block, block_height)?;
+    Ok(test_block)
+}
+
+pub fn create_concentrated_pool_txs(
+    token_a: AlkaneId,
+    token_b: AlkaneId,
+    price_numerator: u128,
+    price_denominator: u128,
+    previous_output: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) -> Result<(Block, AlkaneId)> {
+    let block_height = 840_001;
+    let mut test_block = create_block_with_coinbase_tx(block_height);
+    let next_sequence_pointer = sequence_pointer(&mut AtomicPointer::default());
+    let pool_sequence = next_sequence_pointer.get_value::<u128>();
+    test_block
+        .txdata
+        .push(create_multiple_cellpack_with_witness_and_in(
+            Witness::new(),
+            vec![Cellpack {
+                target: deployment_ids.amm_factory_proxy,
+                inputs: vec![
+                    5,
+                    token_a.block,
+                    token_a.tx,
+                    token_b.block,
+                    token_b.tx,
+                    CL_FEE_PER_1000000,
+                    CL_TICK_SPACING,
+                    price_numerator,
+                    price_denominator,
+                ],
+            }],
+            previous_output,
+            false,
+        ));
+    index_block(&test_block, block_height)?;
+    Ok((
+        test_block,
+        AlkaneId {
+            block: 2,
+            tx: pool_sequence,
+        },
+    ))
+}
+
+fn _insert_concentrated_pool_txs(
+    input_edicts: Vec<ProtostoneEdict>,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    cellpack: Cellpack,
+) {
+    test_block.txdata.push(
+        create_multiple_cellpack_with_witness_and_in_with_edicts_and_leftovers(
+            Witness::new(),
+            vec![
+                CellpackOrEdict::Edict(input_edicts),
+                CellpackOrEdict::Cellpack(cellpack),
+            ],
+            input_outpoint,
+            false,
+            true,
+        ),
+    );
+}
+
+pub fn insert_mint_position_txs(
+    amounts: Vec<(AlkaneId, u128)>,
+    tick_lower: i32,
+    tick_upper: i32,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    pool: AlkaneId,
+) {
+    _insert_concentrated_pool_txs(
+        amounts
+            .into_iter()
+            .map(|(id, amount)| ProtostoneEdict {
+                id: id.into(),
+                amount,
+                output: 0,
+            })
+            .collect(),
+        test_block,
+        input_outpoint,
+        Cellpack {
+            target: pool,
+            inputs: vec![1, tick_to_u128(tick_lower), tick_to_u128(tick_upper)],
+        },
+    )
+}
+
+pub fn insert_concentrated_burn_txs(
+    position: AlkaneId,
+    liquidity: u128,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    pool: AlkaneId,
+) {
+    _insert_concentrated_pool_txs(
+        vec![ProtostoneEdict {
+            id: position.into(),
+            amount: 1,
+            output: 0,
+        }],
+        test_block,
+        input_outpoint,
+        Cellpack {
+            target: pool,
+            inputs: vec![3, liquidity],
+        },
+    )
+}
+
+pub fn insert_concentrated_swap_exact_input_txs(
+    token_in: AlkaneId,
+    amount_in: u128,
+    amount_out_min: u128,
+    tick_limit: i32,
+    deadline: u128,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    pool: AlkaneId,
+) {
+    _insert_concentrated_pool_txs(
+        vec![ProtostoneEdict {
+            id: token_in.into(),
+            amount: amount_in,
+            output: 0,
+        }],
+        test_block,
+        input_outpoint,
+        Cellpack {
+            target: pool,
+            inputs: vec![5, amount_out_min, tick_to_u128(tick_limit), deadline],
+        },
+    )
+}
+
+pub fn insert_concentrated_swap_exact_output_txs(
+    token_in: AlkaneId,
+    amount_in_max: u128,
+    amount_out: u128,
+    tick_limit: i32,
+    deadline: u128,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    pool: AlkaneId,
+) {
+    _insert_concentrated_pool_txs(
+        vec![ProtostoneEdict {
+            id: token_in.into(),
+            amount: amount_in_max,
+            output: 0,
+        }],
+        test_block,
+        input_outpoint,
+        Cellpack {
+            target: pool,
+            inputs: vec![6, amount_out, tick_to_u128(tick_limit), deadline],
+        },
+    )
+}
+
+// sets up the regular amm fixture, registers the concentrated template and creates a
+// token 1 / token 3 concentrated pool at a 1:1 price
+pub fn test_concentrated_pool_init_fixture() -> Result<(Block, AmmTestDeploymentIds, AlkaneId)> {
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let template_block = init_concentrated_pool_template(
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    let (pool_block, pool) = create_concentrated_pool_txs(
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_3_deployment,
+        1,
+        1,
+        OutPoint {
+            txid: template_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    Ok((pool_block, deployment_ids, pool))
+}
@@ -0,0 +1,82 @@
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::create_multiple_cellpack_with_witness_and_in;
+use alkanes::vm::utils::sequence_pointer;
+use alkanes_support::cellpack::Cellpack;
+use alkanes_support::id::AlkaneId;
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use bitcoin::{Block, Witness};
+use metashrew_core::index_pointer::AtomicPointer;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use metashrew_support::index_pointer::KeyValuePointer;
+use protorune::test_helpers::create_block_with_coinbase_tx;
+use std::fmt::Write;
+
+use super::common::AmmTestDeploymentIds;
+
+// the whole input, including the factory auth token, goes to the factory
+pub fn set_fee_tier_txs(
+    fee_tier: u128,
+    enabled: u128,
+    block_height: u32,
+    previous_output: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) -> Result<Block> {
+    let mut test_block = create_block_with_coinbase_tx(block_height);
+    test_block
+        .txdata
+        .push(create_multiple_cellpack_with_witness_and_in(
+            Witness::new(),
+            vec![Cellpack {
+                target: deployment_ids.amm_factory_proxy,
+                inputs: vec![18, fee_tier, enabled],
+            }],
+            previous_output,
+            false,
+        ));
+    index_block(&test_block, block_height)?;
+    Ok(test_block)
+}
+
+pub fn create_pool_with_fee_tier_txs(
+    token_a: AlkaneId,
+    token_b: AlkaneId,
+    amount_a: u128,
+    amount_b: u128,
+    fee_tier: u128,
+    block_height: u32,
+    previous_output: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) -> Result<(Block, AlkaneId)> {
+    let mut test_block = create_block_with_coinbase_tx(block_height);
+    let pool_sequence = sequence_pointer(&mut AtomicPointer::default()).get_value::<u128>();
+    test_block
+        .txdata
+        .push(create_multiple_cellpack_with_witness_and_in(
+            Witness::new(),
+            vec![Cellpack {
+                target: deployment_ids.amm_factory_proxy,
+                inputs: vec![
+                    20,
+                    token_a.block,
+                    token_a.tx,
+                    token_b.block,
+                    token_b.tx,
+                    amount_a,
+                    amount_b,
+                    fee_tier,
+                ],
+            }],
+            previous_output,
+            false,
+        ));
+    index_block(&test_block, block_height)?;
+    Ok((
+        test_block,
+        AlkaneId {
+            block: 2,
+            tx: pool_sequence,
+        },
+    ))
+}
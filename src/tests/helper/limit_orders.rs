@@ -0,0 +1,138 @@
+use crate::tests::std::limit_orders_build;
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::{self as alkane_helpers, BinaryAndCellpack};
+use alkanes::vm::utils::sequence_pointer;
+use alkanes_support::cellpack::Cellpack;
+use alkanes_support::id::AlkaneId;
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use bitcoin::{Block, Witness};
+use metashrew_core::index_pointer::AtomicPointer;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use metashrew_support::index_pointer::KeyValuePointer;
+use protorune_support::protostone::ProtostoneEdict;
+use std::fmt::Write;
+
+use super::common::*;
+
+// deploys the limit order book pointed at the amm factory
+pub fn init_limit_orders(
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) -> Result<Block> {
+    let block_height = 840_001;
+    let cellpack_pairs: Vec<BinaryAndCellpack> = [BinaryAndCellpack {
+        binary: limit_orders_build::get_bytes(),
+        cellpack: Cellpack {
+            target: AlkaneId {
+                block: 3,
+                tx: deployment_ids.limit_orders.tx,
+            },
+            inputs: vec![
+                0,
+                deployment_ids.amm_factory_proxy.block,
+                deployment_ids.amm_factory_proxy.tx,
+            ],
+        },
+    }]
+    .into();
+    let test_block =
+        alkane_helpers::init_with_cellpack_pairs_w_input(cellpack_pairs, input_outpoint);
+    index_block(&test_block, block_height)?;
+    Ok(test_block)
+}
+
+// returns the id of the order token the order book will create for this order
+pub fn insert_place_order_txs(
+    amount_in: u128,
+    token_in: AlkaneId,
+    token_out: AlkaneId,
+    amount_out_min: u128,
+    keeper_tip: u128,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) -> AlkaneId {
+    let order_sequence = sequence_pointer(&mut AtomicPointer::default()).get_value::<u128>();
+    test_block.txdata.push(
+        create_multiple_cellpack_with_witness_and_in_with_edicts_and_leftovers(
+            Witness::new(),
+            vec![
+                CellpackOrEdict::Edict(vec![ProtostoneEdict {
+                    id: token_in.into(),
+                    amount: amount_in,
+                    output: 0,
+                }]),
+                CellpackOrEdict::Cellpack(Cellpack {
+                    target: deployment_ids.limit_orders,
+                    inputs: vec![
+                        1,
+                        token_out.block,
+                        token_out.tx,
+                        amount_out_min,
+                        keeper_tip,
+                        0,
+                    ],
+                }),
+            ],
+            input_outpoint,
+            false,
+            true,
+        ),
+    );
+    AlkaneId {
+        block: 2,
+        tx: order_sequence,
+    }
+}
+
+pub fn insert_fill_order_txs(
+    order_id: AlkaneId,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) {
+    test_block.txdata.push(
+        create_multiple_cellpack_with_witness_and_in_with_edicts_and_leftovers(
+            Witness::new(),
+            vec![
+                CellpackOrEdict::Edict(vec![]),
+                CellpackOrEdict::Cellpack(Cellpack {
+                    target: deployment_ids.limit_orders,
+                    inputs: vec![2, order_id.block, order_id.tx],
+                }),
+            ],
+            input_outpoint,
+            false,
+            true,
+        ),
+    );
+}
+
+pub fn insert_withdraw_order_txs(
+    order_id: AlkaneId,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) {
+    test_block.txdata.push(
+        create_multiple_cellpack_with_witness_and_in_with_edicts_and_leftovers(
+            Witness::new(),
+            vec![
+                CellpackOrEdict::Edict(vec![ProtostoneEdict {
+                    id: order_id.into(),
+                    amount: 1,
+                    output: 0,
+                }]),
+                CellpackOrEdict::Cellpack(Cellpack {
+                    target: deployment_ids.limit_orders,
+                    inputs: vec![3],
+                }),
+            ],
+            input_outpoint,
+            false,
+            true,
+        ),
+    );
+}
@@ -0,0 +1,126 @@
+use crate::tests::std::lp_locker_build;
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::{self as alkane_helpers, BinaryAndCellpack};
+use alkanes::vm::utils::sequence_pointer;
+use alkanes_support::cellpack::Cellpack;
+use alkanes_support::id::AlkaneId;
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use bitcoin::{Block, Witness};
+use metashrew_core::index_pointer::AtomicPointer;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use metashrew_support::index_pointer::KeyValuePointer;
+use protorune_support::protostone::ProtostoneEdict;
+use std::fmt::Write;
+
+use super::common::*;
+
+// deploys the lp locker pointed at the amm factory
+pub fn init_lp_locker(
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) -> Result<Block> {
+    let block_height = 840_001;
+    let cellpack_pairs: Vec<BinaryAndCellpack> = [BinaryAndCellpack {
+        binary: lp_locker_build::get_bytes(),
+        cellpack: Cellpack {
+            target: AlkaneId {
+                block: 3,
+                tx: deployment_ids.lp_locker.tx,
+            },
+            inputs: vec![
+                0,
+                deployment_ids.amm_factory_proxy.block,
+                deployment_ids.amm_factory_proxy.tx,
+            ],
+        },
+    }]
+    .into();
+    let test_block =
+        alkane_helpers::init_with_cellpack_pairs_w_input(cellpack_pairs, input_outpoint);
+    index_block(&test_block, block_height)?;
+    Ok(test_block)
+}
+
+// locks lp tokens of amm pool 1 and returns the id of the receipt token the locker will create
+pub fn insert_lock_lp_txs(
+    amount: u128,
+    unlock_height: u128,
+    vesting_end_height: u128,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) -> AlkaneId {
+    insert_lock_txs(
+        deployment_ids.amm_pool_1_deployment,
+        amount,
+        unlock_height,
+        vesting_end_height,
+        test_block,
+        input_outpoint,
+        deployment_ids,
+    )
+}
+
+pub fn insert_lock_txs(
+    token: AlkaneId,
+    amount: u128,
+    unlock_height: u128,
+    vesting_end_height: u128,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) -> AlkaneId {
+    let receipt_sequence = sequence_pointer(&mut AtomicPointer::default()).get_value::<u128>();
+    test_block.txdata.push(
+        create_multiple_cellpack_with_witness_and_in_with_edicts_and_leftovers(
+            Witness::new(),
+            vec![
+                CellpackOrEdict::Edict(vec![ProtostoneEdict {
+                    id: token.into(),
+                    amount,
+                    output: 0,
+                }]),
+                CellpackOrEdict::Cellpack(Cellpack {
+                    target: deployment_ids.lp_locker,
+                    inputs: vec![1, unlock_height, vesting_end_height],
+                }),
+            ],
+            input_outpoint,
+            false,
+            true,
+        ),
+    );
+    AlkaneId {
+        block: 2,
+        tx: receipt_sequence,
+    }
+}
+
+pub fn insert_redeem_lp_lock_txs(
+    receipt_id: AlkaneId,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) {
+    test_block.txdata.push(
+        create_multiple_cellpack_with_witness_and_in_with_edicts_and_leftovers(
+            Witness::new(),
+            vec![
+                CellpackOrEdict::Edict(vec![ProtostoneEdict {
+                    id: receipt_id.into(),
+                    amount: 1,
+                    output: 0,
+                }]),
+                CellpackOrEdict::Cellpack(Cellpack {
+                    target: deployment_ids.lp_locker,
+                    inputs: vec![2],
+                }),
+            ],
+            input_outpoint,
+            false,
+            true,
+        ),
+    );
+}
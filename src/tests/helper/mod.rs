@@ -1,5 +1,14 @@
 pub mod add_liquidity;
 pub mod common;
+pub mod concentrated_pool;
+pub mod fee_tiers;
 pub mod init_pools;
+pub mod limit_orders;
+pub mod lp_locker;
+pub mod oracle;
 pub mod remove_liquidity;
+pub mod stable_pool;
+pub mod staking;
 pub mod swap;
+pub mod twamm;
+pub mod weighted_pool;
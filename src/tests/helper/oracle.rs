@@ -0,0 +1,65 @@
+use alkanes::tests::helpers::{self as alkane_helpers};
+use alkanes_support::cellpack::Cellpack;
+use alkanes_support::id::AlkaneId;
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use bitcoin::{Block, Witness};
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use oylswap_library::U256;
+use std::fmt::Write;
+
+use super::common::get_last_return_data;
+
+pub fn insert_increase_observation_cardinality_txs(
+    pool: AlkaneId,
+    observation_cardinality_next: u128,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+) {
+    test_block.txdata.push(
+        alkane_helpers::create_multiple_cellpack_with_witness_and_in(
+            Witness::new(),
+            vec![Cellpack {
+                target: pool,
+                inputs: vec![30, observation_cardinality_next],
+            }],
+            input_outpoint,
+            false,
+        ),
+    );
+}
+
+pub fn insert_consult_txs(
+    pool: AlkaneId,
+    seconds_ago: Vec<u128>,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+) {
+    let mut inputs = vec![31, seconds_ago.len() as u128];
+    inputs.extend(seconds_ago);
+    test_block.txdata.push(
+        alkane_helpers::create_multiple_cellpack_with_witness_and_in(
+            Witness::new(),
+            vec![Cellpack {
+                target: pool,
+                inputs,
+            }],
+            input_outpoint,
+            false,
+        ),
+    );
+}
+
+// decodes the (price 0 cumulative, price 1 cumulative) pairs returned by the last consult tx
+pub fn get_consult_result(test_block: &Block) -> Result<Vec<(U256, U256)>> {
+    Ok(get_last_return_data(test_block)?
+        .chunks(64)
+        .map(|chunk| {
+            (
+                U256::from_le_bytes::<32>(chunk[0..32].try_into().unwrap()),
+                U256::from_le_bytes::<32>(chunk[32..64].try_into().unwrap()),
+            )
+        })
+        .collect())
+}
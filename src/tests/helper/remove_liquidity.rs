@@ -52,6 +52,7 @@ pub fn insert_remove_liquidity_txs(
 pub fn insert_remove_liquidity_checked_txs(
     token1_address: AlkaneId,
     token2_address: AlkaneId,
+    fee_tier: u128,
     liquidity: u128,
     amount_a_min: u128,
     amount_b_min: u128,
@@ -59,6 +60,35 @@ pub fn insert_remove_liquidity_checked_txs(
     test_block: &mut Block,
     input_outpoint: OutPoint,
     deployment_ids: &AmmTestDeploymentIds,
+) {
+    insert_remove_liquidity_checked_to_txs(
+        token1_address,
+        token2_address,
+        fee_tier,
+        liquidity,
+        amount_a_min,
+        amount_b_min,
+        AlkaneId::new(0, 0),
+        deadline,
+        test_block,
+        input_outpoint,
+        deployment_ids,
+    )
+}
+
+// the withdrawn tokens go to `to` instead of the caller
+pub fn insert_remove_liquidity_checked_to_txs(
+    token1_address: AlkaneId,
+    token2_address: AlkaneId,
+    fee_tier: u128,
+    liquidity: u128,
+    amount_a_min: u128,
+    amount_b_min: u128,
+    to: AlkaneId,
+    deadline: u128,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
 ) {
     test_block
         .txdata
@@ -72,9 +102,12 @@ pub fn insert_remove_liquidity_checked_txs(
                     token1_address.tx,
                     token2_address.block,
                     token2_address.tx,
+                    fee_tier,
                     liquidity,
                     amount_a_min,
                     amount_b_min,
+                    to.block,
+                    to.tx,
                     deadline,
                 ],
             }],
@@ -83,6 +116,49 @@ pub fn insert_remove_liquidity_checked_txs(
         ));
 }
 
+pub fn insert_zap_out_txs(
+    liquidity: u128,
+    token_a: AlkaneId,
+    token_b: AlkaneId,
+    fee_tier: u128,
+    amount_out_min: u128,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) {
+    test_block.txdata.push(
+        create_multiple_cellpack_with_witness_and_in_with_edicts_and_leftovers(
+            Witness::new(),
+            vec![
+                CellpackOrEdict::Edict(vec![ProtostoneEdict {
+                    id: deployment_ids.amm_pool_1_deployment.into(),
+                    amount: liquidity,
+                    output: 0,
+                }]),
+                CellpackOrEdict::Cellpack(Cellpack {
+                    target: deployment_ids.amm_factory_proxy,
+                    inputs: vec![
+                        17,
+                        token_a.block,
+                        token_a.tx,
+                        token_b.block,
+                        token_b.tx,
+                        fee_tier,
+                        liquidity,
+                        amount_out_min,
+                        0,
+                        0,
+                        test_block.header.time as u128,
+                    ],
+                }),
+            ],
+            input_outpoint,
+            false,
+            true,
+        ),
+    );
+}
+
 pub fn check_remove_liquidity_runtime_balance(
     runtime_balances: &mut BalanceSheet<IndexPointer>,
     removed_amount1: u128,
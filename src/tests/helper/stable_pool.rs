@@ -0,0 +1,145 @@
+use crate::tests::std::stable_pool_build;
+use alkanes::indexer::index_block;
+use alkanes::precompiled::alkanes_std_upgradeable_beacon_build;
+use alkanes::tests::helpers::{
+    self as alkane_helpers, create_multiple_cellpack_with_witness_and_in, BinaryAndCellpack,
+};
+use alkanes::vm::utils::sequence_pointer;
+use alkanes_runtime_factory::POOL_TEMPLATE_STABLE;
+use alkanes_support::cellpack::Cellpack;
+use alkanes_support::id::AlkaneId;
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use bitcoin::{Block, Witness};
+use metashrew_core::index_pointer::AtomicPointer;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use metashrew_support::index_pointer::KeyValuePointer;
+use protorune::test_helpers::create_block_with_coinbase_tx;
+use std::fmt::Write;
+
+use super::common::AmmTestDeploymentIds;
+use super::init_pools::test_amm_pool_init_fixture;
+
+pub const STABLE_POOL_AMP: u128 = 100;
+
+// deploys the stable pool logic behind its own beacon and registers it as a factory template
+pub fn init_stable_pool_template(
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) -> Result<Block> {
+    let block_height = 840_000;
+    let cellpack_pairs: Vec<BinaryAndCellpack> = [
+        BinaryAndCellpack {
+            binary: stable_pool_build::get_bytes(),
+            cellpack: Cellpack {
+                target: AlkaneId {
+                    block: 3,
+                    tx: deployment_ids.stable_pool_logic_impl.tx,
+                },
+                inputs: vec![50],
+            },
+        },
+        BinaryAndCellpack {
+            binary: alkanes_std_upgradeable_beacon_build::get_bytes(),
+            cellpack: Cellpack {
+                target: AlkaneId {
+                    block: 3,
+                    tx: deployment_ids.stable_pool_upgradeable_beacon.tx,
+                },
+                inputs: vec![
+                    0x7fff,
+                    deployment_ids.stable_pool_logic_impl.block,
+                    deployment_ids.stable_pool_logic_impl.tx,
+                    1,
+                ],
+            },
+        },
+        BinaryAndCellpack::cellpack_only(Cellpack {
+            target: deployment_ids.amm_factory_proxy,
+            inputs: vec![
+                8,
+                POOL_TEMPLATE_STABLE,
+                deployment_ids.stable_pool_upgradeable_beacon.block,
+                deployment_ids.stable_pool_upgradeable_beacon.tx,
+            ],
+        }),
+    ]
+    .into();
+    let test_block =
+        alkane_helpers::init_with_cellpack_pairs_w_input(cellpack_pairs, input_outpoint);
+    index_block(&test_block, block_height)?;
+    Ok(test_block)
+}
+
+pub fn create_stable_pool_txs(
+    token_a: AlkaneId,
+    token_b: AlkaneId,
+    amount_a: u128,
+    amount_b: u128,
+    amp: u128,
+    previous_output: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) -> Result<(Block, AlkaneId)> {
+    let block_height = 840_001;
+    let mut test_block = create_block_with_coinbase_tx(block_height);
+    let next_sequence_pointer = sequence_pointer(&mut AtomicPointer::default());
+    let pool_sequence = next_sequence_pointer.get_value::<u128>();
+    test_block
+        .txdata
+        .push(create_multiple_cellpack_with_witness_and_in(
+            Witness::new(),
+            vec![Cellpack {
+                target: deployment_ids.amm_factory_proxy,
+                inputs: vec![
+                    9,
+                    token_a.block,
+                    token_a.tx,
+                    token_b.block,
+                    token_b.tx,
+                    amount_a,
+                    amount_b,
+                    amp,
+                ],
+            }],
+            previous_output,
+            false,
+        ));
+    index_block(&test_block, block_height)?;
+    Ok((
+        test_block,
+        AlkaneId {
+            block: 2,
+            tx: pool_sequence,
+        },
+    ))
+}
+
+// sets up the regular amm fixture, registers the stable template and creates a
+// token 1 / token 3 stable pool, so token 2 is routed through constant product pools
+// and token 1 / token 3 through the stable pool
+pub fn test_stable_pool_init_fixture(
+    amount: u128,
+) -> Result<(Block, AmmTestDeploymentIds, AlkaneId)> {
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let template_block = init_stable_pool_template(
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    let (pool_block, pool) = create_stable_pool_txs(
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_3_deployment,
+        amount,
+        amount,
+        STABLE_POOL_AMP,
+        OutPoint {
+            txid: template_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    Ok((pool_block, deployment_ids, pool))
+}
@@ -0,0 +1,152 @@
+use crate::tests::std::staking_build;
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::{self as alkane_helpers, BinaryAndCellpack};
+use alkanes::vm::utils::sequence_pointer;
+use alkanes_support::cellpack::Cellpack;
+use alkanes_support::id::AlkaneId;
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use bitcoin::{Block, Witness};
+use metashrew_core::index_pointer::AtomicPointer;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use metashrew_support::index_pointer::KeyValuePointer;
+use protorune_support::protostone::ProtostoneEdict;
+use std::fmt::Write;
+
+use super::common::*;
+
+pub fn init_staking(
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) -> Result<Block> {
+    let block_height = 840_001;
+    let cellpack_pairs: Vec<BinaryAndCellpack> = [BinaryAndCellpack {
+        binary: staking_build::get_bytes(),
+        cellpack: Cellpack {
+            target: AlkaneId {
+                block: 3,
+                tx: deployment_ids.staking.tx,
+            },
+            inputs: vec![
+                0,
+                deployment_ids.amm_factory_proxy.block,
+                deployment_ids.amm_factory_proxy.tx,
+                deployment_ids.amm_factory_auth_token.block,
+                deployment_ids.amm_factory_auth_token.tx,
+            ],
+        },
+    }]
+    .into();
+    let test_block =
+        alkane_helpers::init_with_cellpack_pairs_w_input(cellpack_pairs, input_outpoint);
+    index_block(&test_block, block_height)?;
+    Ok(test_block)
+}
+
+// sets up an OYL reward programme for amm pool 1, sending the factory auth token and `funding`
+pub fn insert_set_reward_programme_txs(
+    funding: u128,
+    reward_per_block: u128,
+    end_height: u128,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) {
+    test_block.txdata.push(
+        create_multiple_cellpack_with_witness_and_in_with_edicts_and_leftovers(
+            Witness::new(),
+            vec![
+                CellpackOrEdict::Edict(vec![
+                    ProtostoneEdict {
+                        id: deployment_ids.amm_factory_auth_token.into(),
+                        amount: 1,
+                        output: 0,
+                    },
+                    ProtostoneEdict {
+                        id: deployment_ids.oyl_token_deployment.into(),
+                        amount: funding,
+                        output: 0,
+                    },
+                ]),
+                CellpackOrEdict::Cellpack(Cellpack {
+                    target: deployment_ids.staking,
+                    inputs: vec![
+                        1,
+                        deployment_ids.amm_pool_1_deployment.block,
+                        deployment_ids.amm_pool_1_deployment.tx,
+                        reward_per_block,
+                        end_height,
+                    ],
+                }),
+            ],
+            input_outpoint,
+            false,
+            true,
+        ),
+    );
+}
+
+// stakes lp tokens of amm pool 1 into a new position and returns the id of the position token
+// the staking contract will create
+pub fn insert_stake_txs(
+    amount: u128,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) -> AlkaneId {
+    let position_sequence = sequence_pointer(&mut AtomicPointer::default()).get_value::<u128>();
+    test_block.txdata.push(
+        create_multiple_cellpack_with_witness_and_in_with_edicts_and_leftovers(
+            Witness::new(),
+            vec![
+                CellpackOrEdict::Edict(vec![ProtostoneEdict {
+                    id: deployment_ids.amm_pool_1_deployment.into(),
+                    amount,
+                    output: 0,
+                }]),
+                CellpackOrEdict::Cellpack(Cellpack {
+                    target: deployment_ids.staking,
+                    inputs: vec![2],
+                }),
+            ],
+            input_outpoint,
+            false,
+            true,
+        ),
+    );
+    AlkaneId {
+        block: 2,
+        tx: position_sequence,
+    }
+}
+
+// sends the position token to the staking contract, e.g. vec![3, amount] to unstake or vec![4]
+// to harvest
+pub fn insert_staking_position_call_txs(
+    position_id: AlkaneId,
+    inputs: Vec<u128>,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) {
+    test_block.txdata.push(
+        create_multiple_cellpack_with_witness_and_in_with_edicts_and_leftovers(
+            Witness::new(),
+            vec![
+                CellpackOrEdict::Edict(vec![ProtostoneEdict {
+                    id: position_id.into(),
+                    amount: 1,
+                    output: 0,
+                }]),
+                CellpackOrEdict::Cellpack(Cellpack {
+                    target: deployment_ids.staking,
+                    inputs,
+                }),
+            ],
+            input_outpoint,
+            false,
+            true,
+        ),
+    );
+}
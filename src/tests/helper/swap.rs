@@ -79,6 +79,31 @@ pub fn insert_low_level_swap_txs(
     )
 }
 
+pub fn insert_flash_loan_txs(
+    input_edicts: Vec<ProtostoneEdict>,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    pool_address: AlkaneId,
+    amount_0: u128,
+    amount_1: u128,
+    receiver: AlkaneId,
+    data: Vec<u128>,
+) {
+    let mut inputs: Vec<u128> = vec![4, amount_0, amount_1];
+    inputs.append(&mut receiver.into());
+    inputs.push(data.len() as u128);
+    inputs.extend(data);
+    _insert_swap_txs(
+        input_edicts,
+        test_block,
+        input_outpoint,
+        Cellpack {
+            target: pool_address,
+            inputs,
+        },
+    )
+}
+
 pub fn _prepare_swap_tokens_for_exact_tokens_cellpack(
     swap_path: Vec<AlkaneId>,
     amount_out: u128,
@@ -98,6 +123,7 @@ pub fn _prepare_swap_tokens_for_exact_tokens_cellpack(
         .extend(swap_path.iter().flat_map(|s| vec![s.block, s.tx]));
     cellpack.inputs.push(amount_out);
     cellpack.inputs.push(amount_in_max);
+    cellpack.inputs.extend([0, 0]);
     cellpack.inputs.push(test_block.header.time as u128);
     cellpack
 }
@@ -154,6 +180,7 @@ fn _prepare_swap_exact_tokens_for_tokens_cellpack(
     amount: u128,
     swap_path: Vec<AlkaneId>,
     min_out: u128,
+    to: AlkaneId,
     deadline: u128,
     deployment_ids: &AmmTestDeploymentIds,
 ) -> Cellpack {
@@ -169,6 +196,7 @@ fn _prepare_swap_exact_tokens_for_tokens_cellpack(
         .extend(swap_path.iter().flat_map(|s| vec![s.block, s.tx]));
     cellpack.inputs.push(amount);
     cellpack.inputs.push(min_out);
+    cellpack.inputs.extend([to.block, to.tx]);
     cellpack.inputs.push(deadline);
     cellpack
 }
@@ -186,6 +214,7 @@ pub fn insert_swap_exact_tokens_for_tokens_deadline(
         amount,
         swap_path.clone(),
         min_out,
+        AlkaneId::new(0, 0),
         deadline,
         deployment_ids,
     );
@@ -214,6 +243,7 @@ pub fn insert_swap_exact_tokens_for_tokens_no_split(
         amount,
         swap_path.clone(),
         min_out,
+        AlkaneId::new(0, 0),
         u128::MAX,
         deployment_ids,
     );
@@ -240,6 +270,165 @@ pub fn insert_swap_exact_tokens_for_tokens(
     )
 }
 
+// the swap output goes to `to` instead of the caller
+pub fn insert_swap_exact_tokens_for_tokens_to(
+    amount: u128,
+    swap_path: Vec<AlkaneId>,
+    min_out: u128,
+    to: AlkaneId,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) {
+    let cellpack = _prepare_swap_exact_tokens_for_tokens_cellpack(
+        amount,
+        swap_path.clone(),
+        min_out,
+        to,
+        test_block.header.time as u128,
+        deployment_ids,
+    );
+
+    _insert_swap_txs(
+        vec![ProtostoneEdict {
+            id: swap_path[0].into(),
+            amount,
+            output: 0,
+        }],
+        test_block,
+        input_outpoint,
+        cellpack,
+    )
+}
+
+// the whole input, including the factory auth token, goes to the factory
+pub fn insert_set_route_token_txs(
+    token: AlkaneId,
+    enabled: bool,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) {
+    _insert_swap_txs_no_split(
+        test_block,
+        input_outpoint,
+        Cellpack {
+            target: deployment_ids.amm_factory_proxy,
+            inputs: vec![28, token.block, token.tx, enabled as u128],
+        },
+    )
+}
+
+pub fn insert_swap_exact_tokens_for_tokens_best_path(
+    amount: u128,
+    token_in: AlkaneId,
+    token_out: AlkaneId,
+    min_out: u128,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) {
+    let deadline = test_block.header.time as u128;
+    _insert_swap_txs(
+        vec![ProtostoneEdict {
+            id: token_in.into(),
+            amount,
+            output: 0,
+        }],
+        test_block,
+        input_outpoint,
+        Cellpack {
+            target: deployment_ids.amm_factory_proxy,
+            inputs: vec![
+                26,
+                token_in.block,
+                token_in.tx,
+                token_out.block,
+                token_out.tx,
+                amount,
+                min_out,
+                0,
+                0,
+                deadline,
+            ],
+        },
+    )
+}
+
+pub fn insert_swap_tokens_for_exact_tokens_best_path_txs(
+    amount: u128,
+    token_in: AlkaneId,
+    token_out: AlkaneId,
+    amount_out: u128,
+    amount_in_max: u128,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) {
+    let deadline = test_block.header.time as u128;
+    _insert_swap_txs(
+        vec![ProtostoneEdict {
+            id: token_in.into(),
+            amount,
+            output: 0,
+        }],
+        test_block,
+        input_outpoint,
+        Cellpack {
+            target: deployment_ids.amm_factory_proxy,
+            inputs: vec![
+                27,
+                token_in.block,
+                token_in.tx,
+                token_out.block,
+                token_out.tx,
+                amount_out,
+                amount_in_max,
+                0,
+                0,
+                deadline,
+            ],
+        },
+    )
+}
+
+// paths are flattened into one list, each ending in the output alkane
+pub fn insert_swap_exact_tokens_for_tokens_split(
+    amount: u128,
+    paths: Vec<Vec<AlkaneId>>,
+    shares: Vec<u128>,
+    min_out: u128,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) {
+    let token_in = paths[0][0];
+    let flat_paths: Vec<AlkaneId> = paths.into_iter().flatten().collect();
+    let mut cellpack = Cellpack {
+        target: deployment_ids.amm_factory_proxy,
+        inputs: vec![31, flat_paths.len() as u128],
+    };
+    cellpack
+        .inputs
+        .extend(flat_paths.iter().flat_map(|s| vec![s.block, s.tx]));
+    cellpack.inputs.push(shares.len() as u128);
+    cellpack.inputs.extend(shares);
+    cellpack.inputs.push(amount);
+    cellpack.inputs.push(min_out);
+    cellpack.inputs.extend([0, 0]);
+    cellpack.inputs.push(test_block.header.time as u128);
+    _insert_swap_txs(
+        vec![ProtostoneEdict {
+            id: token_in.into(),
+            amount,
+            output: 0,
+        }],
+        test_block,
+        input_outpoint,
+        cellpack,
+    )
+}
+
 fn calc_swapped_balance(amount: u128, reserve_from: u128, reserve_to: u128) -> Result<u128> {
     let amount_in_with_fee = (1000 - DEFAULT_TOTAL_FEE_AMOUNT_PER_1000) * amount;
     Ok((amount_in_with_fee * reserve_to) / (1000 * reserve_from + amount_in_with_fee))
@@ -0,0 +1,111 @@
+use crate::tests::std::twamm_build;
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::{self as alkane_helpers, BinaryAndCellpack};
+use alkanes::vm::utils::sequence_pointer;
+use alkanes_support::cellpack::Cellpack;
+use alkanes_support::id::AlkaneId;
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use bitcoin::{Block, Witness};
+use metashrew_core::index_pointer::AtomicPointer;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use metashrew_support::index_pointer::KeyValuePointer;
+use protorune_support::protostone::ProtostoneEdict;
+use std::fmt::Write;
+
+use super::common::*;
+
+// deploys the twamm for `pool`
+pub fn init_twamm(
+    pool: AlkaneId,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) -> Result<Block> {
+    let block_height = 840_001;
+    let cellpack_pairs: Vec<BinaryAndCellpack> = [BinaryAndCellpack {
+        binary: twamm_build::get_bytes(),
+        cellpack: Cellpack {
+            target: AlkaneId {
+                block: 3,
+                tx: deployment_ids.twamm.tx,
+            },
+            inputs: vec![
+                0,
+                deployment_ids.amm_factory_proxy.block,
+                deployment_ids.amm_factory_proxy.tx,
+                pool.block,
+                pool.tx,
+            ],
+        },
+    }]
+    .into();
+    let test_block =
+        alkane_helpers::init_with_cellpack_pairs_w_input(cellpack_pairs, input_outpoint);
+    index_block(&test_block, block_height)?;
+    Ok(test_block)
+}
+
+// returns the id of the order token the twamm will create for this order
+pub fn insert_place_long_term_order_txs(
+    amount_in: u128,
+    token_in: AlkaneId,
+    num_intervals: u128,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) -> AlkaneId {
+    let order_sequence = sequence_pointer(&mut AtomicPointer::default()).get_value::<u128>();
+    test_block.txdata.push(
+        create_multiple_cellpack_with_witness_and_in_with_edicts_and_leftovers(
+            Witness::new(),
+            vec![
+                CellpackOrEdict::Edict(vec![ProtostoneEdict {
+                    id: token_in.into(),
+                    amount: amount_in,
+                    output: 0,
+                }]),
+                CellpackOrEdict::Cellpack(Cellpack {
+                    target: deployment_ids.twamm,
+                    inputs: vec![2, num_intervals],
+                }),
+            ],
+            input_outpoint,
+            false,
+            true,
+        ),
+    );
+    AlkaneId {
+        block: 2,
+        tx: order_sequence,
+    }
+}
+
+// opcode 3 withdraws the proceeds so far, opcode 4 cancels the order
+pub fn insert_long_term_order_call_txs(
+    opcode: u128,
+    order_id: AlkaneId,
+    test_block: &mut Block,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) {
+    test_block.txdata.push(
+        create_multiple_cellpack_with_witness_and_in_with_edicts_and_leftovers(
+            Witness::new(),
+            vec![
+                CellpackOrEdict::Edict(vec![ProtostoneEdict {
+                    id: order_id.into(),
+                    amount: 1,
+                    output: 0,
+                }]),
+                CellpackOrEdict::Cellpack(Cellpack {
+                    target: deployment_ids.twamm,
+                    inputs: vec![opcode],
+                }),
+            ],
+            input_outpoint,
+            false,
+            true,
+        ),
+    );
+}
@@ -0,0 +1,276 @@
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::{
+    self as alkane_helpers, assert_revert_context, get_last_outpoint_sheet,
+};
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use init_pools::test_amm_pool_init_fixture;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use oylswap_library::{get_amount_out, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000};
+use protorune::test_helpers::create_block_with_coinbase_tx;
+use std::fmt::Write;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use crate::tests::helper::common::insert_pool_call_txs;
+use crate::tests::helper::limit_orders::{
+    init_limit_orders, insert_fill_order_txs, insert_place_order_txs, insert_withdraw_order_txs,
+};
+use crate::tests::helper::swap::insert_swap_exact_tokens_for_tokens;
+use crate::tests::helper::*;
+use alkane_helpers::clear;
+
+#[wasm_bindgen_test]
+fn test_limit_order_fill_once_price_crosses() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (1000000, 1000000);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let deploy_block = init_limit_orders(
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+
+    // ask for a bit more than the pool currently pays
+    let (amount_in, keeper_tip) = (10000, 10);
+    let amount_out_min = get_amount_out(
+        amount_in,
+        amount1,
+        amount2,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )? + 100;
+    let mut order_block = create_block_with_coinbase_tx(840_002);
+    let order_id = insert_place_order_txs(
+        amount_in,
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        amount_out_min,
+        keeper_tip,
+        &mut order_block,
+        OutPoint {
+            txid: deploy_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&order_block, 840_002)?;
+    let order_outpoint = OutPoint {
+        txid: order_block.txdata.last().unwrap().compute_txid(),
+        vout: 0,
+    };
+    assert_eq!(
+        get_last_outpoint_sheet(&order_block)?.get_cached(&order_id.into()),
+        1
+    );
+
+    let mut early_fill_block = create_block_with_coinbase_tx(840_003);
+    insert_fill_order_txs(
+        order_id,
+        &mut early_fill_block,
+        OutPoint {
+            txid: order_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+        &deployment_ids,
+    );
+    index_block(&early_fill_block, 840_003)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: early_fill_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        "ALKANES: revert: Error: predicate failed: insufficient output",
+    )?;
+
+    // buying token 1 pushes its price past the limit
+    let mut swap_block = create_block_with_coinbase_tx(840_004);
+    let amount_swapped = 50000;
+    insert_swap_exact_tokens_for_tokens(
+        amount_swapped,
+        vec![
+            deployment_ids.owned_token_2_deployment,
+            deployment_ids.owned_token_1_deployment,
+        ],
+        0,
+        &mut swap_block,
+        OutPoint {
+            txid: early_fill_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+        &deployment_ids,
+    );
+    index_block(&swap_block, 840_004)?;
+    let bought = get_amount_out(
+        amount_swapped,
+        amount2,
+        amount1,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+
+    let mut fill_block = create_block_with_coinbase_tx(840_005);
+    insert_fill_order_txs(
+        order_id,
+        &mut fill_block,
+        OutPoint {
+            txid: swap_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+        &deployment_ids,
+    );
+    index_block(&fill_block, 840_005)?;
+    assert_eq!(
+        get_last_outpoint_sheet(&fill_block)?
+            .get_cached(&deployment_ids.owned_token_2_deployment.into()),
+        keeper_tip
+    );
+
+    let mut withdraw_block = create_block_with_coinbase_tx(840_006);
+    insert_withdraw_order_txs(
+        order_id,
+        &mut withdraw_block,
+        order_outpoint,
+        &deployment_ids,
+    );
+    index_block(&withdraw_block, 840_006)?;
+    let amount_out = get_amount_out(
+        amount_in,
+        amount1 - bought,
+        amount2 + amount_swapped,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+    assert!(amount_out - keeper_tip >= amount_out_min);
+    assert_eq!(
+        get_last_outpoint_sheet(&withdraw_block)?
+            .get_cached(&deployment_ids.owned_token_2_deployment.into()),
+        amount_out - keeper_tip
+    );
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_limit_order_cancel() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let deploy_block = init_limit_orders(
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    let mut order_block = create_block_with_coinbase_tx(840_002);
+    let amount_in = 10000;
+    let order_id = insert_place_order_txs(
+        amount_in,
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        20000,
+        0,
+        &mut order_block,
+        OutPoint {
+            txid: deploy_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&order_block, 840_002)?;
+
+    let mut withdraw_block = create_block_with_coinbase_tx(840_003);
+    insert_withdraw_order_txs(
+        order_id,
+        &mut withdraw_block,
+        OutPoint {
+            txid: order_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&withdraw_block, 840_003)?;
+    let sheet = get_last_outpoint_sheet(&withdraw_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_1_deployment.into()),
+        amount_in
+    );
+    assert_eq!(sheet.get_cached(&order_id.into()), 0);
+
+    // the order is gone, so it can no longer be filled
+    let mut fill_block = create_block_with_coinbase_tx(840_004);
+    insert_fill_order_txs(
+        order_id,
+        &mut fill_block,
+        OutPoint {
+            txid: order_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+        &deployment_ids,
+    );
+    index_block(&fill_block, 840_004)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: fill_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        "ALKANES: revert: Error: order",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_limit_order_swap_callback_outside_of_fill_fails() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let deploy_block = init_limit_orders(
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    let mut order_block = create_block_with_coinbase_tx(840_002);
+    let order_id = insert_place_order_txs(
+        10000,
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        20000,
+        0,
+        &mut order_block,
+        OutPoint {
+            txid: deploy_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&order_block, 840_002)?;
+
+    // calling the swap callback directly, posing as the order book, must not pay out the order
+    let mut callback_block = create_block_with_coinbase_tx(840_003);
+    insert_pool_call_txs(
+        deployment_ids.limit_orders,
+        vec![
+            73776170,
+            deployment_ids.limit_orders.block,
+            deployment_ids.limit_orders.tx,
+            0,
+            0,
+            2,
+            order_id.block,
+            order_id.tx,
+        ],
+        &mut callback_block,
+        OutPoint {
+            txid: order_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+    );
+    index_block(&callback_block, 840_003)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: callback_block.txdata.last().unwrap().compute_txid(),
+            vout: 3,
+        },
+        "ALKANES: revert: Error: swap callback outside of a fill",
+    )?;
+    Ok(())
+}
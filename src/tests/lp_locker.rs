@@ -0,0 +1,199 @@
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::{
+    self as alkane_helpers, assert_revert_context, get_last_outpoint_sheet,
+};
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use init_pools::test_amm_pool_init_fixture;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use protorune::test_helpers::create_block_with_coinbase_tx;
+use std::fmt::Write;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use crate::tests::helper::common::{get_last_return_data, insert_pool_call_txs};
+use crate::tests::helper::lp_locker::{
+    init_lp_locker, insert_lock_lp_txs, insert_lock_txs, insert_redeem_lp_lock_txs,
+};
+use crate::tests::helper::*;
+use alkane_helpers::clear;
+
+#[wasm_bindgen_test]
+fn test_lp_lock_until_height() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let deploy_block = init_lp_locker(
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    let mut lock_block = create_block_with_coinbase_tx(840_002);
+    let amount_locked = 10000;
+    let receipt_id = insert_lock_lp_txs(
+        amount_locked,
+        840_010,
+        0,
+        &mut lock_block,
+        OutPoint {
+            txid: deploy_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&lock_block, 840_002)?;
+    let receipt_outpoint = OutPoint {
+        txid: lock_block.txdata.last().unwrap().compute_txid(),
+        vout: 0,
+    };
+    let sheet = get_last_outpoint_sheet(&lock_block)?;
+    assert_eq!(sheet.get_cached(&receipt_id.into()), 1);
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.amm_pool_1_deployment.into()),
+        0
+    );
+
+    let mut early_block = create_block_with_coinbase_tx(840_009);
+    insert_redeem_lp_lock_txs(
+        receipt_id,
+        &mut early_block,
+        receipt_outpoint,
+        &deployment_ids,
+    );
+    index_block(&early_block, 840_009)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: early_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        "ALKANES: revert: Error: nothing to redeem yet",
+    )?;
+
+    // the failed redeem refunded the receipt to the leftovers output
+    let mut redeem_block = create_block_with_coinbase_tx(840_010);
+    insert_redeem_lp_lock_txs(
+        receipt_id,
+        &mut redeem_block,
+        OutPoint {
+            txid: early_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+        &deployment_ids,
+    );
+    index_block(&redeem_block, 840_010)?;
+    let sheet = get_last_outpoint_sheet(&redeem_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.amm_pool_1_deployment.into()),
+        amount_locked
+    );
+    assert_eq!(sheet.get_cached(&receipt_id.into()), 0);
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_lp_lock_linear_vesting() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let deploy_block = init_lp_locker(
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    let mut lock_block = create_block_with_coinbase_tx(840_002);
+    let amount_locked = 10000;
+    let receipt_id = insert_lock_lp_txs(
+        amount_locked,
+        840_010,
+        840_020,
+        &mut lock_block,
+        OutPoint {
+            txid: deploy_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&lock_block, 840_002)?;
+
+    // half way through vesting half of the lock is released and the receipt comes back
+    let mut redeem_block = create_block_with_coinbase_tx(840_015);
+    insert_redeem_lp_lock_txs(
+        receipt_id,
+        &mut redeem_block,
+        OutPoint {
+            txid: lock_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&redeem_block, 840_015)?;
+    let sheet = get_last_outpoint_sheet(&redeem_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.amm_pool_1_deployment.into()),
+        amount_locked / 2
+    );
+    assert_eq!(sheet.get_cached(&receipt_id.into()), 1);
+
+    let mut total_locked_block = create_block_with_coinbase_tx(840_016);
+    insert_pool_call_txs(
+        deployment_ids.lp_locker,
+        vec![
+            4,
+            deployment_ids.amm_pool_1_deployment.block,
+            deployment_ids.amm_pool_1_deployment.tx,
+        ],
+        &mut total_locked_block,
+        OutPoint {
+            txid: redeem_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+    );
+    index_block(&total_locked_block, 840_016)?;
+    let data = get_last_return_data(&total_locked_block)?;
+    assert_eq!(
+        u128::from_le_bytes(data[0..16].try_into()?),
+        amount_locked / 2
+    );
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_lp_lock_non_pool_token_fail() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let deploy_block = init_lp_locker(
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    // a plain token would let anyone inflate GetTotalLocked without locking any liquidity
+    let mut lock_block = create_block_with_coinbase_tx(840_002);
+    insert_lock_txs(
+        deployment_ids.owned_token_1_deployment,
+        10000,
+        840_010,
+        0,
+        &mut lock_block,
+        OutPoint {
+            txid: deploy_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&lock_block, 840_002)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: lock_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        &format!(
+            "ALKANES: revert: Error: {:?} is not a pool of the factory",
+            deployment_ids.owned_token_1_deployment
+        ),
+    )?;
+    Ok(())
+}
@@ -2,8 +2,21 @@ pub mod add_liquidity;
 pub mod amm;
 pub mod attacks;
 pub mod burn;
+pub mod concentrated_pool;
+pub mod fee_tiers;
 pub mod fees;
+pub mod flash_loan;
 pub mod helper;
+pub mod limit_orders;
+pub mod lp_locker;
+pub mod oracle;
+pub mod pause;
 pub mod precision_loss;
+pub mod skim_sync;
+pub mod stable_pool;
+pub mod staking;
+pub mod stats;
 pub mod std;
 pub mod swap_tests;
+pub mod twamm;
+pub mod weighted_pool;
@@ -0,0 +1,409 @@
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::{
+    self as alkane_helpers, assert_revert_context, get_last_outpoint_sheet,
+};
+use alkanes_runtime_pool::PRECISION;
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use bitcoin::Block;
+use init_pools::test_amm_pool_init_fixture;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use oylswap_library::{
+    fixed_point::{average_price, UQ128x128},
+    get_amount_out,
+    oracle::ORACLE_CLOCK_CLAMPED_BLOCK_TIME,
+    DEFAULT_TOTAL_FEE_AMOUNT_PER_1000, U256,
+};
+use protorune::test_helpers::create_block_with_coinbase_tx;
+use protorune_support::balance_sheet::BalanceSheetOperations;
+use std::fmt::Write;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use crate::tests::helper::add_liquidity::{
+    check_add_liquidity_lp_balance, insert_add_liquidity_txs,
+};
+use crate::tests::helper::common::{
+    get_last_return_data, insert_pool_call_txs, AmmTestDeploymentIds,
+};
+use crate::tests::helper::oracle::{
+    get_consult_result, insert_consult_txs, insert_increase_observation_cardinality_txs,
+};
+use crate::tests::helper::swap::insert_swap_exact_tokens_for_tokens;
+use crate::tests::helper::*;
+use alkane_helpers::clear;
+
+// per second increase of the price 0 cumulative at the given reserves
+fn price_0(reserve_0: u128, reserve_1: u128) -> U256 {
+    UQ128x128::fraction(reserve_1, reserve_0).unwrap().0
+}
+
+fn insert_timed_swap(
+    block_height: u32,
+    time: u32,
+    input_outpoint: OutPoint,
+    deployment_ids: &AmmTestDeploymentIds,
+) -> Result<Block> {
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    swap_block.header.time = time;
+    insert_swap_exact_tokens_for_tokens(
+        10000,
+        vec![
+            deployment_ids.owned_token_1_deployment,
+            deployment_ids.owned_token_2_deployment,
+        ],
+        0,
+        &mut swap_block,
+        input_outpoint,
+        deployment_ids,
+    );
+    index_block(&swap_block, block_height)?;
+    Ok(swap_block)
+}
+
+#[wasm_bindgen_test]
+fn test_oracle_consult_extrapolates_latest_observation() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let start = init_block.header.time;
+    let swap_block = insert_timed_swap(
+        840_001,
+        start + 100,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+
+    let mut consult_block = create_block_with_coinbase_tx(840_002);
+    consult_block.header.time = start + 150;
+    insert_consult_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![0, 50],
+        &mut consult_block,
+        OutPoint {
+            txid: swap_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&consult_block, 840_002)?;
+
+    let amount_out = get_amount_out(10000, 1000000, 1000000, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000)?;
+    let observed = U256::from(100) << U256::from(PRECISION);
+    let result = get_consult_result(&consult_block)?;
+    assert_eq!(
+        result[0].0,
+        observed + price_0(1010000, 1000000 - amount_out) * U256::from(50)
+    );
+    assert_eq!(result[1], (observed, observed));
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_oracle_consult_before_oldest_observation_fail() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let start = init_block.header.time;
+    let swap_block = insert_timed_swap(
+        840_001,
+        start + 100,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+
+    // with the default cardinality only the latest observation is kept
+    let mut consult_block = create_block_with_coinbase_tx(840_002);
+    consult_block.header.time = start + 150;
+    insert_consult_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![100],
+        &mut consult_block,
+        OutPoint {
+            txid: swap_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&consult_block, 840_002)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: consult_block.txdata.last().unwrap().compute_txid(),
+            vout: 3,
+        },
+        "ALKANES: revert: Error: OLD",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_oracle_consult_interpolates_observations() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let start = init_block.header.time;
+    let mut cardinality_block = create_block_with_coinbase_tx(840_001);
+    cardinality_block.header.time = start;
+    insert_increase_observation_cardinality_txs(
+        deployment_ids.amm_pool_1_deployment,
+        10,
+        &mut cardinality_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&cardinality_block, 840_001)?;
+
+    let swap_block = insert_timed_swap(
+        840_002,
+        start + 100,
+        OutPoint {
+            txid: cardinality_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    let swap_block_2 = insert_timed_swap(
+        840_003,
+        start + 200,
+        OutPoint {
+            txid: swap_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+        &deployment_ids,
+    )?;
+
+    let mut consult_block = create_block_with_coinbase_tx(840_004);
+    consult_block.header.time = start + 300;
+    insert_consult_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![300, 250, 200, 150, 0],
+        &mut consult_block,
+        OutPoint {
+            txid: swap_block_2.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&consult_block, 840_004)?;
+
+    let fee = DEFAULT_TOTAL_FEE_AMOUNT_PER_1000;
+    let amount_out_1 = get_amount_out(10000, 1000000, 1000000, fee)?;
+    let (reserve_0, reserve_1) = (1010000, 1000000 - amount_out_1);
+    let amount_out_2 = get_amount_out(10000, reserve_0, reserve_1, fee)?;
+    let price_after_1 = price_0(reserve_0, reserve_1);
+    let price_after_2 = price_0(reserve_0 + 10000, reserve_1 - amount_out_2);
+    let one = U256::from(1) << U256::from(PRECISION);
+
+    let result = get_consult_result(&consult_block)?;
+    assert_eq!(result[0].0, U256::ZERO);
+    assert_eq!(result[1].0, one * U256::from(50));
+    assert_eq!(result[2].0, one * U256::from(100));
+    assert_eq!(
+        result[3].0,
+        one * U256::from(100) + price_after_1 * U256::from(50)
+    );
+    assert_eq!(
+        result[4].0,
+        one * U256::from(100) + price_after_1 * U256::from(100) + price_after_2 * U256::from(100)
+    );
+    // consumers turn two cumulatives into the average price over the window between them
+    let twap = average_price(result[2].0, result[3].0, 50)?;
+    assert_eq!(twap.0, price_after_1);
+    assert_eq!(twap.mul_decode(10000)?, 10000 * reserve_1 / reserve_0);
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_oracle_cardinality_above_max_fail() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let mut cardinality_block = create_block_with_coinbase_tx(840_001);
+    insert_increase_observation_cardinality_txs(
+        deployment_ids.amm_pool_1_deployment,
+        65536,
+        &mut cardinality_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&cardinality_block, 840_001)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: cardinality_block.txdata.last().unwrap().compute_txid(),
+            vout: 3,
+        },
+        "ALKANES: revert: Error: observation cardinality cannot exceed 65535",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_oracle_swap_with_decreasing_block_time() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let start = init_block.header.time;
+    let mut cardinality_block = create_block_with_coinbase_tx(840_001);
+    cardinality_block.header.time = start;
+    insert_increase_observation_cardinality_txs(
+        deployment_ids.amm_pool_1_deployment,
+        10,
+        &mut cardinality_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&cardinality_block, 840_001)?;
+
+    let swap_block = insert_timed_swap(
+        840_002,
+        start + 100,
+        OutPoint {
+            txid: cardinality_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    // the next block is timed before the previous one, the swap still goes through but accrues nothing
+    let swap_block_2 = insert_timed_swap(
+        840_003,
+        start + 50,
+        OutPoint {
+            txid: swap_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+        &deployment_ids,
+    )?;
+    let fee = DEFAULT_TOTAL_FEE_AMOUNT_PER_1000;
+    let amount_out_1 = get_amount_out(10000, 1000000, 1000000, fee)?;
+    let (reserve_0, reserve_1) = (1010000, 1000000 - amount_out_1);
+    let amount_out_2 = get_amount_out(10000, reserve_0, reserve_1, fee)?;
+    let sheet = get_last_outpoint_sheet(&swap_block_2)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_2_deployment.into()),
+        amount_out_2
+    );
+    let one = U256::from(1) << U256::from(PRECISION);
+
+    let mut consult_block = create_block_with_coinbase_tx(840_004);
+    consult_block.header.time = start + 60;
+    insert_consult_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![0, 100],
+        &mut consult_block,
+        OutPoint {
+            txid: swap_block_2.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&consult_block, 840_004)?;
+    let result = get_consult_result(&consult_block)?;
+    assert_eq!(result[0].0, one * U256::from(100));
+    assert_eq!(result[1].0, U256::ZERO);
+
+    let swap_block_3 = insert_timed_swap(
+        840_005,
+        start + 200,
+        OutPoint {
+            txid: swap_block_2.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+        &deployment_ids,
+    )?;
+    let mut consult_block = create_block_with_coinbase_tx(840_006);
+    consult_block.header.time = start + 200;
+    insert_consult_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![0, 150],
+        &mut consult_block,
+        OutPoint {
+            txid: swap_block_3.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&consult_block, 840_006)?;
+    let price_after_2 = price_0(reserve_0 + 10000, reserve_1 - amount_out_2);
+    let result = get_consult_result(&consult_block)?;
+    assert_eq!(
+        result[0].0,
+        one * U256::from(100) + price_after_2 * U256::from(100)
+    );
+    assert_eq!(result[1].0, one * U256::from(50));
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_oracle_add_liquidity_with_decreasing_block_time() -> Result<()> {
+    clear();
+    let amount = 1000000;
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount, amount)?;
+    let start = init_block.header.time;
+    let mut add_liquidity_block = create_block_with_coinbase_tx(840_001);
+    add_liquidity_block.header.time = start + 100;
+    insert_add_liquidity_txs(
+        amount,
+        amount,
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        deployment_ids.amm_pool_1_deployment,
+        &mut add_liquidity_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&add_liquidity_block, 840_001)?;
+
+    let mut add_liquidity_block_2 = create_block_with_coinbase_tx(840_002);
+    add_liquidity_block_2.header.time = start + 50;
+    insert_add_liquidity_txs(
+        amount,
+        amount,
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        deployment_ids.amm_pool_1_deployment,
+        &mut add_liquidity_block_2,
+        OutPoint {
+            txid: add_liquidity_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+    );
+    index_block(&add_liquidity_block_2, 840_002)?;
+    check_add_liquidity_lp_balance(
+        2 * amount,
+        2 * amount,
+        0,
+        amount,
+        amount,
+        2 * amount,
+        &add_liquidity_block_2,
+        deployment_ids.amm_pool_1_deployment,
+    )?;
+
+    // the pool clock stays at the latest block time it has seen
+    let mut clock_block = create_block_with_coinbase_tx(840_003);
+    clock_block.header.time = start + 10;
+    insert_pool_call_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![32],
+        &mut clock_block,
+        OutPoint {
+            txid: add_liquidity_block_2.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&clock_block, 840_003)?;
+    let data = get_last_return_data(&clock_block)?;
+    assert_eq!(
+        u128::from_le_bytes(data[0..16].try_into()?),
+        ORACLE_CLOCK_CLAMPED_BLOCK_TIME
+    );
+    assert_eq!(
+        u128::from_le_bytes(data[16..32].try_into()?),
+        (start + 100) as u128
+    );
+    Ok(())
+}
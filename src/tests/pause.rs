@@ -0,0 +1,201 @@
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::{self as alkane_helpers, assert_revert_context};
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use init_pools::{calc_lp_balance_from_pool_init, test_amm_pool_init_fixture};
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use protorune::test_helpers::create_block_with_coinbase_tx;
+use std::fmt::Write;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use crate::tests::helper::add_liquidity::insert_add_liquidity_txs;
+use crate::tests::helper::common::{
+    get_last_return_data, insert_pool_call_txs, insert_set_pool_paused_txs,
+};
+use crate::tests::helper::remove_liquidity::{check_burn_balances, insert_remove_liquidity_txs};
+use crate::tests::helper::swap::{check_swap_lp_balance, insert_swap_exact_tokens_for_tokens};
+use crate::tests::helper::*;
+use alkane_helpers::clear;
+
+#[wasm_bindgen_test]
+fn test_amm_pool_paused_swap_fail() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let block_height = 840_001;
+    let mut test_block = create_block_with_coinbase_tx(block_height);
+    insert_set_pool_paused_txs(
+        true,
+        &mut test_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    let input_outpoint = OutPoint {
+        txid: test_block.txdata.last().unwrap().compute_txid(),
+        vout: 0,
+    };
+    insert_swap_exact_tokens_for_tokens(
+        10000,
+        vec![
+            deployment_ids.owned_token_1_deployment,
+            deployment_ids.owned_token_2_deployment,
+        ],
+        0,
+        &mut test_block,
+        input_outpoint,
+        &deployment_ids,
+    );
+    index_block(&test_block, block_height)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: test_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        "ALKANES: revert: Error: PAUSED",
+    )?;
+
+    let mut paused_block = create_block_with_coinbase_tx(block_height + 1);
+    insert_pool_call_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![33],
+        &mut paused_block,
+        OutPoint {
+            txid: test_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&paused_block, block_height + 1)?;
+    let data = get_last_return_data(&paused_block)?;
+    assert_eq!(u128::from_le_bytes(data[0..16].try_into()?), 1);
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_paused_add_liquidity_fail() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let block_height = 840_001;
+    let mut test_block = create_block_with_coinbase_tx(block_height);
+    insert_set_pool_paused_txs(
+        true,
+        &mut test_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    let input_outpoint = OutPoint {
+        txid: test_block.txdata.last().unwrap().compute_txid(),
+        vout: 0,
+    };
+    insert_add_liquidity_txs(
+        10000,
+        10000,
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        deployment_ids.amm_pool_1_deployment,
+        &mut test_block,
+        input_outpoint,
+    );
+    index_block(&test_block, block_height)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: test_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        "ALKANES: revert: Error: PAUSED",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_paused_burn() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (1000000, 1000000);
+    let total_lp = calc_lp_balance_from_pool_init(amount1, amount2);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let block_height = 840_001;
+    let mut test_block = create_block_with_coinbase_tx(block_height);
+    insert_set_pool_paused_txs(
+        true,
+        &mut test_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    let input_outpoint = OutPoint {
+        txid: test_block.txdata.last().unwrap().compute_txid(),
+        vout: 0,
+    };
+    insert_remove_liquidity_txs(
+        total_lp,
+        &mut test_block,
+        input_outpoint,
+        deployment_ids.amm_pool_1_deployment,
+        false,
+    );
+    index_block(&test_block, block_height)?;
+    check_burn_balances(
+        &test_block,
+        total_lp,
+        total_lp,
+        amount1,
+        amount2,
+        &deployment_ids,
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_unpaused_swap() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (1000000, 1000000);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let block_height = 840_001;
+    let mut test_block = create_block_with_coinbase_tx(block_height);
+    insert_set_pool_paused_txs(
+        true,
+        &mut test_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    let input_outpoint = OutPoint {
+        txid: test_block.txdata.last().unwrap().compute_txid(),
+        vout: 0,
+    };
+    insert_set_pool_paused_txs(false, &mut test_block, input_outpoint, &deployment_ids);
+    let input_outpoint = OutPoint {
+        txid: test_block.txdata.last().unwrap().compute_txid(),
+        vout: 0,
+    };
+    let amount_to_swap = 10000;
+    insert_swap_exact_tokens_for_tokens(
+        amount_to_swap,
+        vec![
+            deployment_ids.owned_token_1_deployment,
+            deployment_ids.owned_token_2_deployment,
+        ],
+        0,
+        &mut test_block,
+        input_outpoint,
+        &deployment_ids,
+    );
+    index_block(&test_block, block_height)?;
+    check_swap_lp_balance(
+        vec![amount1, amount2],
+        amount_to_swap,
+        0,
+        deployment_ids.owned_token_2_deployment,
+        &test_block,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::{self as alkane_helpers, assert_revert_context};
+use alkanes_runtime_pool::PRECISION;
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use init_pools::test_amm_pool_init_fixture;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use oylswap_library::U256;
+use protorune::test_helpers::create_block_with_coinbase_tx;
+use std::fmt::Write;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use crate::tests::helper::common::{get_last_return_data, insert_pool_call_txs};
+use crate::tests::helper::*;
+use alkane_helpers::clear;
+
+#[wasm_bindgen_test]
+fn test_amm_pool_skim_without_excess() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let mut skim_block = create_block_with_coinbase_tx(840_001);
+    insert_pool_call_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![26, 0, 0],
+        &mut skim_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&skim_block, 840_001)?;
+    get_last_return_data(&skim_block)?;
+
+    let mut reserves_block = create_block_with_coinbase_tx(840_002);
+    insert_pool_call_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![97],
+        &mut reserves_block,
+        OutPoint {
+            txid: skim_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&reserves_block, 840_002)?;
+    let data = get_last_return_data(&reserves_block)?;
+    assert_eq!(u128::from_le_bytes(data[0..16].try_into()?), 1000000);
+    assert_eq!(u128::from_le_bytes(data[16..32].try_into()?), 1000000);
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_skim_to_pool_token_fail() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let mut skim_block = create_block_with_coinbase_tx(840_001);
+    insert_pool_call_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![
+            26,
+            deployment_ids.owned_token_1_deployment.block,
+            deployment_ids.owned_token_1_deployment.tx,
+        ],
+        &mut skim_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&skim_block, 840_001)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: skim_block.txdata.last().unwrap().compute_txid(),
+            vout: 3,
+        },
+        "ALKANES: revert: Error: INVALID_TO",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_sync_accrues_price_cumulative() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let mut sync_block = create_block_with_coinbase_tx(840_001);
+    sync_block.header.time = init_block.header.time + 100;
+    insert_pool_call_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![27],
+        &mut sync_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&sync_block, 840_001)?;
+
+    let mut price_block = create_block_with_coinbase_tx(840_002);
+    price_block.header.time = sync_block.header.time;
+    insert_pool_call_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![98],
+        &mut price_block,
+        OutPoint {
+            txid: sync_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&price_block, 840_002)?;
+    let data = get_last_return_data(&price_block)?;
+    let p0 = U256::from_le_bytes::<32>(data[0..32].try_into()?);
+    let p1 = U256::from_le_bytes::<32>(data[32..64].try_into()?);
+    assert_eq!(p0, U256::from(100) << U256::from(PRECISION));
+    assert_eq!(p1, U256::from(100) << U256::from(PRECISION));
+    Ok(())
+}
@@ -0,0 +1,284 @@
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::{
+    self as alkane_helpers, assert_id_points_to_alkane_id, assert_revert_context,
+    get_last_outpoint_sheet,
+};
+use alkanes_runtime_pool::MINIMUM_LIQUIDITY;
+use alkanes_support::id::AlkaneId;
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use oylswap_library::{stableswap, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000};
+use protorune::test_helpers::create_block_with_coinbase_tx;
+use protorune_support::balance_sheet::BalanceSheetOperations;
+use protorune_support::protostone::ProtostoneEdict;
+use std::fmt::Write;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use crate::tests::helper::common::{get_last_return_data, insert_pool_call_txs};
+use crate::tests::helper::stable_pool::*;
+use crate::tests::helper::swap::{
+    insert_low_level_swap_txs, insert_swap_exact_tokens_for_tokens,
+    insert_swap_tokens_for_exact_tokens_txs,
+};
+use alkane_helpers::clear;
+
+#[wasm_bindgen_test]
+fn test_stable_pool_init() -> Result<()> {
+    clear();
+    let amount = 1_000_000;
+    let (pool_block, deployment_ids, pool) = test_stable_pool_init_fixture(amount)?;
+    assert_id_points_to_alkane_id(pool, deployment_ids.pool_beacon_proxy)?;
+    // D of a balanced pool is the sum of its reserves
+    let sheet = get_last_outpoint_sheet(&pool_block)?;
+    assert_eq!(
+        sheet.get_cached(&pool.into()),
+        2 * amount - MINIMUM_LIQUIDITY
+    );
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_stable_pool_invalid_amp_fail() -> Result<()> {
+    clear();
+    let (pool_block, deployment_ids, _) = test_stable_pool_init_fixture(1_000_000)?;
+    let (fail_block, _) = create_stable_pool_txs(
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        1_000_000,
+        1_000_000,
+        0,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    assert_revert_context(
+        &OutPoint {
+            txid: fail_block.txdata.last().unwrap().compute_txid(),
+            vout: 3,
+        },
+        "ALKANES: revert: Error: amplification coefficient must be between 1 and 1000000",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_stable_pool_next_to_constant_product_pool() -> Result<()> {
+    clear();
+    let (pool_block, deployment_ids, _) = test_stable_pool_init_fixture(1_000_000)?;
+    // token 1 / token 2 already has a constant product pool
+    let (stable_block, stable_pool) = create_stable_pool_txs(
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        1_000_000,
+        1_000_000,
+        STABLE_POOL_AMP,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    assert_id_points_to_alkane_id(stable_pool, deployment_ids.pool_beacon_proxy)?;
+    assert_ne!(stable_pool, deployment_ids.amm_pool_1_deployment);
+
+    let mut find_block = create_block_with_coinbase_tx(840_002);
+    insert_pool_call_txs(
+        deployment_ids.amm_factory_proxy,
+        vec![
+            2,
+            deployment_ids.owned_token_1_deployment.block,
+            deployment_ids.owned_token_1_deployment.tx,
+            deployment_ids.owned_token_2_deployment.block,
+            deployment_ids.owned_token_2_deployment.tx,
+        ],
+        &mut find_block,
+        OutPoint {
+            txid: stable_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&find_block, 840_002)?;
+    let found: AlkaneId = get_last_return_data(&find_block)?.try_into()?;
+    assert_eq!(found, deployment_ids.amm_pool_1_deployment);
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_stable_pool_swap_exact_tokens_for_tokens() -> Result<()> {
+    clear();
+    let amount = 1_000_000;
+    let (pool_block, deployment_ids, _) = test_stable_pool_init_fixture(amount)?;
+    let block_height = 840_002;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    let amount_in = 100_000;
+    insert_swap_exact_tokens_for_tokens(
+        amount_in,
+        vec![
+            deployment_ids.owned_token_1_deployment,
+            deployment_ids.owned_token_3_deployment,
+        ],
+        0,
+        &mut swap_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&swap_block, block_height)?;
+
+    let expected = stableswap::get_amount_out(
+        amount_in,
+        amount,
+        amount,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+        STABLE_POOL_AMP,
+    )?;
+    let constant_product = oylswap_library::get_amount_out(
+        amount_in,
+        amount,
+        amount,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+    println!(
+        "stable out {} constant product out {}",
+        expected, constant_product
+    );
+    assert!(expected > constant_product);
+    let sheet = get_last_outpoint_sheet(&swap_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_3_deployment.into()),
+        expected
+    );
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_stable_pool_swap_tokens_for_exact_tokens() -> Result<()> {
+    clear();
+    let amount = 1_000_000;
+    let (pool_block, deployment_ids, _) = test_stable_pool_init_fixture(amount)?;
+    let block_height = 840_002;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    let (amount_to_swap, amount_out) = (100_000, 50_000);
+    let amount_in_required = stableswap::get_amount_in(
+        amount_out,
+        amount,
+        amount,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+        STABLE_POOL_AMP,
+    )?;
+    insert_swap_tokens_for_exact_tokens_txs(
+        amount_to_swap,
+        vec![
+            deployment_ids.owned_token_3_deployment,
+            deployment_ids.owned_token_1_deployment,
+        ],
+        amount_out,
+        amount_to_swap,
+        &mut swap_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&swap_block, block_height)?;
+
+    let sheet = get_last_outpoint_sheet(&swap_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_1_deployment.into()),
+        amount_out
+    );
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_3_deployment.into()),
+        amount_to_swap - amount_in_required
+    );
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_stable_pool_swap_multi_hop() -> Result<()> {
+    clear();
+    let amount = 1_000_000;
+    let (pool_block, deployment_ids, _) = test_stable_pool_init_fixture(amount)?;
+    let block_height = 840_002;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    let amount_in = 10_000;
+    // token 2 -> token 1 goes through a constant product pool, token 1 -> token 3 through the stable pool
+    insert_swap_exact_tokens_for_tokens(
+        amount_in,
+        vec![
+            deployment_ids.owned_token_2_deployment,
+            deployment_ids.owned_token_1_deployment,
+            deployment_ids.owned_token_3_deployment,
+        ],
+        0,
+        &mut swap_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&swap_block, block_height)?;
+
+    let first_hop = oylswap_library::get_amount_out(
+        amount_in,
+        1000000,
+        1000000,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+    let expected = stableswap::get_amount_out(
+        first_hop,
+        amount,
+        amount,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+        STABLE_POOL_AMP,
+    )?;
+    let sheet = get_last_outpoint_sheet(&swap_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_3_deployment.into()),
+        expected
+    );
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_stable_pool_low_level_swap_fail() -> Result<()> {
+    clear();
+    let (pool_block, deployment_ids, pool) = test_stable_pool_init_fixture(1_000_000)?;
+    let block_height = 840_002;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    // even near the peg a 1:1 swap takes out more than the curve allows once the fee is charged
+    insert_low_level_swap_txs(
+        vec![ProtostoneEdict {
+            id: deployment_ids.owned_token_1_deployment.into(),
+            amount: 10000,
+            output: 0,
+        }],
+        &mut swap_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        pool,
+        0,
+        10000,
+        AlkaneId::new(0, 0),
+        vec![],
+    );
+    index_block(&swap_block, block_height)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: swap_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        "ALKANES: revert: Error: D is not increasing",
+    )?;
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::{
+    self as alkane_helpers, assert_revert_context, get_last_outpoint_sheet,
+};
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use init_pools::test_amm_pool_init_fixture;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use protorune::test_helpers::create_block_with_coinbase_tx;
+use std::fmt::Write;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use crate::tests::helper::staking::{
+    init_staking, insert_set_reward_programme_txs, insert_stake_txs,
+    insert_staking_position_call_txs,
+};
+use crate::tests::helper::*;
+use alkane_helpers::clear;
+
+#[wasm_bindgen_test]
+fn test_staking_rewards_split_by_stake() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let deploy_block = init_staking(
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+
+    let reward_per_block = 1000;
+    let mut programme_block = create_block_with_coinbase_tx(840_002);
+    insert_set_reward_programme_txs(
+        reward_per_block * 100,
+        reward_per_block,
+        840_102,
+        &mut programme_block,
+        OutPoint {
+            txid: deploy_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&programme_block, 840_002)?;
+    let sheet = get_last_outpoint_sheet(&programme_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.amm_factory_auth_token.into()),
+        1
+    );
+
+    let (stake_a, stake_b) = (16384, 49152);
+    let mut stake_a_block = create_block_with_coinbase_tx(840_003);
+    let position_a = insert_stake_txs(
+        stake_a,
+        &mut stake_a_block,
+        OutPoint {
+            txid: programme_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+        &deployment_ids,
+    );
+    index_block(&stake_a_block, 840_003)?;
+    let sheet = get_last_outpoint_sheet(&stake_a_block)?;
+    assert_eq!(sheet.get_cached(&position_a.into()), 1);
+
+    let mut stake_b_block = create_block_with_coinbase_tx(840_005);
+    let position_b = insert_stake_txs(
+        stake_b,
+        &mut stake_b_block,
+        OutPoint {
+            txid: stake_a_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+        &deployment_ids,
+    );
+    index_block(&stake_b_block, 840_005)?;
+
+    // a had the pool to itself for two blocks and a quarter of it for four more
+    let mut unstake_block = create_block_with_coinbase_tx(840_009);
+    insert_staking_position_call_txs(
+        position_a,
+        vec![3, stake_a],
+        &mut unstake_block,
+        OutPoint {
+            txid: stake_a_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&unstake_block, 840_009)?;
+    let sheet = get_last_outpoint_sheet(&unstake_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.oyl_token_deployment.into()),
+        reward_per_block * 2 + reward_per_block * 4 / 4
+    );
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.amm_pool_1_deployment.into()),
+        stake_a
+    );
+    assert_eq!(sheet.get_cached(&position_a.into()), 0);
+
+    let mut harvest_block = create_block_with_coinbase_tx(840_010);
+    insert_staking_position_call_txs(
+        position_b,
+        vec![4],
+        &mut harvest_block,
+        OutPoint {
+            txid: stake_b_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&harvest_block, 840_010)?;
+    let expected = reward_per_block * 4 * 3 / 4 + reward_per_block;
+    let sheet = get_last_outpoint_sheet(&harvest_block)?;
+    let harvested = sheet.get_cached(&deployment_ids.oyl_token_deployment.into());
+    assert!(harvested <= expected && expected - harvested <= 1);
+    assert_eq!(sheet.get_cached(&position_b.into()), 1);
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.amm_pool_1_deployment.into()),
+        0
+    );
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_staking_programme_must_be_funded() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let deploy_block = init_staking(
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+
+    let mut programme_block = create_block_with_coinbase_tx(840_002);
+    insert_set_reward_programme_txs(
+        1000,
+        1000,
+        840_102,
+        &mut programme_block,
+        OutPoint {
+            txid: deploy_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&programme_block, 840_002)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: programme_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        "ALKANES: revert: Error: INSUFFICIENT_REWARDS",
+    )?;
+    Ok(())
+}
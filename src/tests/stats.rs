@@ -0,0 +1,104 @@
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::{self as alkane_helpers};
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use init_pools::test_amm_pool_init_fixture;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use oylswap_library::{
+    get_amount_out, PoolStats, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000, PROTOCOL_FEE_AMOUNT_PER_1000,
+};
+use protorune::test_helpers::create_block_with_coinbase_tx;
+use std::fmt::Write;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use crate::tests::helper::common::{get_last_return_data, insert_pool_call_txs};
+use crate::tests::helper::swap::insert_swap_exact_tokens_for_tokens;
+use crate::tests::helper::*;
+use alkane_helpers::clear;
+
+#[wasm_bindgen_test]
+fn test_amm_pool_stats() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (1000000, 1000000);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let block_height = 840_001;
+
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    let amount_in_0 = 10000;
+    insert_swap_exact_tokens_for_tokens(
+        amount_in_0,
+        vec![
+            deployment_ids.owned_token_1_deployment,
+            deployment_ids.owned_token_2_deployment,
+        ],
+        0,
+        &mut swap_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&swap_block, block_height)?;
+    let amount_out_1 = get_amount_out(
+        amount_in_0,
+        amount1,
+        amount2,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+
+    let mut swap_block_2 = create_block_with_coinbase_tx(block_height + 1);
+    let amount_in_1 = 5000;
+    insert_swap_exact_tokens_for_tokens(
+        amount_in_1,
+        vec![
+            deployment_ids.owned_token_2_deployment,
+            deployment_ids.owned_token_1_deployment,
+        ],
+        0,
+        &mut swap_block_2,
+        OutPoint {
+            txid: swap_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+        &deployment_ids,
+    );
+    index_block(&swap_block_2, block_height + 1)?;
+    let amount_out_0 = get_amount_out(
+        amount_in_1,
+        amount2 - amount_out_1,
+        amount1 + amount_in_0,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+
+    let mut stats_block = create_block_with_coinbase_tx(block_height + 2);
+    insert_pool_call_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![35],
+        &mut stats_block,
+        OutPoint {
+            txid: swap_block_2.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+    );
+    index_block(&stats_block, block_height + 2)?;
+    let stats = PoolStats::from_vec(&get_last_return_data(&stats_block)?)?;
+
+    let protocol_fees = [
+        amount_in_0 * PROTOCOL_FEE_AMOUNT_PER_1000 / 1000,
+        amount_in_1 * PROTOCOL_FEE_AMOUNT_PER_1000 / 1000,
+    ];
+    assert_eq!(stats.amount_in, [amount_in_0, amount_in_1]);
+    assert_eq!(stats.amount_out, [amount_out_0, amount_out_1]);
+    assert_eq!(
+        stats.lp_fees,
+        [
+            amount_in_0 * DEFAULT_TOTAL_FEE_AMOUNT_PER_1000 / 1000 - protocol_fees[0],
+            amount_in_1 * DEFAULT_TOTAL_FEE_AMOUNT_PER_1000 / 1000 - protocol_fees[1],
+        ]
+    );
+    assert_eq!(stats.protocol_fees, protocol_fees);
+    assert_eq!(stats.swap_count, 2);
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use alkanes::indexer::index_block;
 use alkanes::tests::helpers::{
-    self as alkane_helpers, assert_revert_context, get_last_outpoint_sheet,
+    self as alkane_helpers, assert_revert_context, get_last_outpoint_sheet, get_sheet_for_runtime,
 };
 use alkanes::view;
 use alkanes_runtime_pool::PRECISION;
@@ -13,7 +13,9 @@ use bitcoin::blockdata::transaction::OutPoint;
 use bitcoin::Witness;
 use init_pools::{calc_lp_balance_from_pool_init, test_amm_pool_init_fixture};
 use metashrew_support::byte_view::ByteView;
-use oylswap_library::{get_amount_in, StorableU256, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000, U256};
+use oylswap_library::{
+    get_amount_in, get_amount_out, StorableU256, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000, U256,
+};
 use protorune::test_helpers::create_block_with_coinbase_tx;
 use protorune_support::balance_sheet::BalanceSheetOperations;
 use protorune_support::protostone::ProtostoneEdict;
@@ -22,10 +24,14 @@ use wasm_bindgen_test::wasm_bindgen_test;
 use super::helper::swap::{
     check_swap_runtime_balance, insert_low_level_swap_txs, insert_swap_tokens_for_exact_tokens_txs,
 };
-use crate::tests::helper::common::{check_input_tokens_refunded, AmmTestDeploymentIds};
+use crate::tests::helper::common::{
+    check_input_tokens_refunded, get_last_return_data, insert_pool_call_txs, AmmTestDeploymentIds,
+};
 use crate::tests::helper::swap::{
-    check_swap_lp_balance, insert_swap_exact_tokens_for_tokens,
-    insert_swap_exact_tokens_for_tokens_deadline, insert_swap_exact_tokens_for_tokens_no_split,
+    check_swap_lp_balance, insert_set_route_token_txs, insert_swap_exact_tokens_for_tokens,
+    insert_swap_exact_tokens_for_tokens_best_path, insert_swap_exact_tokens_for_tokens_deadline,
+    insert_swap_exact_tokens_for_tokens_no_split, insert_swap_exact_tokens_for_tokens_split,
+    insert_swap_exact_tokens_for_tokens_to, insert_swap_tokens_for_exact_tokens_best_path_txs,
     insert_swap_tokens_for_exact_tokens_txs_no_split,
 };
 use crate::tests::helper::*;
@@ -891,6 +897,68 @@ fn test_amm_pool_swap_with_reentrancy_swap() -> Result<()> {
     Ok(())
 }
 
+// flash swaps out of pool 1 and calls `opcode` on it from the callback
+fn check_view_reentrancy_locked(opcode: u128) -> Result<()> {
+    clear();
+    let (amount1, amount2) = (500000, 500000);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let block_height = 840_001;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    let input_outpoint = OutPoint {
+        txid: init_block.txdata[init_block.txdata.len() - 1].compute_txid(),
+        vout: 0,
+    };
+
+    let swap_out = 10000;
+    let amount_fee_cover = DEFAULT_TOTAL_FEE_AMOUNT_PER_1000 * amount1 * swap_out
+        / ((1000 - DEFAULT_TOTAL_FEE_AMOUNT_PER_1000) * amount2
+            - (1000 - DEFAULT_TOTAL_FEE_AMOUNT_PER_1000)
+                * DEFAULT_TOTAL_FEE_AMOUNT_PER_1000
+                * swap_out
+                / 1000)
+        + 1;
+    insert_low_level_swap_txs(
+        vec![ProtostoneEdict {
+            id: deployment_ids.owned_token_1_deployment.into(),
+            amount: amount_fee_cover,
+            output: 0,
+        }],
+        &mut swap_block,
+        input_outpoint,
+        deployment_ids.amm_pool_1_deployment,
+        0,
+        swap_out,
+        deployment_ids.example_flashswap,
+        vec![2, deployment_ids.amm_pool_1_deployment.tx, opcode],
+    );
+
+    index_block(&swap_block, block_height)?;
+
+    let outpoint = OutPoint {
+        txid: swap_block.txdata[swap_block.txdata.len() - 1].compute_txid(),
+        vout: 5,
+    };
+
+    assert_revert_context(&outpoint, "ALKANES: revert: Error: LOCKED")?;
+
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_swap_with_reentrancy_get_reserves() -> Result<()> {
+    check_view_reentrancy_locked(97)
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_swap_with_reentrancy_get_price_cumulative_last() -> Result<()> {
+    check_view_reentrancy_locked(98)
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_swap_with_reentrancy_pool_details() -> Result<()> {
+    check_view_reentrancy_locked(999)
+}
+
 #[wasm_bindgen_test]
 fn test_amm_pool_swap_tokens_for_exact_no_split() -> Result<()> {
     clear();
@@ -1430,3 +1498,434 @@ fn test_amm_price_swap_2() -> Result<()> {
 
     Ok(())
 }
+
+#[wasm_bindgen_test]
+fn test_amm_pool_swap_updates_stored_reserves() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(1000000, 1000000)?;
+    let block_height = 840_001;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    insert_swap_exact_tokens_for_tokens(
+        10000,
+        vec![
+            deployment_ids.owned_token_1_deployment,
+            deployment_ids.owned_token_2_deployment,
+        ],
+        0,
+        &mut swap_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&swap_block, block_height)?;
+
+    let mut reserves_block = create_block_with_coinbase_tx(block_height + 1);
+    insert_pool_call_txs(
+        deployment_ids.amm_pool_1_deployment,
+        vec![97],
+        &mut reserves_block,
+        OutPoint {
+            txid: swap_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&reserves_block, block_height + 1)?;
+    let amount_out = oylswap_library::get_amount_out(
+        10000,
+        1000000,
+        1000000,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+    let data = get_last_return_data(&reserves_block)?;
+    assert_eq!(u128::from_le_bytes(data[0..16].try_into()?), 1010000);
+    assert_eq!(
+        u128::from_le_bytes(data[16..32].try_into()?),
+        1000000 - amount_out
+    );
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_swap_best_path_through_route_token() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (500000, 500000);
+    let (init_block, mut runtime_balances, deployment_ids) =
+        test_amm_pool_init_fixture(amount1, amount2)?;
+    let mut route_block = create_block_with_coinbase_tx(840_001);
+    insert_set_route_token_txs(
+        deployment_ids.owned_token_2_deployment,
+        true,
+        &mut route_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&route_block, 840_001)?;
+
+    // there is no direct pool between token 1 and token 3
+    let block_height = 840_002;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    let amount_to_swap = 10000;
+    insert_swap_exact_tokens_for_tokens_best_path(
+        amount_to_swap,
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_3_deployment,
+        0,
+        &mut swap_block,
+        OutPoint {
+            txid: route_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&swap_block, block_height)?;
+
+    check_swap_lp_balance(
+        vec![amount1, amount2, amount2],
+        amount_to_swap,
+        0,
+        deployment_ids.owned_token_3_deployment,
+        &swap_block,
+    )?;
+    check_swap_runtime_balance(
+        vec![amount1, amount2, amount2],
+        &mut runtime_balances,
+        amount_to_swap,
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_3_deployment,
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_swap_tokens_for_exact_best_path() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (500000, 500000);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let mut route_block = create_block_with_coinbase_tx(840_001);
+    insert_set_route_token_txs(
+        deployment_ids.owned_token_2_deployment,
+        true,
+        &mut route_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&route_block, 840_001)?;
+
+    let block_height = 840_002;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    let (amount_to_send, amount_out) = (10000, 5000);
+    insert_swap_tokens_for_exact_tokens_best_path_txs(
+        amount_to_send,
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_3_deployment,
+        amount_out,
+        amount_to_send,
+        &mut swap_block,
+        OutPoint {
+            txid: route_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&swap_block, block_height)?;
+
+    let middle_amount = get_amount_in(
+        amount_out,
+        amount2,
+        amount2,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+    let amount_in = get_amount_in(
+        middle_amount,
+        amount1,
+        amount2,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+    let sheet = get_last_outpoint_sheet(&swap_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_3_deployment.into()),
+        amount_out
+    );
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_1_deployment.into()),
+        amount_to_send - amount_in
+    );
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_swap_best_path_without_route() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(500000, 500000)?;
+    let block_height = 840_001;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    insert_swap_exact_tokens_for_tokens_best_path(
+        10000,
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_3_deployment,
+        0,
+        &mut swap_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&swap_block, block_height)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: swap_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        "ALKANES: revert: Error: no route from",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_swap_split_across_paths() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (500000, 500000);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let block_height = 840_001;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    let path = vec![
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+    ];
+    insert_swap_exact_tokens_for_tokens_split(
+        10001,
+        vec![path.clone(), path],
+        vec![5000, 5000],
+        0,
+        &mut swap_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&swap_block, block_height)?;
+
+    // both halves go through the same pool, the second one at the reserves left by the first,
+    // and the last path takes the rounding remainder
+    let first_out = get_amount_out(5000, amount1, amount2, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000)?;
+    let second_out = get_amount_out(
+        5001,
+        amount1 + 5000,
+        amount2 - first_out,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+    let sheet = get_last_outpoint_sheet(&swap_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_2_deployment.into()),
+        first_out + second_out
+    );
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_1_deployment.into()),
+        0
+    );
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_swap_split_shares_must_add_up() -> Result<()> {
+    clear();
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(500000, 500000)?;
+    let block_height = 840_001;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    let path = vec![
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+    ];
+    insert_swap_exact_tokens_for_tokens_split(
+        10000,
+        vec![path.clone(), path],
+        vec![5000, 4000],
+        0,
+        &mut swap_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&swap_block, block_height)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: swap_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        "ALKANES: revert: Error: shares must add up to 10000, got 9000",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_factory_get_amounts_out_quote() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (500000, 500000);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let block_height = 840_001;
+    let mut quote_block = create_block_with_coinbase_tx(block_height);
+    let amount_in = 10000;
+    let path = vec![
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        deployment_ids.owned_token_3_deployment,
+    ];
+    let mut inputs = vec![32, amount_in, path.len() as u128];
+    inputs.extend(path.iter().flat_map(|id| vec![id.block, id.tx]));
+    insert_pool_call_txs(
+        deployment_ids.amm_factory_proxy,
+        inputs,
+        &mut quote_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&quote_block, block_height)?;
+
+    let data = get_last_return_data(&quote_block)?;
+    let value = |offset: usize| -> Result<u128> {
+        Ok(u128::from_le_bytes(data[offset..offset + 16].try_into()?))
+    };
+    let middle_out = get_amount_out(
+        amount_in,
+        amount1,
+        amount2,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+    let final_out = get_amount_out(
+        middle_out,
+        amount2,
+        amount2,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+    assert_eq!(value(0)?, 3);
+    assert_eq!(value(16)?, amount_in);
+    assert_eq!(value(32)?, middle_out);
+    assert_eq!(value(48)?, final_out);
+
+    // each hop is a pool id, the fee in the hop's input and the price impact per million
+    let hops = [
+        (deployment_ids.amm_pool_1_deployment, amount_in, middle_out),
+        (deployment_ids.amm_pool_2_deployment, middle_out, final_out),
+    ];
+    for (i, (pool, hop_in, hop_out)) in hops.into_iter().enumerate() {
+        let offset = 64 + i * 64;
+        assert_eq!(AlkaneId::new(value(offset)?, value(offset + 16)?), pool);
+        assert_eq!(
+            value(offset + 32)?,
+            hop_in * DEFAULT_TOTAL_FEE_AMOUNT_PER_1000 / 1000
+        );
+        // the reserves of both pools are balanced, so the marginal price is one
+        let spot_out = hop_in * (1000 - DEFAULT_TOTAL_FEE_AMOUNT_PER_1000);
+        assert_eq!(
+            value(offset + 48)?,
+            (spot_out - hop_out * 1000) * 1_000_000 / spot_out
+        );
+    }
+    assert!(value(64 + 48)? > 0);
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_factory_get_amounts_in_quote() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (500000, 500000);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let block_height = 840_001;
+    let mut quote_block = create_block_with_coinbase_tx(block_height);
+    let amount_out = 5000;
+    insert_pool_call_txs(
+        deployment_ids.amm_factory_proxy,
+        vec![
+            33,
+            amount_out,
+            2,
+            deployment_ids.owned_token_1_deployment.block,
+            deployment_ids.owned_token_1_deployment.tx,
+            deployment_ids.owned_token_2_deployment.block,
+            deployment_ids.owned_token_2_deployment.tx,
+        ],
+        &mut quote_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&quote_block, block_height)?;
+
+    let data = get_last_return_data(&quote_block)?;
+    let amount_in = get_amount_in(
+        amount_out,
+        amount1,
+        amount2,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+    assert_eq!(u128::from_le_bytes(data[0..16].try_into()?), 2);
+    assert_eq!(u128::from_le_bytes(data[16..32].try_into()?), amount_in);
+    assert_eq!(u128::from_le_bytes(data[32..48].try_into()?), amount_out);
+    assert_eq!(
+        AlkaneId::new(
+            u128::from_le_bytes(data[48..64].try_into()?),
+            u128::from_le_bytes(data[64..80].try_into()?)
+        ),
+        deployment_ids.amm_pool_1_deployment
+    );
+    assert_eq!(
+        u128::from_le_bytes(data[80..96].try_into()?),
+        amount_in * DEFAULT_TOTAL_FEE_AMOUNT_PER_1000 / 1000
+    );
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_amm_pool_swap_to_recipient() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (500000, 500000);
+    let (init_block, mut runtime_balances, deployment_ids) =
+        test_amm_pool_init_fixture(amount1, amount2)?;
+    let block_height = 840_001;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    let amount_to_swap = 10000;
+    insert_swap_exact_tokens_for_tokens_to(
+        amount_to_swap,
+        vec![
+            deployment_ids.owned_token_1_deployment,
+            deployment_ids.owned_token_2_deployment,
+        ],
+        0,
+        deployment_ids.example_flashswap,
+        &mut swap_block,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&swap_block, block_height)?;
+
+    // nothing comes back to the caller, the output stays with the recipient contract
+    let sheet = get_last_outpoint_sheet(&swap_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_2_deployment.into()),
+        0
+    );
+    runtime_balances.increase(
+        &deployment_ids.owned_token_1_deployment.into(),
+        amount_to_swap,
+    );
+    assert_eq!(get_sheet_for_runtime(), runtime_balances.clone());
+    Ok(())
+}
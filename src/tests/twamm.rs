@@ -0,0 +1,357 @@
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::{
+    self as alkane_helpers, assert_revert_context, get_last_outpoint_sheet,
+};
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use init_pools::test_amm_pool_init_fixture;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use oylswap_library::{get_amount_out, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000};
+use protorune::test_helpers::create_block_with_coinbase_tx;
+use std::fmt::Write;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use crate::tests::helper::stable_pool::test_stable_pool_init_fixture;
+use crate::tests::helper::twamm::{
+    init_twamm, insert_long_term_order_call_txs, insert_place_long_term_order_txs,
+};
+use crate::tests::helper::*;
+use alkane_helpers::clear;
+
+// twamm::ORDER_BLOCK_INTERVAL, the contract is only linked into the tests as a wasm binary
+const ORDER_BLOCK_INTERVAL: u128 = 10;
+
+#[wasm_bindgen_test]
+fn test_twamm_order_executes_over_blocks() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (1000000, 1000000);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let deploy_block = init_twamm(
+        deployment_ids.amm_pool_1_deployment,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+
+    let mut order_block = create_block_with_coinbase_tx(840_002);
+    let (amount_in, num_intervals) = (100000, 5);
+    let order_id = insert_place_long_term_order_txs(
+        amount_in,
+        deployment_ids.owned_token_1_deployment,
+        num_intervals,
+        &mut order_block,
+        OutPoint {
+            txid: deploy_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&order_block, 840_002)?;
+
+    // the order sells evenly until the interval boundary it expires on
+    let expiry = (840_002 / ORDER_BLOCK_INTERVAL + num_intervals) * ORDER_BLOCK_INTERVAL;
+    let sale_rate = amount_in / (expiry - 840_002);
+    let amount_sold = sale_rate * (expiry - 840_002);
+    let sheet = get_last_outpoint_sheet(&order_block)?;
+    assert_eq!(sheet.get_cached(&order_id.into()), 1);
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_1_deployment.into()),
+        amount_in - amount_sold
+    );
+
+    let withdraw_height = expiry as u32 + 10;
+    let mut withdraw_block = create_block_with_coinbase_tx(withdraw_height);
+    insert_long_term_order_call_txs(
+        3,
+        order_id,
+        &mut withdraw_block,
+        OutPoint {
+            txid: order_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&withdraw_block, withdraw_height)?;
+
+    // nobody called ExecuteVirtualOrders in between, so everything is settled in one swap. The
+    // test blocks share a header time, so there is no TWAP the swap is held to
+    let amount_out = get_amount_out(
+        amount_sold,
+        amount1,
+        amount2,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+    let sheet = get_last_outpoint_sheet(&withdraw_block)?;
+    let proceeds = sheet.get_cached(&deployment_ids.owned_token_2_deployment.into());
+    assert!(proceeds <= amount_out && amount_out - proceeds <= 1);
+    // the order is done, so the order token is not returned
+    assert_eq!(sheet.get_cached(&order_id.into()), 0);
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_twamm_cancel_order() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (1000000, 1000000);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let deploy_block = init_twamm(
+        deployment_ids.amm_pool_1_deployment,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+
+    let mut order_block = create_block_with_coinbase_tx(840_002);
+    let (amount_in, num_intervals) = (100000, 5);
+    let order_id = insert_place_long_term_order_txs(
+        amount_in,
+        deployment_ids.owned_token_1_deployment,
+        num_intervals,
+        &mut order_block,
+        OutPoint {
+            txid: deploy_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&order_block, 840_002)?;
+    let expiry = (840_002 / ORDER_BLOCK_INTERVAL + num_intervals) * ORDER_BLOCK_INTERVAL;
+    let sale_rate = amount_in / (expiry - 840_002);
+
+    let cancel_height = 840_026;
+    let mut cancel_block = create_block_with_coinbase_tx(cancel_height);
+    insert_long_term_order_call_txs(
+        4,
+        order_id,
+        &mut cancel_block,
+        OutPoint {
+            txid: order_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&cancel_block, cancel_height)?;
+
+    // what was sold so far is paid out and the rest is refunded
+    let amount_sold = sale_rate * (cancel_height as u128 - 840_002);
+    let amount_out = get_amount_out(
+        amount_sold,
+        amount1,
+        amount2,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+    let sheet = get_last_outpoint_sheet(&cancel_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_1_deployment.into()),
+        sale_rate * (expiry - cancel_height as u128)
+    );
+    let proceeds = sheet.get_cached(&deployment_ids.owned_token_2_deployment.into());
+    assert!(proceeds <= amount_out && amount_out - proceeds <= 1);
+    assert_eq!(sheet.get_cached(&order_id.into()), 0);
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_twamm_settlement_below_twap_keeps_order_open() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (1000000, 1000000);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let deploy_block = init_twamm(
+        deployment_ids.amm_pool_1_deployment,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+
+    let mut order_block = create_block_with_coinbase_tx(840_002);
+    let (amount_in, num_intervals) = (100000, 5);
+    let order_id = insert_place_long_term_order_txs(
+        amount_in,
+        deployment_ids.owned_token_1_deployment,
+        num_intervals,
+        &mut order_block,
+        OutPoint {
+            txid: deploy_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&order_block, 840_002)?;
+    let expiry = (840_002 / ORDER_BLOCK_INTERVAL + num_intervals) * ORDER_BLOCK_INTERVAL;
+    let sale_rate = amount_in / (expiry - 840_002);
+
+    // without a keeper the whole order is sold in one swap, whose price impact puts it well
+    // below the pool's TWAP, so the settlement fails and the order is still open
+    let withdraw_height = expiry as u32 + 10;
+    let mut withdraw_block = create_block_with_coinbase_tx(withdraw_height);
+    withdraw_block.header.time = order_block.header.time + 600;
+    insert_long_term_order_call_txs(
+        3,
+        order_id,
+        &mut withdraw_block,
+        OutPoint {
+            txid: order_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&withdraw_block, withdraw_height)?;
+    let sheet = get_last_outpoint_sheet(&withdraw_block)?;
+    assert_eq!(sheet.get_cached(&order_id.into()), 1);
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_2_deployment.into()),
+        0
+    );
+
+    // nothing was sold, so cancelling refunds all of it
+    let cancel_height = withdraw_height + 1;
+    let mut cancel_block = create_block_with_coinbase_tx(cancel_height);
+    cancel_block.header.time = withdraw_block.header.time + 600;
+    insert_long_term_order_call_txs(
+        4,
+        order_id,
+        &mut cancel_block,
+        OutPoint {
+            txid: withdraw_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&cancel_block, cancel_height)?;
+    let sheet = get_last_outpoint_sheet(&cancel_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_1_deployment.into()),
+        sale_rate * (expiry - 840_002)
+    );
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_2_deployment.into()),
+        0
+    );
+    assert_eq!(sheet.get_cached(&order_id.into()), 0);
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_twamm_init_on_stable_pool_fail() -> Result<()> {
+    clear();
+    let (pool_block, deployment_ids, stable_pool) = test_stable_pool_init_fixture(1_000_000)?;
+    let deploy_block = init_twamm(
+        stable_pool,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    assert_revert_context(
+        &OutPoint {
+            txid: deploy_block.txdata.last().unwrap().compute_txid(),
+            vout: 3,
+        },
+        "ALKANES: revert: Error: twamm only supports constant product pools",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_twamm_blocked_direction_does_not_hold_up_the_other() -> Result<()> {
+    clear();
+    let (amount1, amount2) = (1000000, 1000000);
+    let (init_block, _, deployment_ids) = test_amm_pool_init_fixture(amount1, amount2)?;
+    let deploy_block = init_twamm(
+        deployment_ids.amm_pool_1_deployment,
+        OutPoint {
+            txid: init_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+
+    // a large order selling token 1, whose settlement in one swap is below the TWAP
+    let mut order_block = create_block_with_coinbase_tx(840_002);
+    insert_place_long_term_order_txs(
+        100000,
+        deployment_ids.owned_token_1_deployment,
+        5,
+        &mut order_block,
+        OutPoint {
+            txid: deploy_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&order_block, 840_002)?;
+
+    // and a small one selling token 2
+    let mut small_order_block = create_block_with_coinbase_tx(840_003);
+    let (amount_in, num_intervals) = (1000, 5);
+    let small_order_id = insert_place_long_term_order_txs(
+        amount_in,
+        deployment_ids.owned_token_2_deployment,
+        num_intervals,
+        &mut small_order_block,
+        OutPoint {
+            txid: order_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+        &deployment_ids,
+    );
+    index_block(&small_order_block, 840_003)?;
+    let expiry = (840_003 / ORDER_BLOCK_INTERVAL + num_intervals) * ORDER_BLOCK_INTERVAL;
+    let amount_sold = amount_in / (expiry - 840_003) * (expiry - 840_003);
+
+    // selling token 1 cannot be settled any more, selling token 2 still takes orders
+    let place_height = expiry as u32 + 10;
+    let mut place_block = create_block_with_coinbase_tx(place_height);
+    place_block.header.time = small_order_block.header.time + 600;
+    let new_order_id = insert_place_long_term_order_txs(
+        amount_in,
+        deployment_ids.owned_token_2_deployment,
+        1,
+        &mut place_block,
+        OutPoint {
+            txid: small_order_block.txdata.last().unwrap().compute_txid(),
+            vout: 2,
+        },
+        &deployment_ids,
+    );
+    index_block(&place_block, place_height)?;
+    assert_eq!(
+        get_last_outpoint_sheet(&place_block)?.get_cached(&new_order_id.into()),
+        1
+    );
+
+    // and the small order was settled in full, against the reserves the large one left alone
+    let withdraw_height = place_height + 1;
+    let mut withdraw_block = create_block_with_coinbase_tx(withdraw_height);
+    withdraw_block.header.time = place_block.header.time + 600;
+    insert_long_term_order_call_txs(
+        3,
+        small_order_id,
+        &mut withdraw_block,
+        OutPoint {
+            txid: small_order_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&withdraw_block, withdraw_height)?;
+    let amount_out = get_amount_out(
+        amount_sold,
+        amount2,
+        amount1,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+    )?;
+    let sheet = get_last_outpoint_sheet(&withdraw_block)?;
+    let proceeds = sheet.get_cached(&deployment_ids.owned_token_1_deployment.into());
+    assert!(proceeds <= amount_out && amount_out - proceeds <= 1);
+    assert_eq!(sheet.get_cached(&small_order_id.into()), 0);
+    Ok(())
+}
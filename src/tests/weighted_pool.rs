@@ -0,0 +1,219 @@
+use alkanes::indexer::index_block;
+use alkanes::tests::helpers::{
+    self as alkane_helpers, assert_id_points_to_alkane_id, assert_revert_context,
+    get_last_outpoint_sheet,
+};
+use alkanes_runtime_pool::{MINIMUM_LIQUIDITY, PRECISION};
+use alkanes_support::id::AlkaneId;
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+#[allow(unused_imports)]
+use metashrew_core::{get_cache, index_pointer::IndexPointer, println, stdio::stdout};
+use oylswap_library::{weighted, DEFAULT_TOTAL_FEE_AMOUNT_PER_1000, U256};
+use protorune::test_helpers::create_block_with_coinbase_tx;
+use protorune_support::balance_sheet::BalanceSheetOperations;
+use protorune_support::protostone::ProtostoneEdict;
+use std::fmt::Write;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use crate::tests::helper::oracle::{get_consult_result, insert_consult_txs};
+use crate::tests::helper::swap::{
+    insert_low_level_swap_txs, insert_swap_exact_tokens_for_tokens,
+    insert_swap_tokens_for_exact_tokens_txs,
+};
+use crate::tests::helper::weighted_pool::*;
+use alkane_helpers::clear;
+
+#[wasm_bindgen_test]
+fn test_weighted_pool_init() -> Result<()> {
+    clear();
+    let amount = 1_000_000;
+    let (pool_block, deployment_ids, pool) = test_weighted_pool_init_fixture(amount)?;
+    assert_id_points_to_alkane_id(pool, deployment_ids.pool_beacon_proxy)?;
+    let invariant: u128 = weighted::get_invariant(
+        amount * 4,
+        amount,
+        WEIGHTED_POOL_WEIGHT_A,
+        weighted::WEIGHT_DENOMINATOR - WEIGHTED_POOL_WEIGHT_A,
+    )?
+    .try_into()?;
+    let sheet = get_last_outpoint_sheet(&pool_block)?;
+    assert_eq!(
+        sheet.get_cached(&pool.into()),
+        invariant - MINIMUM_LIQUIDITY
+    );
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_weighted_pool_invalid_weights_fail() -> Result<()> {
+    clear();
+    let (pool_block, deployment_ids, _) = test_weighted_pool_init_fixture(1_000_000)?;
+    let (fail_block, _) = create_weighted_pool_txs(
+        deployment_ids.owned_token_1_deployment,
+        deployment_ids.owned_token_2_deployment,
+        1_000_000,
+        1_000_000,
+        995,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    )?;
+    assert_revert_context(
+        &OutPoint {
+            txid: fail_block.txdata.last().unwrap().compute_txid(),
+            vout: 3,
+        },
+        "ALKANES: revert: Error: weights must each be at least 10 and add up to 1000",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_weighted_pool_swap_exact_tokens_for_tokens() -> Result<()> {
+    clear();
+    let amount = 1_000_000;
+    let (pool_block, deployment_ids, _) = test_weighted_pool_init_fixture(amount)?;
+    let block_height = 840_002;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    let amount_in = 10_000;
+    insert_swap_exact_tokens_for_tokens(
+        amount_in,
+        vec![
+            deployment_ids.owned_token_1_deployment,
+            deployment_ids.owned_token_3_deployment,
+        ],
+        0,
+        &mut swap_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&swap_block, block_height)?;
+
+    let expected = weighted::get_amount_out(
+        amount_in,
+        amount * 4,
+        amount,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+        WEIGHTED_POOL_WEIGHT_A,
+        weighted::WEIGHT_DENOMINATOR - WEIGHTED_POOL_WEIGHT_A,
+    )?;
+    // both tokens start at the same price, so the output is close to the input minus the fee
+    assert!(expected < amount_in * 990 / 1000 && expected > amount_in * 980 / 1000);
+    let sheet = get_last_outpoint_sheet(&swap_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_3_deployment.into()),
+        expected
+    );
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_weighted_pool_swap_tokens_for_exact_tokens() -> Result<()> {
+    clear();
+    let amount = 1_000_000;
+    let (pool_block, deployment_ids, _) = test_weighted_pool_init_fixture(amount)?;
+    let block_height = 840_002;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    let (amount_to_swap, amount_out) = (20_000, 10_000);
+    let amount_in_required = weighted::get_amount_in(
+        amount_out,
+        amount,
+        amount * 4,
+        DEFAULT_TOTAL_FEE_AMOUNT_PER_1000,
+        weighted::WEIGHT_DENOMINATOR - WEIGHTED_POOL_WEIGHT_A,
+        WEIGHTED_POOL_WEIGHT_A,
+    )?;
+    insert_swap_tokens_for_exact_tokens_txs(
+        amount_to_swap,
+        vec![
+            deployment_ids.owned_token_3_deployment,
+            deployment_ids.owned_token_1_deployment,
+        ],
+        amount_out,
+        amount_to_swap,
+        &mut swap_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        &deployment_ids,
+    );
+    index_block(&swap_block, block_height)?;
+
+    let sheet = get_last_outpoint_sheet(&swap_block)?;
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_1_deployment.into()),
+        amount_out
+    );
+    assert_eq!(
+        sheet.get_cached(&deployment_ids.owned_token_3_deployment.into()),
+        amount_to_swap - amount_in_required
+    );
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_weighted_pool_low_level_swap_fail() -> Result<()> {
+    clear();
+    let (pool_block, deployment_ids, pool) = test_weighted_pool_init_fixture(1_000_000)?;
+    let block_height = 840_002;
+    let mut swap_block = create_block_with_coinbase_tx(block_height);
+    // both tokens have the same price, so taking out as much as was sent ignores the fee
+    insert_low_level_swap_txs(
+        vec![ProtostoneEdict {
+            id: deployment_ids.owned_token_1_deployment.into(),
+            amount: 10000,
+            output: 0,
+        }],
+        &mut swap_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+        pool,
+        0,
+        10000,
+        AlkaneId::new(0, 0),
+        vec![],
+    );
+    index_block(&swap_block, block_height)?;
+    assert_revert_context(
+        &OutPoint {
+            txid: swap_block.txdata.last().unwrap().compute_txid(),
+            vout: 5,
+        },
+        "ALKANES: revert: Error: V is not increasing",
+    )?;
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_weighted_pool_oracle_uses_weighted_price() -> Result<()> {
+    clear();
+    let (pool_block, _, pool) = test_weighted_pool_init_fixture(1_000_000)?;
+    let mut consult_block = create_block_with_coinbase_tx(840_002);
+    consult_block.header.time = pool_block.header.time + 100;
+    insert_consult_txs(
+        pool,
+        vec![0],
+        &mut consult_block,
+        OutPoint {
+            txid: pool_block.txdata.last().unwrap().compute_txid(),
+            vout: 0,
+        },
+    );
+    index_block(&consult_block, 840_002)?;
+
+    // the 80/20 pool holds four times more token 1 than token 3 at a price of one, where the
+    // reserve ratio alone would read a quarter
+    let one = U256::from(1) << U256::from(PRECISION);
+    let result = get_consult_result(&consult_block)?;
+    assert_eq!(result[0], (one * U256::from(100), one * U256::from(100)));
+    Ok(())
+}